@@ -5,6 +5,7 @@ extern crate mech_core;
 
 use test::Bencher;
 use mech_core::{Quantity, ToQuantity, QuantityMath, make_quantity};
+use mech_core::{Lane, add_integers, multiply_integers};
 
 #[bench]
 fn quantity_add(b:&mut Bencher) {
@@ -94,4 +95,41 @@ fn bench_quantity_normal_add_100000(b:&mut Bencher) {
             test::black_box(x * y);
         }
      });
-}
\ No newline at end of file
+}
+
+#[bench]
+fn quantity_add_1000(b:&mut Bencher) {
+    let x: Vec<Quantity> = (0..1000).map(|i| make_quantity(i, 0, 0)).collect();
+    let y: Vec<Quantity> = (0..1000).map(|i| make_quantity(-i, 0, 0)).collect();
+    b.iter(|| {
+        let sum: Vec<Quantity> = x.iter().zip(y.iter()).map(|(a, b)| a.add(*b).unwrap()).collect();
+        test::black_box(sum);
+    });
+}
+
+#[bench]
+fn integer_kernel_add_1000(b:&mut Bencher) {
+    let x: Vec<i64> = (0..1000).collect();
+    let y: Vec<i64> = (0..1000).map(|i| -i).collect();
+    b.iter(|| {
+        test::black_box(add_integers(Lane::Column(&x), Lane::Column(&y)));
+    });
+}
+
+#[bench]
+fn quantity_multiply_1000(b:&mut Bencher) {
+    let x: Vec<Quantity> = (0..1000).map(|i| make_quantity(i, 0, 0)).collect();
+    let y = make_quantity(3, 0, 0);
+    b.iter(|| {
+        let product: Vec<Quantity> = x.iter().map(|a| a.multiply(y).unwrap()).collect();
+        test::black_box(product);
+    });
+}
+
+#[bench]
+fn integer_kernel_multiply_1000(b:&mut Bencher) {
+    let x: Vec<i64> = (0..1000).collect();
+    b.iter(|| {
+        test::black_box(multiply_integers(Lane::Column(&x), Lane::Scalar(3)));
+    });
+}
@@ -4,7 +4,8 @@ extern crate test;
 extern crate mech_core;
 
 use test::Bencher;
-use mech_core::{Table, Value, Index};
+use mech_core::{Table, Value, Index, Column, make_quantity};
+use mech_core::{math_add, compare_less_than};
 
 #[bench]
 fn make_table(b: &mut Bencher) {
@@ -35,4 +36,62 @@ fn set_clear_cell(b: &mut Bencher) {
         table.set_cell(&Index::Index(1), &Index::Index(1), Value::from_u64(100));
         table.clear_cell(&Index::Index(1), &Index::Index(1));
     });
-}
\ No newline at end of file
+}
+
+fn number_column(rows: i64, range: i64) -> Table {
+    let mut table = Table::new(0, rows as u64, 1);
+    for row in 0..rows {
+        table.data[0].set(row as usize, Value::from_quantity(make_quantity(row, range, 0)));
+    }
+    table
+}
+
+// Whole numbers go through the columnar kernels
+#[bench]
+fn add_columns_1000(b: &mut Bencher) {
+    let (lhs, rhs) = (number_column(1000, 0), number_column(1000, 0));
+    let empty = Column::new(0);
+    b.iter(|| {
+        let mut out = Table::new(0, 0, 0);
+        let mut errors = Vec::new();
+        math_add(&lhs, &empty, &empty, &rhs, &empty, &empty, &mut out, &mut errors);
+        test::black_box(out);
+    });
+}
+
+// Numbers with a scale go cell by cell
+#[bench]
+fn add_columns_1000_fallback(b: &mut Bencher) {
+    let (lhs, rhs) = (number_column(1000, -1), number_column(1000, -1));
+    let empty = Column::new(0);
+    b.iter(|| {
+        let mut out = Table::new(0, 0, 0);
+        let mut errors = Vec::new();
+        math_add(&lhs, &empty, &empty, &rhs, &empty, &empty, &mut out, &mut errors);
+        test::black_box(out);
+    });
+}
+
+#[bench]
+fn compare_columns_1000(b: &mut Bencher) {
+    let (lhs, rhs) = (number_column(1000, 0), number_column(1000, -1));
+    let empty = Column::new(0);
+    b.iter(|| {
+        let mut out = Table::new(0, 0, 0);
+        compare_less_than(&lhs, &empty, &empty, &rhs, &empty, &empty, &mut out);
+        test::black_box(out);
+    });
+}
+
+// An empty cell keeps the whole column off the kernels
+#[bench]
+fn compare_columns_1000_fallback(b: &mut Bencher) {
+    let (lhs, mut rhs) = (number_column(1000, 0), number_column(1000, -1));
+    rhs.data[0].set(999, Value::Empty);
+    let empty = Column::new(0);
+    b.iter(|| {
+        let mut out = Table::new(0, 0, 0);
+        compare_less_than(&lhs, &empty, &empty, &rhs, &empty, &empty, &mut out);
+        test::black_box(out);
+    });
+}
@@ -0,0 +1,338 @@
+// # Conflicts
+
+// When several cores write to the same tables, their transactions can cross
+// in flight, and each core sees them in a different order. To make every core
+// end up with the same tables anyway, each write is stamped with a logical
+// clock, and tables can opt in to a merge policy that decides what concurrent
+// writes add up to regardless of the order they arrive in.
+
+// Tables without a policy take changes as they come. Structural changes
+// (new tables, renamed columns, removed tables) always pass straight through.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
+#[cfg(feature = "no-std")] use alloc::collections::BTreeSet;
+#[cfg(not(feature = "no-std"))] use std::collections::BTreeSet;
+use table::{Value, Index};
+use database::Change;
+use errors::ErrorType;
+use quantities::{Quantity, QuantityMath};
+use hashbrown::hash_map::HashMap;
+use core::cmp::{self, Ordering};
+
+// ## Clocks
+
+// A stamp orders writes first by time and then by the site that made them, so
+// no two sites ever make the same stamp.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Stamp {
+  pub time: u64,
+  pub site: u64,
+}
+
+pub trait Clock {
+  // A stamp for a new write, later than any this clock has made or seen
+  fn stamp(&mut self) -> Stamp;
+  // Take note of a stamp from another site
+  fn observe(&mut self, stamp: &Stamp);
+}
+
+pub struct LamportClock {
+  pub site: u64,
+  pub time: u64,
+}
+
+impl LamportClock {
+  pub fn new(site: u64) -> LamportClock {
+    LamportClock {
+      site,
+      time: 0,
+    }
+  }
+}
+
+impl Clock for LamportClock {
+
+  fn stamp(&mut self) -> Stamp {
+    self.time += 1;
+    Stamp{time: self.time, site: self.site}
+  }
+
+  fn observe(&mut self, stamp: &Stamp) {
+    self.time = cmp::max(self.time, stamp.time);
+  }
+
+}
+
+// A hybrid logical clock keeps close to physical time, so last-writer-wins
+// means roughly what it says, but never runs backwards. The top 48 bits of a
+// stamp's time are physical time and the low 16 bits are a logical counter.
+pub struct HybridClock {
+  pub site: u64,
+  pub time: u64,
+  now: Box<dyn FnMut() -> u64>,
+}
+
+const LOGICAL_BITS: u64 = 16;
+
+impl HybridClock {
+  // now reads physical time, in whatever unit the sites agree on
+  pub fn new(site: u64, now: Box<dyn FnMut() -> u64>) -> HybridClock {
+    HybridClock {
+      site,
+      time: 0,
+      now,
+    }
+  }
+}
+
+impl Clock for HybridClock {
+
+  fn stamp(&mut self) -> Stamp {
+    let physical = (self.now)() << LOGICAL_BITS;
+    self.time = cmp::max(self.time + 1, physical);
+    Stamp{time: self.time, site: self.site}
+  }
+
+  fn observe(&mut self, stamp: &Stamp) {
+    self.time = cmp::max(self.time, stamp.time);
+  }
+
+}
+
+// ## Stamped Changes
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stamped {
+  pub stamp: Stamp,
+  pub sequence: u64, // which of its site's writes this is, counting from one
+  pub change: Change,
+}
+
+// A cell's value along with the stamp of the write that put it there
+#[derive(Clone, Debug, PartialEq)]
+pub struct Version {
+  pub value: Value,
+  pub stamp: Stamp,
+}
+
+// ## Merge Policies
+
+pub enum MergePolicy {
+  // The write with the latest stamp wins. Removes are writes of Empty.
+  LastWriterWins,
+  // Each column is a set that only grows. Every value written to a column
+  // is a member, and the column lists the members in a fixed order. Rows
+  // given with a write are ignored, and so are removes.
+  GrowOnlySet,
+  // Each cell is a counter, and every write adds its value to it. Removes
+  // are ignored. A write that can't be added to the count, like one in
+  // another unit, is turned away and reported in the reconciler's errors.
+  Counter,
+  // Concurrent writes to a cell are combined by the given function, which is
+  // handed the current version and the incoming one. It has to give the same
+  // answer whichever way round they come, or cores won't converge.
+  Custom(Resolver),
+}
+
+pub type Resolver = Box<dyn Fn(&Version, &Version) -> Value>;
+
+type Cell = (u64, Index, Index);
+
+// The writes seen from one site, by sequence number. Every write up to the
+// watermark has been seen, so only the ones that arrived early, ahead of a
+// gap, are kept separately.
+struct Seen {
+  watermark: u64,
+  ahead: BTreeSet<u64>,
+}
+
+impl Seen {
+
+  fn new() -> Seen {
+    Seen {
+      watermark: 0,
+      ahead: BTreeSet::new(),
+    }
+  }
+
+  // Whether the write is new, noting it if it is
+  fn insert(&mut self, sequence: u64) -> bool {
+    if sequence <= self.watermark || !self.ahead.insert(sequence) {
+      return false;
+    }
+    while self.ahead.remove(&(self.watermark + 1)) {
+      self.watermark += 1;
+    }
+    true
+  }
+
+}
+
+pub struct Reconciler {
+  pub policies: HashMap<u64, MergePolicy>,
+  versions: HashMap<Cell, Version>,
+  members: HashMap<(u64, Index), Vec<Value>>,
+  counts: HashMap<Cell, Quantity>,
+  seen: HashMap<u64, Seen>,
+  written: u64, // how many local writes have been stamped
+  pub errors: Vec<ErrorType>, // writes that couldn't be merged
+}
+
+impl Reconciler {
+
+  pub fn new() -> Reconciler {
+    Reconciler {
+      policies: HashMap::new(),
+      versions: HashMap::new(),
+      members: HashMap::new(),
+      counts: HashMap::new(),
+      seen: HashMap::new(),
+      written: 0,
+      errors: Vec::new(),
+    }
+  }
+
+  pub fn set_policy(&mut self, table: u64, policy: MergePolicy) {
+    self.policies.insert(table, policy);
+  }
+
+  // Stamp a batch of local changes. Every change in the batch gets its own
+  // stamp and sequence number, so a counter can tell them apart.
+  pub fn stamp(&mut self, clock: &mut dyn Clock, changes: Vec<Change>) -> Vec<Stamped> {
+    let mut stamped = Vec::with_capacity(changes.len());
+    for change in changes {
+      self.written += 1;
+      stamped.push(Stamped{stamp: clock.stamp(), sequence: self.written, change});
+    }
+    stamped
+  }
+
+  // Resolve writes from another site, keeping the local clock ahead of them
+  pub fn receive(&mut self, clock: &mut dyn Clock, writes: &[Stamped]) -> Vec<Change> {
+    for write in writes {
+      clock.observe(&write.stamp);
+    }
+    self.resolve(writes)
+  }
+
+  // Work out what stamped writes, local or remote, do to the tables. The
+  // changes that come back are ready to hand to Core::process_changes.
+  // Writes that have been resolved before are skipped, so it's safe to
+  // deliver a write more than once.
+  pub fn resolve(&mut self, writes: &[Stamped]) -> Vec<Change> {
+    let mut resolved = Vec::new();
+    for write in writes {
+      if !self.seen.entry(write.stamp.site).or_insert_with(Seen::new).insert(write.sequence) {
+        continue;
+      }
+      let (table, row, column, value) = match &write.change {
+        Change::Set{table, row, column, value} => (*table, *row, *column, value.clone()),
+        Change::Remove{table, row, column, ..} => (*table, *row, *column, Value::Empty),
+        change => {
+          resolved.push(change.clone());
+          continue;
+        }
+      };
+      match self.policies.get(&table) {
+        None => resolved.push(write.change.clone()),
+        Some(MergePolicy::LastWriterWins) => {
+          let incoming = Version{value, stamp: write.stamp};
+          let newer = match self.versions.get(&(table, row, column)) {
+            Some(current) => incoming.stamp > current.stamp,
+            None => true,
+          };
+          if newer {
+            resolved.push(write_cell(table, row, column, incoming.value.clone()));
+            self.versions.insert((table, row, column), incoming);
+          }
+        },
+        Some(MergePolicy::Custom(resolver)) => {
+          let incoming = Version{value, stamp: write.stamp};
+          let merged = match self.versions.get(&(table, row, column)) {
+            Some(current) => Version{
+              value: resolver(current, &incoming),
+              stamp: cmp::max(current.stamp, incoming.stamp),
+            },
+            None => incoming,
+          };
+          resolved.push(write_cell(table, row, column, merged.value.clone()));
+          self.versions.insert((table, row, column), merged);
+        },
+        Some(MergePolicy::GrowOnlySet) => {
+          if value == Value::Empty {
+            continue;
+          }
+          let members = self.members.entry((table, column)).or_insert(Vec::new());
+          match members.binary_search_by(|member| compare_values(member, &value)) {
+            Ok(_) => (),
+            Err(ix) => {
+              members.insert(ix, value);
+              // Everything from the new member down has moved along a row
+              for (offset, member) in members[ix..].iter().enumerate() {
+                let row = Index::Index((ix + offset) as u64 + 1);
+                resolved.push(write_cell(table, row, column, member.clone()));
+              }
+            },
+          }
+        },
+        Some(MergePolicy::Counter) => {
+          let amount = match value.as_quantity() {
+            Some(amount) => amount,
+            None => continue,
+          };
+          let total = match self.counts.get(&(table, row, column)) {
+            Some(total) => match total.add(amount) {
+              Ok(sum) => sum,
+              Err(error) => {
+                self.errors.push(error);
+                continue;
+              },
+            },
+            None => amount,
+          };
+          self.counts.insert((table, row, column), total);
+          resolved.push(write_cell(table, row, column, Value::from_quantity(total)));
+        },
+      }
+    }
+    resolved
+  }
+
+}
+
+impl Default for Reconciler {
+  fn default() -> Reconciler {
+    Reconciler::new()
+  }
+}
+
+fn write_cell(table: u64, row: Index, column: Index, value: Value) -> Change {
+  match value {
+    Value::Empty => Change::Remove{table, row, column, value},
+    value => Change::Set{table, row, column, value},
+  }
+}
+
+// A fixed order over values, so sets list their members the same way on
+// every core
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+  fn rank(value: &Value) -> u8 {
+    match value {
+      Value::Number(_) => 0,
+      Value::String(_) => 1,
+      Value::Bool(_) => 2,
+      Value::Reference(_) => 3,
+      Value::Empty => 4,
+    }
+  }
+  match (a, b) {
+    (Value::Number(a), Value::Number(b)) => a.cmp(b),
+    (Value::String(a), Value::String(b)) => a.cmp(b),
+    (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+    (Value::Reference(a), Value::Reference(b)) => a.cmp(b),
+    _ => rank(a).cmp(&rank(b)),
+  }
+}
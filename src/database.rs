@@ -4,10 +4,15 @@
 
 #[cfg(feature = "no-std")] use alloc::string::String;
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
 use core::fmt;
-use table::{Value, Table, Index};
+use core::mem;
+use table::{Value, Table, Index, Column};
 use indexes::TableIndex;
+use schema::Schema;
+use errors::ErrorType;
 use hashbrown::hash_map::{HashMap, Entry};
+use hashbrown::hash_set::HashSet;
 
 // ## Changes
 
@@ -17,7 +22,25 @@ pub enum Change {
   Remove{table: u64, row: Index, column: Index, value: Value},
   NewTable{id: u64, rows: u64, columns: u64},
   RenameColumn{table: u64, column_ix: u64, column_alias: u64},
-  RemoveTable{id: u64, rows: u64, columns: u64},
+  // The contents are the changes that fill in a new table of the same shape.
+  // They're filled in from the table that was actually removed when the 
+  // change is saved, so the change alone is enough to put it back.
+  RemoveTable{id: u64, rows: u64, columns: u64, contents: Vec<Change>},
+}
+
+impl Change {
+
+  // The table a change is made to
+  pub fn table(&self) -> u64 {
+    match self {
+      Change::Set{table, ..} |
+      Change::Remove{table, ..} |
+      Change::RenameColumn{table, ..} => *table,
+      Change::NewTable{id, ..} |
+      Change::RemoveTable{id, ..} => *id,
+    }
+  }
+
 }
 
 impl fmt::Debug for Change {
@@ -28,14 +51,14 @@ impl fmt::Debug for Change {
       Change::Remove{table, row, column, value} => write!(f, "<remove> #{:#x} [{:?} {:?}: {:?}]", table, row, column, value),
       Change::NewTable{id, rows, columns} => write!(f, "<newtable> #{:#x} [{:?} x {:?}]", id, rows, columns),
       Change::RenameColumn{table, column_ix, column_alias} => write!(f, "<renamecolumn> #{:#x} {:#x} -> {:#x}", table, column_ix, column_alias),
-      Change::RemoveTable{id, rows, columns} => write!(f, "<removetable> #{:#x} [{:?} x {:?}]", id, rows, columns),
+      Change::RemoveTable{id, rows, columns, ..} => write!(f, "<removetable> #{:#x} [{:?} x {:?}]", id, rows, columns),
     }
   }
 }
   
 // ## Transaction
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
   pub tables: Vec<Change>,
   pub adds: Vec<Change>,
@@ -111,6 +134,63 @@ impl fmt::Debug for Transaction {
   }
 }
 
+// ## Undo
+
+// Some changes can't be taken back using only what's in the change log. When 
+// one of those is saved, whatever else is needed to undo it is kept alongside,
+// keyed by the number of the change.
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Undo {
+  // The table had to grow to fit a Set. This is the shape it had before.
+  Shape{rows: u64, columns: u64},
+}
+
+// ## Archive
+
+// Once the change log fills up, the oldest changes are evicted to make room.
+// An archive catches them on the way out, so a Core can rewind further back 
+// than its change log holds. Hosts implement this to spill history to disk or
+// other secondary storage.
+
+pub trait ChangeArchive: fmt::Debug {
+  fn archive(&mut self, number: usize, change: &Change, undo: Option<&Undo>);
+  fn retrieve(&self, number: usize) -> Option<(Change, Option<Undo>)>;
+  fn clear(&mut self);
+}
+
+// Keeps evicted changes in memory, for unlimited rewind at the cost of 
+// unbounded growth.
+
+#[derive(Debug)]
+pub struct MemoryArchive {
+  pub changes: HashMap<usize, (Change, Option<Undo>)>,
+}
+
+impl MemoryArchive {
+  pub fn new() -> MemoryArchive {
+    MemoryArchive {
+      changes: HashMap::new(),
+    }
+  }
+}
+
+impl ChangeArchive for MemoryArchive {
+
+  fn archive(&mut self, number: usize, change: &Change, undo: Option<&Undo>) {
+    self.changes.insert(number, (change.clone(), undo.cloned()));
+  }
+
+  fn retrieve(&self, number: usize) -> Option<(Change, Option<Undo>)> {
+    self.changes.get(&number).cloned()
+  }
+
+  fn clear(&mut self) {
+    self.changes.clear();
+  }
+
+}
+
 // ## Interner
 
 #[derive(Debug)]
@@ -119,10 +199,19 @@ pub struct Interner {
   pub tables: TableIndex,
   pub names: HashMap<u64,String>,
   pub changes: Vec<Change>,
+  pub change_capacity: usize, // how many changes are held in memory
   pub changes_count: usize,
   pub change_pointer: usize, // points at the next available slot in memory that can hold a change
   pub rollover: usize,
   pub last_round: usize,
+  pub undo: HashMap<usize, Undo>,
+  pub archive: Option<Box<dyn ChangeArchive>>,
+  pub first_change: usize, // the number of the change that went into the first slot
+  pub previous: TableIndex, // remembered tables as they were at the last transaction boundary
+  pub remembered: HashSet<u64>,
+  pub changed_tables: HashSet<u64>, // tables changed since the last transaction boundary
+  pub schemas: HashMap<u64, Schema>,
+  pub errors: Vec<ErrorType>, // changes in this transaction turned away because they didn't fit a schema
 }
 
 impl Interner {
@@ -133,10 +222,19 @@ impl Interner {
       tables: TableIndex::new(table_capacity),
       names: HashMap::new(),
       changes: Vec::with_capacity(change_capacity),
+      change_capacity,
       changes_count: 0,
       change_pointer: 0,
       rollover: 0,
       last_round: 0,
+      undo: HashMap::new(),
+      archive: None,
+      first_change: 0,
+      previous: TableIndex::new(0),
+      remembered: HashSet::new(),
+      changed_tables: HashSet::new(),
+      schemas: HashMap::new(),
+      errors: Vec::new(),
     }
   }
 
@@ -145,6 +243,73 @@ impl Interner {
     self.changes.clear();
     self.changes_count = 0;
     self.change_pointer = 0;
+    self.rollover = 0;
+    self.first_change = 0;
+    self.undo.clear();
+    self.previous.clear();
+    self.remembered.clear();
+    self.changed_tables.clear();
+    self.errors.clear();
+    match &mut self.archive {
+      Some(archive) => archive.clear(),
+      None => (),
+    }
+  }
+
+  // Keep the value a table had at the last transaction boundary
+  pub fn remember(&mut self, table: u64) {
+    self.remembered.insert(table);
+    // Make sure it's copied at the next boundary
+    self.changed_tables.insert(table);
+  }
+
+  // A transaction is about to start, so what the remembered tables look like
+  // now is what they looked like at the last boundary. Only the ones that
+  // changed since then are copied. Errors are only kept for the transaction
+  // they happened in.
+  pub fn mark_boundary(&mut self) {
+    self.errors.clear();
+    let changed = mem::replace(&mut self.changed_tables, HashSet::new());
+    for table in changed.iter() {
+      if !self.remembered.contains(table) {
+        continue;
+      }
+      match self.tables.get(*table) {
+        Some(table_ref) => {
+          self.previous.remove(table);
+          self.previous.insert(table_ref.clone());
+        },
+        None => {
+          self.previous.remove(table);
+        },
+      }
+    }
+  }
+
+  // Declare what a table's columns hold. The columns are named after the
+  // schema, and from then on changes that don't fit are turned away.
+  pub fn declare_schema(&mut self, schema: Schema) {
+    let table = schema.table;
+    self.schemas.insert(table, schema);
+    if self.tables.contains(table) {
+      self.name_columns(table);
+    }
+  }
+
+  // Intern changes one at a time, in the order given
+  pub fn process_changes(&mut self, changes: &[Change]) {
+    for change in changes {
+      self.intern_change(change);
+    }
+  }
+
+  // Intern changes that were already checked and logged somewhere else, like
+  // on a primary. Schemas aren't applied again, because the names and 
+  // defaults they lead to are among the changes already.
+  pub fn process_logged_changes(&mut self, changes: &[Change]) {
+    let schemas = mem::replace(&mut self.schemas, HashMap::new());
+    self.process_changes(changes);
+    self.schemas = schemas;
   }
 
   pub fn process_transaction(&mut self, txn: &Transaction) {
@@ -169,89 +334,363 @@ impl Interner {
   fn intern_change(&mut self, change: &Change) {  
     match change {
       Change::Set{table, row, column, value} => {
+        // Sets replayed while moving through time went in once already.
+        if self.offset == 0 {
+          match self.schemas.get(table).map(|schema| schema.check(column, value)) {
+            Some(Err(error)) => {
+              self.errors.push(error);
+              return;
+            },
+            _ => (),
+          }
+        }
         let mut changed = false;
         let mut alias: Option<u64> = None;
+        let mut old_value = Value::Empty;
+        let mut old_shape: Option<(u64, u64)> = None;
         match self.tables.get_mut(*table) {
           Some(table_ref) => {
             alias = table_ref.get_column_alias(column);
-            let old_value = table_ref.set_cell(&row, &column, value.clone());
-            if old_value != *value {
-              changed = true;
-            }
-            if self.offset == 0 && changed == true {
-              match old_value {
-                Value::Empty => (),
-                // Save a remove so that we can rewind
-                _ => self.save_change(&Change::Remove{table: *table, row: row.clone(), column: column.clone(), value: old_value}),
-              }
+            let shape = (table_ref.rows, table_ref.columns);
+            old_value = table_ref.set_cell(&row, &column, value.clone());
+            if shape != (table_ref.rows, table_ref.columns) {
+              old_shape = Some(shape);
             }
-            
+            changed = old_value != *value || old_shape != None;
           }
           None => (),
         };
         if changed == true {
-          match alias {
-            Some(id) => {
-              self.tables.changed_this_round.insert((table.clone(), Index::Alias(id)))
-            },
-            _ => false,
-          };
-          self.tables.changed_this_round.insert((table.clone(), column.clone()));
-          self.tables.changed_this_round.insert((table.clone(), Index::Index(0)));
+          if self.offset == 0 {
+            match old_value {
+              Value::Empty => (),
+              // Save a remove so that we can rewind
+              _ => {
+                self.save_change(&Change::Remove{table: *table, row: row.clone(), column: column.clone(), value: old_value}, None);
+              },
+            }
+            let undo = match old_shape {
+              Some((rows, columns)) => Some(Undo::Shape{rows, columns}),
+              None => None,
+            };
+            self.save_change(change, undo);
+          }
+          self.mark_changed(*table, column, alias);
+          self.mark_row(*table, row, column, old_shape.is_some());
+          if let Some((rows, _)) = old_shape {
+            self.fill_defaults(*table, rows + 1);
+          }
         }
       },
-      Change::Remove{table, row, column, value} => {
-        /*
-        match value {
-          Value::Empty => (),
-          _ => {
-            match self.tables.get_mut(*table) {
-              Some(table_ref) => {
-                table_ref.set_cell_by_id(*row as usize, *column as usize, Value::Empty);
-              }
-              None => (),
-            };            
-          },
+      Change::Remove{table, row, column, value: _} => {
+        // Emptying a cell has to be allowed too. Removes replayed while moving 
+        // through time went in once already.
+        if self.offset == 0 {
+          match self.schemas.get(table).map(|schema| schema.check(column, &Value::Empty)) {
+            Some(Err(error)) => {
+              self.errors.push(error);
+              return;
+            },
+            _ => (),
+          }
+        }
+        let mut alias: Option<u64> = None;
+        let mut old_value = Value::Empty;
+        match self.tables.get_mut(*table) {
+          Some(table_ref) => {
+            match (table_ref.get_row_index(row), table_ref.get_column_index(column)) {
+              (Some(row_ix), Some(column_ix)) if row_ix > 0 && row_ix <= table_ref.rows &&
+                                                 column_ix > 0 && column_ix <= table_ref.columns => {
+                alias = table_ref.get_column_alias(column);
+                old_value = table_ref.set_cell(&row, &column, Value::Empty);
+              },
+              _ => (),
+            }
+          }
+          None => (),
         };
-        self.tables.changed_this_round.insert((*table as usize, *column as usize));
-        */
+        if old_value != Value::Empty {
+          // Save the value that was actually removed, so that rewinding puts 
+          // back what was there.
+          if self.offset == 0 {
+            self.save_change(&Change::Remove{table: *table, row: row.clone(), column: column.clone(), value: old_value}, None);
+          }
+          self.mark_changed(*table, column, alias);
+          self.mark_row(*table, row, column, false);
+        }
       },
       Change::NewTable{id, rows, columns } => {
-        self.tables.insert(Table::new(*id, *rows, *columns));
+        if !self.tables.contains(*id) {
+          self.tables.insert(Table::new(*id, *rows, *columns));
+          self.tables.changed_rows.insert(*id, None);
+          self.changed_tables.insert(*id);
+          if self.offset == 0 {
+            self.save_change(change, None);
+          }
+          if self.schemas.contains_key(id) {
+            self.name_columns(*id);
+            self.fill_defaults(*id, 1);
+          }
+        }
       }
-      Change::RemoveTable{id, rows: _, columns: _} => {
-        self.tables.remove(&id);
+      Change::RemoveTable{id, ..} => {
+        match self.tables.remove(&id) {
+          Some(table) => {
+            self.tables.changed_rows.insert(*id, None);
+            self.changed_tables.insert(*id);
+            // Log the whole table so anything replaying the change can recreate it exactly
+            if self.offset == 0 {
+              let contents = Table::new(*id, table.rows, table.columns).diff(&table);
+              self.save_change(&Change::RemoveTable{id: *id, rows: table.rows, columns: table.columns, contents}, None);
+            }
+          },
+          None => (),
+        }
       }
       Change::RenameColumn{table, column_ix, column_alias} => { 
         match self.tables.get_mut(*table) {
           Some(table_ref) => {
-            table_ref.set_column_alias(*column_alias, *column_ix);
+            if table_ref.get_column_index(&Index::Alias(*column_alias)) == None {
+              table_ref.set_column_alias(*column_alias, *column_ix);
+              if self.offset == 0 {
+                self.save_change(change, None);
+              }
+            }
           }
           None => (),
         };
         self.tables.changed_this_round.insert((*table, Index::Alias(*column_alias)));
+        self.changed_tables.insert(*table);
       },
     }
-    if self.offset == 0 {
-      self.save_change(change);
-    }    
+  }
+
+  // Give a table's columns the names in its schema
+  fn name_columns(&mut self, table: u64) {
+    let renames: Vec<Change> = match self.schemas.get(&table) {
+      Some(schema) => schema.columns.iter().enumerate().map(|(ix, column)| {
+        Change::RenameColumn{table, column_ix: ix as u64 + 1, column_alias: column.name}
+      }).collect(),
+      None => return,
+    };
+    for rename in renames.iter() {
+      self.intern_change(rename);
+    }
+  }
+
+  // Set empty cells in declared columns to their defaults, from a row down.
+  // They go in as ordinary changes, so they're logged and rewound like any 
+  // other.
+  fn fill_defaults(&mut self, table: u64, from_row: u64) {
+    let mut defaults = Vec::new();
+    match (self.schemas.get(&table), self.tables.get(table)) {
+      (Some(schema), Some(table_ref)) => {
+        for (ix, column) in schema.columns.iter().enumerate() {
+          let default = match &column.default {
+            Some(default) => default,
+            None => continue,
+          };
+          let column_ix = Index::Index(ix as u64 + 1);
+          for row in from_row..table_ref.rows + 1 {
+            match table_ref.get_value(&Index::Index(row), &column_ix) {
+              Some(Value::Empty) | None => {
+                defaults.push(Change::Set{table, row: Index::Index(row), column: column_ix.clone(), value: default.clone()});
+              },
+              _ => (),
+            }
+          }
+        }
+      },
+      _ => return,
+    }
+    for default in defaults.iter() {
+      self.intern_change(default);
+    }
+  }
+
+  fn mark_changed(&mut self, table: u64, column: &Index, alias: Option<u64>) {
+    match alias {
+      Some(id) => {
+        self.tables.changed_this_round.insert((table, Index::Alias(id)));
+      },
+      _ => (),
+    };
+    self.tables.changed_this_round.insert((table, column.clone()));
+    self.tables.changed_this_round.insert((table, Index::Index(0)));
+    self.changed_tables.insert(table);
+  }
+
+  fn mark_row(&mut self, table: u64, row: &Index, column: &Index, reshaped: bool) {
+    let rows = self.tables.changed_rows.entry(table).or_insert(Some(Vec::new()));
+    match (rows, row) {
+      (Some(rows), Index::Index(row)) if *row > 0 && !reshaped => rows.push((*row as usize - 1, column.clone())),
+      (rows, _) => *rows = None,
+    }
+  }
+
+  // Undo a change, putting the tables back the way they were just before it 
+  // was interned. Nothing is saved while undoing.
+  pub fn revert_change(&mut self, change: &Change, undo: Option<&Undo>) {
+    self.changed_tables.insert(change.table());
+    match change {
+      Change::Set{table, row, column, value: _} => {
+        match self.tables.get_mut(*table) {
+          Some(table_ref) => {
+            table_ref.set_cell(&row, &column, Value::Empty);
+            match undo {
+              Some(Undo::Shape{rows, columns}) => table_ref.shrink_to_fit(*rows, *columns),
+              _ => (),
+            }
+          },
+          None => (),
+        }
+      },
+      Change::Remove{table, row, column, value} => {
+        match self.tables.get_mut(*table) {
+          Some(table_ref) => {
+            table_ref.set_cell(&row, &column, value.clone());
+          },
+          None => (),
+        }
+      },
+      Change::NewTable{id, ..} => {
+        self.tables.remove(&id);
+      },
+      Change::RemoveTable{id, rows, columns, contents} => {
+        let mut table = Table::new(*id, *rows, *columns);
+        for change in contents {
+          match change {
+            Change::RenameColumn{column_ix, column_alias, ..} => table.set_column_alias(*column_alias, *column_ix),
+            Change::Set{row, column, value, ..} => { table.set_cell(row, column, value.clone()); },
+            _ => (),
+          }
+        }
+        self.tables.insert(table);
+      },
+      Change::RenameColumn{table, column_ix: _, column_alias} => {
+        match self.tables.get_mut(*table) {
+          Some(table_ref) => table_ref.remove_column_alias(*column_alias),
+          None => (),
+        }
+      },
+    }
+  }
+
+  // Changes are numbered from the beginning of time. The change log is a ring 
+  // buffer, so it holds only the most recent changes; the change numbered n 
+  // lives in slot (n - first_change) % change_capacity. Changes older than 
+  // that are found in the archive, if there is one.
+  pub fn get_change(&self, number: usize) -> Option<(Change, Option<Undo>)> {
+    if number >= self.changes_count {
+      None
+    } else if number >= self.oldest_change() {
+      let slot = self.slot(number);
+      Some((self.changes[slot].clone(), self.undo.get(&number).cloned()))
+    } else {
+      match &self.archive {
+        Some(archive) => archive.retrieve(number),
+        None => None,
+      }
+    }
+  }
+
+  // The number of the oldest change still held in memory
+  pub fn oldest_change(&self) -> usize {
+    self.changes_count - self.changes.len()
+  }
+
+  fn slot(&self, number: usize) -> usize {
+    (number - self.first_change) % self.change_capacity
+  }
+
+  // Forget every change from the given number on, as if they never happened.
+  // The tables are left as they are.
+  pub fn truncate_history(&mut self, number: usize) {
+    if number >= self.changes_count {
+      return;
+    }
+    let oldest = self.oldest_change();
+    let mut kept = Vec::with_capacity(self.change_capacity);
+    for n in oldest..number {
+      kept.push(self.changes[self.slot(n)].clone());
+    }
+    self.first_change = if number > oldest { oldest } else { number };
+    self.changes = kept;
+    self.changes_count = number;
+    self.undo.retain(|n, _| *n < number);
+    let filled = number - self.first_change;
+    self.change_pointer = match (filled, self.change_capacity) {
+      (0, _) | (_, 0) => 0,
+      (filled, capacity) => (filled - 1) % capacity + 1,
+    };
+    self.rollover = match self.change_capacity {
+      0 => 0,
+      capacity if filled > 0 => (filled - 1) / capacity,
+      _ => 0,
+    };
+  }
+
+  // Copy the interner, history and all. The archive stays behind, so the copy
+  // can only reach back as far as the changes held in memory.
+  pub fn branch(&self) -> Interner {
+    let mut changes = Vec::with_capacity(self.change_capacity);
+    changes.extend(self.changes.iter().cloned());
+    Interner {
+      offset: self.offset,
+      tables: self.tables.clone(),
+      names: self.names.clone(),
+      changes,
+      change_capacity: self.change_capacity,
+      changes_count: self.changes_count,
+      change_pointer: self.change_pointer,
+      rollover: self.rollover,
+      last_round: self.last_round,
+      undo: self.undo.clone(),
+      archive: None,
+      first_change: self.first_change,
+      previous: self.previous.clone(),
+      remembered: self.remembered.clone(),
+      changed_tables: self.changed_tables.clone(),
+      schemas: self.schemas.clone(),
+      errors: self.errors.clone(),
+    }
   }
 
   // Save the change. If there's enough room in memory, store it there. 
-  // If not, make room by evicting some old change and throw that on disk. 
-  // For now, we'll make the policy that the oldest record get evicted first.
-  fn save_change(&mut self, change: &Change) {
-    if self.changes.len() < self.changes.capacity() {
-      self.changes.push(change.clone());
-    } else if self.change_pointer == self.changes.capacity() {
-      self.change_pointer = 0;
-      self.rollover += 1;
-      self.changes[self.change_pointer] = change.clone();
+  // If not, make room by evicting the oldest change, and hand it to the 
+  // archive if there is one. Returns the number of the saved change.
+  fn save_change(&mut self, change: &Change, undo: Option<Undo>) -> usize {
+    let number = self.changes_count;
+    let capacity = self.change_capacity;
+    if capacity == 0 {
+      match &mut self.archive {
+        Some(archive) => archive.archive(number, change, undo.as_ref()),
+        None => (),
+      }
     } else {
-      self.changes[self.change_pointer] = change.clone();
+      if self.changes.len() < capacity {
+        self.changes.push(change.clone());
+      } else {
+        let slot = self.slot(number);
+        let evicted = number - capacity;
+        let evicted_undo = self.undo.remove(&evicted);
+        match &mut self.archive {
+          Some(archive) => archive.archive(evicted, &self.changes[slot], evicted_undo.as_ref()),
+          None => (),
+        }
+        self.changes[slot] = change.clone();
+      }
+      match undo {
+        Some(undo) => {
+          self.undo.insert(number, undo);
+        },
+        None => (),
+      }
+      self.change_pointer = self.slot(number) + 1;
+      self.rollover = (number - self.first_change) / capacity;
     }
-    self.change_pointer += 1;
     self.changes_count += 1;
+    number
   }
 
   pub fn get_table(&self, table: u64) -> Option<&Table> {
@@ -262,7 +701,7 @@ impl Interner {
     self.tables.get_mut(table)
   }
 
-  pub fn get_column(&self, table: u64, column: Index) -> Option<&Vec<Value>> {
+  pub fn get_column(&self, table: u64, column: Index) -> Option<Vec<Value>> {
     match self.tables.get(table) {
       Some(stored_table) => {
         match stored_table.get_column(&column) {
@@ -274,6 +713,13 @@ impl Interner {
     }
   }
 
+  pub fn get_column_data(&self, table: u64, column: Index) -> Option<&Column> {
+    match self.tables.get(table) {
+      Some(stored_table) => stored_table.get_column_data(&column),
+      None => None,
+    }
+  }
+
   /*
   pub fn get_cell(&self, table: u64, row_ix: usize, column_ix: usize) -> Option<&Value> {
     match self.tables.get(table) {
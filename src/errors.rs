@@ -4,8 +4,10 @@
 
 // ## Prelude
 
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
 use table::{Index};
-use runtime::Constraint;
+use runtime::{Constraint, Register};
+use schema::Kind;
 
 // ## The Error Struct
 
@@ -21,6 +23,23 @@ pub struct Error {
 pub enum ErrorType {
   MissingAttribute(Index),
   IndexOutOfBounds(((u64, u64), (u64, u64))),
+  // Tables that don't fit together: the size of each, as (rows, columns)
+  DimensionMismatch(((u64, u64), (u64, u64))),
   DuplicateAlias(u64),
   DomainMismatch(u64, u64),
+  UnsupportedWireVersion(u8),
+  MalformedWireData(usize),
+  TimeNotRetained(usize),
+  ChangeNotRetained(usize),
+  ReplicaDiverged(usize),
+  UnknownRemote(u64),
+  DuplicateProvider(u64),
+  NotProvided(u64),
+  // Blocks that feed into each other, and the registers they pass around
+  CycleDetected(Vec<u64>, Vec<Register>),
+  // A feedback loop that didn't settle within the iteration limit
+  Oscillation(Vec<u64>, Vec<Register>),
+  // A value that doesn't match the table's schema: the table, the column, the
+  // kind the schema wants, and the kind that was found. None is an empty cell.
+  TypeMismatch(u64, Index, Kind, Option<Kind>),
 }
\ No newline at end of file
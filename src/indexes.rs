@@ -2,14 +2,16 @@
 
 // ## Prelude
 
-use table::{Value, Table, Index};
+use table::{Value, Table, Index, Aliases};
 #[cfg(feature = "no-std")] use alloc::string::String;
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
 #[cfg(feature = "no-std")] use alloc::fmt;
 #[cfg(not(feature = "no-std"))] use core::fmt;
-use hashbrown::hash_map::{HashMap, Entry};
+use hashbrown::hash_map::HashMap;
 use hashbrown::hash_set::HashSet;
 use errors::ErrorType;
+use database::Change;
+use serde::*;
 
 // ## Hasher
 
@@ -91,8 +93,11 @@ impl Hasher {
 #[derive(Clone, PartialEq)]
 pub struct TableIndex {
   pub map: HashMap<u64, Table>,
-  pub aliases: HashMap<u64, u64>,
+  pub aliases: Aliases,
   pub changed_this_round: HashSet<(u64, Index)>,
+  // The rows and columns that changed this round, for blocks that work from
+  // deltas. None means the table was made, removed, or changed shape.
+  pub changed_rows: HashMap<u64, Option<Vec<(usize, Index)>>>,
 }
 
 impl TableIndex {
@@ -100,8 +105,9 @@ impl TableIndex {
   pub fn new(capacity: usize) -> TableIndex {
     TableIndex {
       map: HashMap::with_capacity(capacity),
-      aliases: HashMap::new(),
+      aliases: Aliases::new(),
       changed_this_round: HashSet::new(),
+      changed_rows: HashMap::new(),
     }
   }
 
@@ -109,6 +115,7 @@ impl TableIndex {
     self.map.clear();
     self.aliases.clear();
     self.changed_this_round.clear();
+    self.changed_rows.clear();
   }
 
   pub fn len(&self) -> usize {
@@ -116,17 +123,40 @@ impl TableIndex {
   }
 
   pub fn add_alias(&mut self, table: u64, alias: u64) -> Result<(),ErrorType> {
-    match self.aliases.entry(alias) {
-      Entry::Occupied(_) => {
-        Err(ErrorType::DuplicateAlias(alias))
-      },
-      Entry::Vacant(v) => {    
-        v.insert(table);
-        Ok(())
-      },
+    if self.aliases.contains_key(&alias) {
+      Err(ErrorType::DuplicateAlias(alias))
+    } else {
+      self.aliases.insert(alias, table);
+      Ok(())
     }
   }
 
+  // The changes that turn this index into the other one. Tables only this one
+  // has are removed, tables only the other has are created and filled, and 
+  // tables in both are diffed. Changes come out in table id order.
+  pub fn diff(&self, other: &TableIndex) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut ids: Vec<&u64> = self.map.keys().chain(other.map.keys().filter(|id| !self.map.contains_key(id))).collect();
+    ids.sort();
+    for id in ids {
+      match (self.map.get(id), other.map.get(id)) {
+        (Some(table), None) => {
+          let contents = Table::new(*id, table.rows, table.columns).diff(table);
+          changes.push(Change::RemoveTable{id: *id, rows: table.rows, columns: table.columns, contents});
+        },
+        (None, Some(table)) => {
+          changes.push(Change::NewTable{id: *id, rows: table.rows, columns: table.columns});
+          changes.extend(Table::new(*id, table.rows, table.columns).diff(table));
+        },
+        (Some(table), Some(other_table)) => {
+          changes.extend(table.diff(other_table));
+        },
+        (None, None) => (),
+      }
+    }
+    changes
+  }
+
   pub fn get(&self, table_id: u64) -> Option<&Table> {
     match self.map.get(&table_id) {
       Some(table) => Some(table),
@@ -161,10 +191,65 @@ impl TableIndex {
     self.map.contains_key(&table)
   }
 
-  pub fn remove(&mut self, table: &u64) {
-    self.map.remove(&table);
+  pub fn remove(&mut self, table: &u64) -> Option<Table> {
+    self.map.remove(&table)
+  }
+
+}
+
+// Tables are written out in id order, and the changed set in a fixed order, so 
+// that the same index always serializes to the same bytes.
+
+#[derive(Serialize)]
+#[serde(rename = "TableIndex")]
+struct SerializedTableIndex<'a> {
+  tables: Vec<&'a Table>,
+  aliases: &'a Aliases,
+  changed_this_round: Vec<&'a (u64, Index)>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "TableIndex")]
+struct DeserializedTableIndex {
+  tables: Vec<Table>,
+  aliases: Aliases,
+  changed_this_round: Vec<(u64, Index)>,
+}
+
+impl Serialize for TableIndex {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    let mut tables: Vec<&Table> = self.map.values().collect();
+    tables.sort_by_key(|table| table.id);
+    let mut changed_this_round: Vec<&(u64, Index)> = self.changed_this_round.iter().collect();
+    changed_this_round.sort_by_key(|(table, index)| match index {
+      Index::Index(ix) => (*table, 0, *ix),
+      Index::Alias(alias) => (*table, 1, *alias),
+    });
+    SerializedTableIndex {
+      tables,
+      aliases: &self.aliases,
+      changed_this_round,
+    }.serialize(serializer)
   }
+}
 
+impl<'de> Deserialize<'de> for TableIndex {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let deserialized = DeserializedTableIndex::deserialize(deserializer)?;
+    let mut index = TableIndex::new(deserialized.tables.len());
+    for table in deserialized.tables {
+      index.map.insert(table.id, table);
+    }
+    index.aliases = deserialized.aliases;
+    index.changed_this_round.extend(deserialized.changed_this_round);
+    Ok(index)
+  }
 }
 
 impl fmt::Debug for TableIndex {
@@ -0,0 +1,241 @@
+// # Kernels
+
+// Columnar kernels for arithmetic, comparison and logic. When whole tables
+// are used, and every cell in a column holds the same kind of value, the
+// column's typed storage is unpacked into a contiguous slice and the operation
+// runs as a tight loop over slices. The loops don't branch on the Value enum,
+// so the compiler can vectorize them. Anything else goes through the
+// cell-by-cell Value path in operations.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+use table::{Table, Column};
+use core::cmp;
+use quantities::{Quantity, QuantityMath, make_quantity};
+
+// Whole numbers are only worked on as integers when the result is sure to
+// fit in a quantity's mantissa, so it comes out the same as quantity math.
+pub const SUM_LIMIT: i64 = 1 << 46;
+pub const PRODUCT_LIMIT: i64 = 1 << 23;
+pub const QUOTIENT_LIMIT: i64 = 1 << 34;
+
+// ## Lanes
+
+// One side of an operation: a whole column, or a scalar that goes with every
+// element on the other side
+pub enum Lane<'a, T: 'a> {
+  Column(&'a [T]),
+  Scalar(T),
+}
+
+#[inline(always)]
+fn zip_lanes<T: Copy, U, F: Fn(T, T) -> U>(lhs: Lane<T>, rhs: Lane<T>, f: F) -> Vec<U> {
+  match (lhs, rhs) {
+    (Lane::Column(x), Lane::Column(y)) => x.iter().zip(y.iter()).map(|(a, b)| f(*a, *b)).collect(),
+    (Lane::Scalar(a), Lane::Column(y)) => y.iter().map(|b| f(a, *b)).collect(),
+    (Lane::Column(x), Lane::Scalar(b)) => x.iter().map(|a| f(*a, b)).collect(),
+    (Lane::Scalar(a), Lane::Scalar(b)) => vec![f(a, b)],
+  }
+}
+
+// ## Typed Columns
+
+// Mantissas of a column of whole numbers with no unit, each smaller than the
+// limit in magnitude
+pub fn integer_column(column: &[Quantity], limit: i64) -> Option<Vec<i64>> {
+  let mut integers = Vec::with_capacity(column.len());
+  for x in column {
+    if x.range() != 0 || x.domain() != 0 {
+      return None;
+    }
+    let mantissa = x.mantissa();
+    if mantissa <= -limit || mantissa >= limit {
+      return None;
+    }
+    integers.push(mantissa);
+  }
+  Some(integers)
+}
+
+pub fn float_column(column: &[Quantity]) -> Option<Vec<f64>> {
+  Some(column.iter().map(|x| x.to_float()).collect())
+}
+
+pub fn bool_column(column: &[bool]) -> Option<Vec<bool>> {
+  Some(column.to_vec())
+}
+
+// ## Slice Kernels
+
+pub fn add_integers(lhs: Lane<i64>, rhs: Lane<i64>) -> Vec<i64> {
+  zip_lanes(lhs, rhs, |a, b| a + b)
+}
+
+pub fn subtract_integers(lhs: Lane<i64>, rhs: Lane<i64>) -> Vec<i64> {
+  zip_lanes(lhs, rhs, |a, b| a - b)
+}
+
+pub fn multiply_integers(lhs: Lane<i64>, rhs: Lane<i64>) -> Vec<i64> {
+  zip_lanes(lhs, rhs, |a, b| a * b)
+}
+
+// Quotients keep four decimal places, the same as quantity division
+pub fn divide_integers(lhs: Lane<i64>, rhs: Lane<i64>) -> Vec<i64> {
+  zip_lanes(lhs, rhs, |a, b| a * 10000 / b)
+}
+
+pub fn less_than_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a < b)
+}
+
+pub fn greater_than_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a > b)
+}
+
+// Quantity comparison says a non-negative number is <= a negative one and
+// not the other way around. That's kept here so both paths agree.
+pub fn less_than_equal_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| {
+    let signs_differ = (a < 0.0) != (b < 0.0);
+    (signs_differ & (b < 0.0)) | (!signs_differ & (a <= b))
+  })
+}
+
+pub fn greater_than_equal_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a >= b)
+}
+
+pub fn equal_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a == b)
+}
+
+pub fn not_equal_floats(lhs: Lane<f64>, rhs: Lane<f64>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a != b)
+}
+
+pub fn and_bools(lhs: Lane<bool>, rhs: Lane<bool>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a & b)
+}
+
+pub fn or_bools(lhs: Lane<bool>, rhs: Lane<bool>) -> Vec<bool> {
+  zip_lanes(lhs, rhs, |a, b| a | b)
+}
+
+// ## Table Kernels
+
+// Each of these runs an operation over whole tables. They return false,
+// leaving the output alone, if the tables don't line up or a column holds
+// anything the kernel doesn't handle.
+
+fn run_kernel<T, U, Unpack, Pack>(lhs: &Table, rhs: &Table, out: &mut Table, unpack: Unpack,
+                                  kernel: fn(Lane<T>, Lane<T>) -> Vec<U>, pack: Pack) -> bool
+  where T: Copy, Unpack: Fn(&Column, usize) -> Option<Vec<T>>, Pack: Fn(Vec<U>) -> Column {
+  let same_shape = lhs.rows == rhs.rows && lhs.columns == rhs.columns;
+  let lhs_is_scalar = !same_shape && lhs.rows == 1 && lhs.columns == 1;
+  let rhs_is_scalar = !same_shape && !lhs_is_scalar && rhs.rows == 1 && rhs.columns == 1;
+  let (rows, columns) = if same_shape || rhs_is_scalar { (lhs.rows, lhs.columns) }
+                        else if lhs_is_scalar { (rhs.rows, rhs.columns) }
+                        else { return false };
+  let unpack_table = |table: &Table, scalar: bool| -> Option<Vec<Vec<T>>> {
+    let used = if scalar { 1 } else { columns as usize };
+    let height = if scalar { 1 } else { rows as usize };
+    let mut unpacked = Vec::with_capacity(used);
+    for column in table.data.iter().take(used) {
+      unpacked.push(unpack(column, height)?);
+    }
+    Some(unpacked)
+  };
+  let lhs_columns = match unpack_table(lhs, lhs_is_scalar) {
+    Some(columns) => columns,
+    None => return false,
+  };
+  let rhs_columns = match unpack_table(rhs, rhs_is_scalar) {
+    Some(columns) => columns,
+    None => return false,
+  };
+  // An empty output is filled a column at a time instead of a cell at a time
+  let fresh = out.rows == 0 && out.columns == 0;
+  if fresh {
+    out.data.clear();
+  }
+  out.grow_to_fit(if fresh { 0 } else { rows }, columns);
+  for i in 0..columns as usize {
+    let lhs_lane = if lhs_is_scalar { Lane::Scalar(lhs_columns[0][0]) }
+                   else { Lane::Column(&lhs_columns[i][..]) };
+    let rhs_lane = if rhs_is_scalar { Lane::Scalar(rhs_columns[0][0]) }
+                   else { Lane::Column(&rhs_columns[i][..]) };
+    let results = pack(kernel(lhs_lane, rhs_lane));
+    if fresh {
+      out.data[i] = results;
+    } else {
+      for (j, result) in results.iter().enumerate() {
+        out.data[i].set(j, result);
+      }
+    }
+  }
+  out.rows = cmp::max(out.rows, rows);
+  true
+}
+
+fn integer_math(lhs: &Table, rhs: &Table, out: &mut Table, limit: i64, range: i64,
+                kernel: fn(Lane<i64>, Lane<i64>) -> Vec<i64>) -> bool {
+  run_kernel(lhs, rhs, out, |column, height| integer_column(&column.numbers()?[..height], limit), kernel,
+             |mantissas| Column::from_numbers(mantissas.into_iter().map(|mantissa| make_quantity(mantissa, range, 0)).collect()))
+}
+
+fn float_comparison(lhs: &Table, rhs: &Table, out: &mut Table, kernel: fn(Lane<f64>, Lane<f64>) -> Vec<bool>) -> bool {
+  run_kernel(lhs, rhs, out, |column, height| float_column(&column.numbers()?[..height]), kernel, Column::from_bools)
+}
+
+fn bool_logic(lhs: &Table, rhs: &Table, out: &mut Table, kernel: fn(Lane<bool>, Lane<bool>) -> Vec<bool>) -> bool {
+  run_kernel(lhs, rhs, out, |column, height| bool_column(&column.bools()?[..height]), kernel, Column::from_bools)
+}
+
+pub fn add_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  integer_math(lhs, rhs, out, SUM_LIMIT, 0, add_integers)
+}
+
+pub fn subtract_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  integer_math(lhs, rhs, out, SUM_LIMIT, 0, subtract_integers)
+}
+
+pub fn multiply_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  integer_math(lhs, rhs, out, PRODUCT_LIMIT, 0, multiply_integers)
+}
+
+pub fn divide_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  integer_math(lhs, rhs, out, QUOTIENT_LIMIT, -4, divide_integers)
+}
+
+pub fn less_than_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, less_than_floats)
+}
+
+pub fn greater_than_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, greater_than_floats)
+}
+
+pub fn less_than_equal_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, less_than_equal_floats)
+}
+
+pub fn greater_than_equal_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, greater_than_equal_floats)
+}
+
+pub fn equal_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, equal_floats)
+}
+
+pub fn not_equal_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  float_comparison(lhs, rhs, out, not_equal_floats)
+}
+
+pub fn and_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  bool_logic(lhs, rhs, out, and_bools)
+}
+
+pub fn or_tables(lhs: &Table, rhs: &Table, out: &mut Table) -> bool {
+  bool_logic(lhs, rhs, out, or_bools)
+}
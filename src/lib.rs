@@ -24,27 +24,59 @@ extern crate num;
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
 #[cfg(feature = "no-std")] use alloc::fmt;
 #[cfg(not(feature = "no-std"))] use core::fmt;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
+#[cfg(not(feature = "no-std"))] use std::sync::mpsc::Receiver;
 use hashbrown::hash_set::HashSet;
+use core::mem;
+use core::cmp;
 
 // ## Modules
 
 mod database;
 mod runtime;
 mod table;
+mod symbols;
+mod schema;
 mod indexes;
 mod operations;
+mod kernels;
 mod quantities;
 mod errors;
+mod wire;
+mod view;
+mod subscriptions;
+mod replication;
+mod conflicts;
+mod mirrors;
+mod machines;
+mod timers;
+#[cfg(not(feature = "no-std"))] mod pool;
 
 // ## Exported Modules
 
-pub use self::database::{Transaction, Change, Interner};
-pub use self::table::{Value, Index, TableId, Table, Bar, Aliases};
+pub use self::database::{Transaction, Change, Interner, Undo, ChangeArchive, MemoryArchive};
+pub use self::table::{Value, Index, TableId, Table, Bar, Aliases, Column, Bitmap, Dictionary};
+pub use self::symbols::{Symbol, sweep_symbols, interned_symbols};
+pub use self::schema::{Schema, ColumnSchema, Kind};
 pub use self::indexes::{TableIndex, Hasher};
 pub use self::operations::{Function, Comparator, Logic, Parameter};
-pub use self::runtime::{Runtime, Block, BlockState, Constraint, Register};
+pub use self::operations::{math_add, math_subtract, math_multiply, math_divide};
+pub use self::operations::{compare_less_than, compare_greater_than, compare_less_than_equal, compare_greater_than_equal, compare_equal, compare_not_equal};
+pub use self::operations::{logic_and, logic_or};
+pub use self::kernels::{Lane, integer_column, float_column, bool_column};
+pub use self::kernels::{add_integers, subtract_integers, multiply_integers, divide_integers};
+pub use self::runtime::{Runtime, Block, BlockState, Constraint, Register, Trigger, Component};
 pub use self::quantities::{Quantity, ToQuantity, QuantityMath, make_quantity};
 pub use self::errors::{Error, ErrorType};
+pub use self::wire::WIRE_VERSION;
+pub use self::view::View;
+pub use self::subscriptions::{Subscriptions, Subscription, Observer, Callback, CellChange};
+pub use self::conflicts::{Stamp, Clock, LamportClock, HybridClock, Stamped, Version, MergePolicy, Resolver, Reconciler};
+pub use self::machines::{Machine, MockMachine, register_changed};
+pub use self::timers::{TimeSource, ManualTime, TimerMachine, timer_table, period_column, ticks_column};
+#[cfg(not(feature = "no-std"))] pub use self::timers::SystemTime;
+pub use self::mirrors::{MirrorMessage, MirrorServer, Mirror, Mirrors};
+pub use self::replication::{Transport, Message, Primary, Follower, MemoryTransport, roll_hash, INITIAL_HASH};
 
 
 // ## Core
@@ -59,10 +91,16 @@ pub struct Core {
   pub runtime: Runtime,
   pub change_capacity: usize,
   pub table_capacity: usize,
+  pub max_iterations: u64, // how many times a feedback loop can go around in one transaction
   pub input: HashSet<Register>,
   pub output: HashSet<Register>,
   pub paused: bool,
+  pub fork_point: Option<usize>, // the change number where this core branched off another
+  pub subscriptions: Subscriptions,
+  pub machines: Vec<Box<dyn Machine>>,
   transaction_boundaries: Vec<usize>,
+  pending: Vec<Vec<Change>>, // input held while paused or looking at the past
+  inputs: Vec<(usize, Vec<Change>)>, // input transactions, and the change number each started at
 }
 
 impl Core {
@@ -76,12 +114,18 @@ impl Core {
       changes: 0,
       change_capacity,
       table_capacity,
+      max_iterations: 10_000,
       store: Interner::new(change_capacity, table_capacity),
       runtime: Runtime::new(),
       input: HashSet::new(),
       output: HashSet::new(),
       paused: false,
+      fork_point: None,
+      subscriptions: Subscriptions::new(),
+      machines: Vec::new(),
       transaction_boundaries: Vec::new(),
+      pending: Vec::new(),
+      inputs: Vec::new(),
     }
   }
 
@@ -93,6 +137,10 @@ impl Core {
     self.input.clear();
     self.output.clear();
     self.transaction_boundaries.clear();
+    self.pending.clear();
+    self.inputs.clear();
+    self.fork_point = None;
+    self.subscriptions.clear();
   }
 
   pub fn register_blocks(&mut self, blocks: Vec<Block>) {
@@ -123,6 +171,12 @@ impl Core {
     self.runtime.remove_block(&block_id);
   }
 
+  // Declare what a table holds. Blocks registered from then on are checked
+  // against it too.
+  pub fn declare_schema(&mut self, schema: Schema) {
+    self.store.declare_schema(schema);
+  }
+
   pub fn last_transaction(&self) -> usize {
     if self.transaction_boundaries.len() <= 1 {
       0
@@ -142,11 +196,119 @@ impl Core {
   }
 
   pub fn step(&mut self) {
-    self.runtime.run_network(&mut self.store, 10_000);
-    self.transaction_boundaries.push(self.store.change_pointer);
+    // Keep this transaction's changes apart until it's done
+    let changed = mem::replace(&mut self.runtime.changed_this_round, HashSet::new());
+    // Count every transaction, including ones that didn't run any blocks
+    self.runtime.transactions = self.transaction_boundaries.len() + 1;
+    self.runtime.run_network(&mut self.store, self.max_iterations);
+    self.end_transaction(changed);
+  }
+
+  // Apply changes another core already made and logged, as one transaction.
+  // Whatever blocks made of them is among them, so blocks don't run again. 
+  // Their memory is brought up to date from the store instead.
+  pub fn apply_logged_changes(&mut self, changes: &[Change]) {
+    self.begin_transaction();
+    let changed = mem::replace(&mut self.runtime.changed_this_round, HashSet::new());
+    self.store.process_logged_changes(changes);
+    self.runtime.changed_this_round.extend(self.store.tables.changed_this_round.drain());
+    self.store.tables.changed_rows.clear();
+    self.runtime.refresh_blocks(&self.store);
+    self.end_transaction(changed);
+  }
+
+  fn end_transaction(&mut self, mut changed: HashSet<(u64, Index)>) {
+    if !self.subscriptions.is_empty() {
+      self.subscriptions.notify(&self.store);
+    }
+    self.route_outputs();
+    changed.extend(self.runtime.changed_this_round.drain());
+    self.runtime.changed_this_round = changed;
+    self.transaction_boundaries.push(self.store.changes_count);
+    self.epoch = self.store.rollover;
+  }
+
+  // ## Machines
+
+  // Connect a machine to the core. It's an error for it to provide a table
+  // that another machine already provides.
+  pub fn register_machine(&mut self, mut machine: Box<dyn Machine>) -> Result<(), ErrorType> {
+    let inputs = machine.inputs();
+    for table in inputs.iter() {
+      if self.machines.iter().any(|other| other.inputs().contains(table)) {
+        return Err(ErrorType::DuplicateProvider(*table));
+      }
+    }
+    let setup = machine.setup();
+    match setup.iter().find(|change| !inputs.contains(&change.table())) {
+      Some(change) => return Err(ErrorType::NotProvided(change.table())),
+      None => (),
+    }
+    self.machines.push(machine);
+    if !setup.is_empty() {
+      self.process_changes(setup);
+    }
+    Ok(())
+  }
+
+  // Collect input from every machine and process it as one transaction. A
+  // machine may only change the tables it provides; if one strays, the input
+  // is dropped and nothing is processed. Returns the number of changes 
+  // processed.
+  pub fn poll_machines(&mut self) -> Result<usize, ErrorType> {
+    let mut changes = Vec::new();
+    for machine in self.machines.iter_mut() {
+      let inputs = machine.inputs();
+      for change in machine.poll() {
+        if !inputs.contains(&change.table()) {
+          return Err(ErrorType::NotProvided(change.table()));
+        }
+        changes.push(change);
+      }
+    }
+    let count = changes.len();
+    if count > 0 {
+      self.process_changes(changes);
+    }
+    Ok(count)
+  }
+
+  // Hand machines the output tables that changed in the step just taken
+  fn route_outputs(&mut self) {
+    let changed = &self.runtime.changed_this_round;
+    for machine in self.machines.iter_mut() {
+      for register in machine.outputs() {
+        match self.store.get_table(register.table) {
+          Some(table) => if register_changed(changed, &register, table) {
+            machine.output(&register, table);
+          },
+          None => (),
+        }
+      }
+    }
   }
 
-  pub fn index(&mut self, table: u64, row: &Index, column: &Index) -> Option<&Value> {
+  // ## Subscriptions
+
+  // Call back with the cells that changed in a table (column 0) or a column 
+  // after every transaction that changes them. Returns an id for unsubscribe.
+  pub fn subscribe<F>(&mut self, register: Register, callback: F) -> usize 
+    where F: FnMut(&[CellChange]) + 'static {
+    self.subscriptions.subscribe(register, Observer::Callback(Box::new(callback)))
+  }
+
+  // Like subscribe, but the changed cells are sent down a channel. The 
+  // subscription ends when the receiver is dropped.
+  #[cfg(not(feature = "no-std"))]
+  pub fn subscribe_channel(&mut self, register: Register) -> (usize, Receiver<Vec<CellChange>>) {
+    self.subscriptions.subscribe_channel(register)
+  }
+
+  pub fn unsubscribe(&mut self, id: usize) -> bool {
+    self.subscriptions.unsubscribe(id)
+  }
+
+  pub fn index(&mut self, table: u64, row: &Index, column: &Index) -> Option<Value> {
     match self.store.tables.get(table) {
       Some(table_ref) => {
         match table_ref.index(row, column) {
@@ -158,79 +320,86 @@ impl Core {
     }
   }
 
-  pub fn step_backward(&mut self, steps: usize) {
+  pub fn get_value(&self, table: u64, row: &Index, column: &Index) -> Option<Value> {
+    match self.store.tables.get(table) {
+      Some(table_ref) => table_ref.get_value(row, column),
+      None => None,
+    }
+  }
+
+  pub fn step_backward(&mut self, steps: usize) -> Result<(), ErrorType> {
+    let mut result = Ok(());
     for _ in 0..steps {
-      self.step_back_one();
+      result = self.step_back_one();
+      if result.is_err() {
+        break;
+      }
     }
+    self.settle();
+    result
   }
 
-  pub fn step_back_one(&mut self) {
+  pub fn step_back_one(&mut self) -> Result<(), ErrorType> {
     let time = self.store.offset;
-    self.store.offset += 1;
     let transactions = self.transaction_boundaries.len();
     // We can only step back if there is at least one transaction, 
     // and we aren't at the beginning of time
-    if transactions > 0  {
-      let now_ix = if transactions <= time {
-        0
-      } else {
-        self.transaction_boundaries[transactions - time - 1]
-      };
+    if time < transactions {
+      let now_ix = self.transaction_boundaries[transactions - time - 1];
       let prev_ix = if transactions <= time + 1 {
         0 
       } else {
         self.transaction_boundaries[transactions - time - 2]
       };
-
-      // Now process the transactions in reverse order
-      for ix in (prev_ix..now_ix).rev() {
-        let core = self as *mut Core;
-        match &self.store.changes[ix] {
-          Change::Set{table, row, column, value} => {
-            unsafe {
-              (*core).store.process_transaction(&Transaction::from_change(
-                Change::Remove{table: table.clone(), row: row.clone(), column: column.clone(), value: value.clone()}
-              ));
-            }
-          },
-          Change::Remove{table, row, column, value} => {
-            unsafe {
-              (*core).store.process_transaction(&Transaction::from_change(
-                Change::Set{table: table.clone(), row: row.clone(), column: column.clone(), value: value.clone()}
-              ));
-            }
-          },
-          Change::NewTable{id, rows, columns} => {
-            /*self.store.process_transaction(&Transaction::from_change(
-              Change::RemoveTable{id: *id, rows: *rows, columns: *columns}
-            ));*/
-          },
-          _ => (),
-        };
+      // Gather the whole transaction before touching anything, so we don't 
+      // get stuck halfway through it if part of it is gone.
+      let mut history = Vec::with_capacity(now_ix - prev_ix);
+      for ix in prev_ix..now_ix {
+        match self.store.get_change(ix) {
+          Some(entry) => history.push(entry),
+          None => return Err(ErrorType::TimeNotRetained(time + 1)),
+        }
       }
-
+      // Now undo the changes in reverse order
+      for (change, undo) in history.iter().rev() {
+        self.store.revert_change(change, undo.as_ref());
+      }
+      self.store.offset += 1;
     }
     self.offset = self.store.offset;
+    Ok(())
   }
 
-  pub fn step_forward(&mut self, steps: usize) {
-    for i in 0..steps {
-      self.step_forward_one();
+  pub fn step_forward(&mut self, steps: usize) -> Result<(), ErrorType> {
+    let mut result = Ok(());
+    for _ in 0..steps {
+      result = self.step_forward_one();
+      if result.is_err() {
+        break;
+      }
+    }
+    self.settle();
+    // Back to now, so catch up on whatever arrived while we were away
+    if !self.paused && self.offset == 0 {
+      self.apply_pending();
     }
+    result
   }
 
-  pub fn set_time(&mut self, time: usize) {
+  pub fn set_time(&mut self, time: usize) -> Result<(), ErrorType> {
     let current_time = self.offset;
     if current_time > time {
       let dt = current_time - time;
-      self.step_forward(dt); 
+      self.step_forward(dt)
     } else if current_time < time {
       let dt = time - current_time;
-      self.step_backward(dt); 
+      self.step_backward(dt)
+    } else {
+      Ok(())
     }
   }
 
-  pub fn step_forward_one(&mut self) {
+  pub fn step_forward_one(&mut self) -> Result<(), ErrorType> {
     let time = self.store.offset;
     let transactions = self.transaction_boundaries.len();
     // We can only step forward if there is at least one transaction and we are
@@ -241,24 +410,114 @@ impl Core {
       } else {
         self.transaction_boundaries[transactions - time - 1]
       };
-      let next_ix = if transactions <= time - 1 {
-        0 
-      } else {
-        self.transaction_boundaries[transactions - time]
-      };
+      let next_ix = self.transaction_boundaries[transactions - time];
+      let mut history = Vec::with_capacity(next_ix - now_ix);
       for ix in now_ix..next_ix {
-        self.store.process_transaction(&Transaction::from_change(self.store.changes[ix].clone()));
+        match self.store.get_change(ix) {
+          Some((change, _)) => history.push(change),
+          None => return Err(ErrorType::TimeNotRetained(time - 1)),
+        }
       }
+      self.store.process_changes(&history);
       self.store.offset -= 1;
     }
     self.offset = self.store.offset;
+    Ok(())
   }
 
-  pub fn resume(&mut self) {
-    for _ in 0..self.offset {
-      self.step_forward_one();
+  // Reconstruct the tables as they were at the given time, an offset from now
+  // just like set_time, without touching the store. Times before the 
+  // beginning of time show the beginning.
+  pub fn view_at(&self, time: usize) -> Result<View, ErrorType> {
+    let transactions = self.transaction_boundaries.len();
+    let time = cmp::min(time, transactions);
+    let boundary = |offset: usize| if offset >= transactions {
+      0
+    } else {
+      self.transaction_boundaries[transactions - offset - 1]
+    };
+    let here = boundary(self.offset);
+    let there = boundary(time);
+    let mut scratch = Interner::new(0, 0);
+    scratch.tables = self.store.tables.clone();
+    // Replayed changes aren't logged again, just as when stepping forward
+    scratch.offset = cmp::max(self.store.offset, 1);
+    if there < here {
+      for number in (there..here).rev() {
+        match self.store.get_change(number) {
+          Some((change, undo)) => scratch.revert_change(&change, undo.as_ref()),
+          None => return Err(ErrorType::TimeNotRetained(time)),
+        }
+      }
+    } else {
+      for number in here..there {
+        match self.store.get_change(number) {
+          Some((change, _)) => scratch.process_changes(&[change]),
+          None => return Err(ErrorType::TimeNotRetained(time)),
+        }
+      }
     }
+    scratch.tables.changed_this_round.clear();
+    scratch.tables.changed_rows.clear();
+    Ok(View::new(time, scratch.tables))
+  }
+
+  // What changed between the given time, an offset from now as in set_time,
+  // and the tables as they stand, as a single transaction. Processing it 
+  // against the tables at that time brings them up to date.
+  pub fn diff_since(&self, time: usize) -> Result<Transaction, ErrorType> {
+    let view = self.view_at(time)?;
+    Ok(Transaction::from_changeset(view.tables.diff(&self.store.tables)))
+  }
+
+  // After moving through time, the store holds exactly the tables it had at 
+  // that time. Nothing has changed this round, and block memory is recomputed
+  // from the store so the runtime agrees with it.
+  fn settle(&mut self) {
+    self.store.tables.changed_this_round.clear();
+    self.store.tables.changed_rows.clear();
+    // Previous values are whatever the tables held one transaction back
+    if !self.store.remembered.is_empty() {
+      // They're copied again at the next boundary, whether they change or not
+      self.store.changed_tables.extend(self.store.remembered.iter().cloned());
+      match self.view_at(self.offset + 1) {
+        Ok(view) => {
+          self.store.previous.clear();
+          for table in self.store.remembered.iter() {
+            match view.get_table(*table) {
+              Some(table_ref) => self.store.previous.insert(table_ref.clone()),
+              None => (),
+            }
+          }
+        },
+        Err(_) => self.store.mark_boundary(),
+      }
+    }
+    self.runtime.refresh_blocks(&self.store);
+  }
+
+  // Everything that has to happen before the changes of a new transaction go
+  // into the store
+  fn begin_transaction(&mut self) {
+    self.subscriptions.begin(&self.store);
+    self.store.mark_boundary();
+  }
+
+  pub fn resume(&mut self) -> Result<(), ErrorType> {
+    let offset = self.offset;
+    let result = self.step_forward(offset);
     self.paused = false;
+    if result.is_ok() {
+      self.apply_pending();
+    }
+    result
+  }
+
+  fn apply_pending(&mut self) {
+    let pending = mem::replace(&mut self.pending, Vec::new());
+    for changes in pending {
+      self.apply_input(changes);
+    }
   }
 
   pub fn pause(&mut self) {
@@ -266,17 +525,132 @@ impl Core {
   }
 
   pub fn process_transaction(&mut self, txn: &Transaction) {
-    if !self.paused {
-      self.store.process_transaction(txn);
-      self.runtime.run_network(&mut self.store, 10_000);
+    // Kept in the order the interner would apply it
+    let mut changes = Vec::with_capacity(txn.tables.len() + txn.names.len() + txn.removes.len() + txn.adds.len());
+    changes.extend(txn.tables.iter().cloned());
+    changes.extend(txn.names.iter().cloned());
+    changes.extend(txn.removes.iter().cloned());
+    changes.extend(txn.adds.iter().cloned());
+    self.process_changes(changes);
+  }
+
+  // Input is remembered as it was given, so a branch can be merged by 
+  // replaying its input rather than everything it logged. It's kept for as 
+  // long as the change log goes back.
+  fn apply_input(&mut self, changes: Vec<Change>) {
+    let start = self.store.changes_count;
+    self.begin_transaction();
+    self.store.process_changes(&changes);
+    self.step();
+    self.inputs.push((start, changes));
+    let oldest = self.oldest_input();
+    let forgotten = self.inputs.iter().take_while(|(number, _)| *number < oldest).count();
+    self.inputs.drain(..forgotten);
+  }
+
+  // The number of the oldest change input is remembered from
+  fn oldest_input(&self) -> usize {
+    match self.store.archive {
+      Some(_) => 0,
+      None => self.store.oldest_change(),
+    }
+  }
+
+  // ## Branches
+
+  // Fork an independent copy of this core at the given time, which is an 
+  // offset from now just like set_time. The branch keeps the history up to 
+  // that point and forgets everything after it, so new input starts an 
+  // alternate timeline. Nothing done on the branch affects this core.
+  pub fn fork(&self, time: usize) -> Result<Core, ErrorType> {
+    let mut branch = Core {
+      id: self.id,
+      epoch: self.epoch,
+      offset: self.offset,
+      round: self.round,
+      changes: self.changes,
+      change_capacity: self.change_capacity,
+      table_capacity: self.table_capacity,
+      max_iterations: self.max_iterations,
+      store: self.store.branch(),
+      runtime: self.runtime.clone(),
+      input: self.input.clone(),
+      output: self.output.clone(),
+      paused: false,
+      fork_point: None,
+      subscriptions: Subscriptions::new(),
+      machines: Vec::new(),
+      transaction_boundaries: self.transaction_boundaries.clone(),
+      pending: Vec::new(),
+      inputs: Vec::new(),
+    };
+    branch.set_time(time)?;
+    let transactions = branch.transaction_boundaries.len() - branch.offset;
+    branch.transaction_boundaries.truncate(transactions);
+    let fork_point = branch.this_transaction();
+    branch.store.truncate_history(fork_point);
+    branch.store.offset = 0;
+    branch.offset = 0;
+    branch.epoch = branch.store.rollover;
+    branch.fork_point = Some(fork_point);
+    branch.inputs = self.inputs.iter().take_while(|(number, _)| *number < fork_point).cloned().collect();
+    Ok(branch)
+  }
 
-      self.transaction_boundaries.push(self.store.change_pointer);
-      self.epoch = self.store.rollover;
+  // The changes made on this branch since it was forked, in the order they 
+  // were made. For a core that was never forked this is its whole history.
+  pub fn branch_changes(&self) -> Result<Vec<Change>, ErrorType> {
+    let start = match self.fork_point {
+      Some(fork_point) => fork_point,
+      None => 0,
+    };
+    let mut changes = Vec::with_capacity(self.store.changes_count - start);
+    for number in start..self.store.changes_count {
+      match self.store.get_change(number) {
+        Some((change, _)) => changes.push(change),
+        None => return Err(ErrorType::TimeNotRetained(self.transaction_boundaries.len())),
+      }
+    }
+    Ok(changes)
+  }
+
+  // The input given to this branch since it was forked, one transaction at a
+  // time. For a core that was never forked this is all of its input.
+  pub fn branch_inputs(&self) -> Result<Vec<Vec<Change>>, ErrorType> {
+    let start = match self.fork_point {
+      Some(fork_point) => fork_point,
+      None => 0,
+    };
+    if start < self.oldest_input() {
+      return Err(ErrorType::TimeNotRetained(self.transaction_boundaries.len()));
+    }
+    Ok(self.inputs.iter().filter(|(number, _)| *number >= start).map(|(_, changes)| changes.clone()).collect())
+  }
+
+  // Replay the input given to a branch onto this core as one transaction.
+  // Inputs are applied in the order they were given, so later writes on the 
+  // branch win. Whatever the branch's blocks made of them is worked out again
+  // here by this core's blocks.
+  pub fn merge(&mut self, branch: &Core) -> Result<(), ErrorType> {
+    let changes = branch.branch_inputs()?.into_iter().flat_map(|changes| changes).collect();
+    self.process_changes(changes);
+    Ok(())
+  }
+
+  // Apply changes in the order given as one transaction, and run the network.
+  // Input that arrives while we're paused is held until we resume, and input
+  // that arrives while we're looking at the past is held until we're back to
+  // now. Either way it would go into history at the wrong place.
+  pub fn process_changes(&mut self, changes: Vec<Change>) {
+    if self.paused || self.offset > 0 {
+      self.pending.push(changes);
+    } else {
+      self.apply_input(changes);
     }
   }
 
   pub fn capacity(&self) -> f64 {
-    100.0 * (self.store.changes.len() as f64 / self.store.changes.capacity() as f64)
+    100.0 * (self.store.changes.len() as f64 / self.store.change_capacity as f64)
   }
 }
 
@@ -289,13 +663,16 @@ impl fmt::Debug for Core {
     write!(f, "│ Time Offset: {:?}\n", self.offset).unwrap();
     write!(f, "│ Epoch: {:?}\n", self.epoch).unwrap();
     write!(f, "│ Changes: {:?}\n", self.store.changes_count).unwrap();
-    write!(f, "│ Capacity: {:0.2}%\n", 100.0 * (self.store.changes.len() as f64 / self.store.changes.capacity() as f64)).unwrap();
+    write!(f, "│ Capacity: {:0.2}%\n", 100.0 * (self.store.changes.len() as f64 / self.store.change_capacity as f64)).unwrap();
     write!(f, "│ Tables: {:?}\n", self.store.tables.len()).unwrap();
     write!(f, "│ Blocks: {:?}\n", self.runtime.blocks.len()).unwrap();
     write!(f, "│   Input: {:?}\n", self.input).unwrap();
     write!(f, "│   Output: {:?}\n", self.output).unwrap();
+    write!(f, "│ Machines: {:?}\n", self.machines.len()).unwrap();
     write!(f, "│   Errors:\n").unwrap();
     write!(f, "│     {:?}\n", self.runtime.errors).unwrap();
+    write!(f, "│   Warnings:\n").unwrap();
+    write!(f, "│     {:?}\n", self.runtime.warnings).unwrap();
     write!(f, "└────────────────────┘\n").unwrap();
     for table in self.store.tables.map.values() {
       write!(f, "{:?}", table).unwrap();
@@ -0,0 +1,117 @@
+// # Machines
+
+// Machines connect a core to the world outside it. A machine provides input
+// tables, which it fills in from timers, sensors, keyboards and the like, and
+// consumes output tables, which it turns into motor commands, pixels and so
+// on. The core polls machines for input, and hands them their output tables
+// after every step that changes them.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::rc::Rc;
+#[cfg(not(feature = "no-std"))] use std::rc::Rc;
+use core::cell::RefCell;
+use table::{Table, Index};
+use database::Change;
+use runtime::Register;
+use hashbrown::hash_set::HashSet;
+
+// ## Machine
+
+pub trait Machine {
+  // The tables this machine fills in. No two machines can provide the same
+  // table.
+  fn inputs(&self) -> Vec<u64>;
+  // The output registers this machine consumes. Column 0 stands for the
+  // whole table.
+  fn outputs(&self) -> Vec<Register>;
+  // Changes that set up the machine's input tables, processed as soon as the
+  // machine is registered
+  fn setup(&mut self) -> Vec<Change> {
+    Vec::new()
+  }
+  // Changes to the machine's input tables that are ready to go into the core
+  fn poll(&mut self) -> Vec<Change>;
+  // Called after a step that changed one of the machine's outputs, with the
+  // table as it stands afterwards
+  fn output(&mut self, register: &Register, table: &Table);
+}
+
+// Did a step change the column, or table, a register refers to? Changes are
+// recorded under whatever index they were made with, so look for the column
+// under its alias as well as its position.
+pub fn register_changed(changed: &HashSet<(u64, Index)>, register: &Register, table: &Table) -> bool {
+  if !changed.contains(&(register.table, Index::Index(0))) {
+    return false;
+  }
+  match register.column {
+    Index::Index(0) => true,
+    column => {
+      changed.contains(&(register.table, column)) ||
+      match table.get_column_index(&column) {
+        Some(ix) => changed.contains(&(register.table, Index::Index(ix))),
+        None => false,
+      } ||
+      match table.get_column_alias(&column) {
+        Some(alias) => changed.contains(&(register.table, Index::Alias(alias))),
+        None => false,
+      }
+    },
+  }
+}
+
+// ## Mock Machine
+
+// A machine for tests. Queue up input with feed, and look at what it was
+// given in received. Clones share their queues, so a test can keep one and
+// hand the other to a core.
+#[derive(Clone)]
+pub struct MockMachine {
+  pub inputs: Vec<u64>,
+  pub outputs: Vec<Register>,
+  pub queued: Rc<RefCell<Vec<Change>>>,
+  pub received: Rc<RefCell<Vec<(Register, Table)>>>,
+}
+
+impl MockMachine {
+
+  pub fn new(inputs: Vec<u64>, outputs: Vec<Register>) -> MockMachine {
+    MockMachine {
+      inputs,
+      outputs,
+      queued: Rc::new(RefCell::new(Vec::new())),
+      received: Rc::new(RefCell::new(Vec::new())),
+    }
+  }
+
+  pub fn feed(&self, change: Change) {
+    self.queued.borrow_mut().push(change);
+  }
+
+  // Everything received so far, oldest first. The record is emptied.
+  pub fn take_received(&self) -> Vec<(Register, Table)> {
+    self.received.borrow_mut().drain(..).collect()
+  }
+
+}
+
+impl Machine for MockMachine {
+
+  fn inputs(&self) -> Vec<u64> {
+    self.inputs.clone()
+  }
+
+  fn outputs(&self) -> Vec<Register> {
+    self.outputs.clone()
+  }
+
+  fn poll(&mut self) -> Vec<Change> {
+    self.queued.borrow_mut().drain(..).collect()
+  }
+
+  fn output(&mut self, register: &Register, table: &Table) {
+    self.received.borrow_mut().push((register.clone(), table.clone()));
+  }
+
+}
@@ -0,0 +1,237 @@
+// # Mirrors
+
+// Blocks only read tables in their own store. To build a program out of
+// several cores, a global table on one core can mirror a table on another,
+// named core. The mirroring core asks the remote for a snapshot of the table,
+// then follows the changes the remote logs to it. Mirrored changes go through
+// Core::process_changes, so blocks reading the mirror run when it updates,
+// just as they do for local input.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
+use table::Table;
+use database::Change;
+use errors::ErrorType;
+use replication::Transport;
+use hashbrown::hash_map::HashMap;
+use Core;
+
+// ## Messages
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MirrorMessage {
+  Subscribe{table: u64},
+  Unsubscribe{table: u64},
+  // The whole table as it stands on the remote, or None if there isn't one
+  Snapshot{table: u64, contents: Option<Box<Table>>},
+  // Changes to the table, in the order the remote logged them
+  Changes{table: u64, changes: Vec<Change>},
+}
+
+fn retarget(change: Change, to: u64) -> Change {
+  match change {
+    Change::Set{row, column, value, ..} => Change::Set{table: to, row, column, value},
+    Change::Remove{row, column, value, ..} => Change::Remove{table: to, row, column, value},
+    Change::RenameColumn{column_ix, column_alias, ..} => Change::RenameColumn{table: to, column_ix, column_alias},
+    Change::NewTable{rows, columns, ..} => Change::NewTable{id: to, rows, columns},
+    Change::RemoveTable{rows, columns, contents, ..} => {
+      let contents = contents.into_iter().map(|change| retarget(change, to)).collect();
+      Change::RemoveTable{id: to, rows, columns, contents}
+    },
+  }
+}
+
+// ## Mirror Server
+
+// Serves tables on this core to one other core
+pub struct MirrorServer<T: Transport<MirrorMessage>> {
+  pub transport: T,
+  subscribed: HashMap<u64, usize>, // table, and the number of the next change to send
+}
+
+impl<T: Transport<MirrorMessage>> MirrorServer<T> {
+
+  pub fn new(transport: T) -> MirrorServer<T> {
+    MirrorServer {
+      transport,
+      subscribed: HashMap::new(),
+    }
+  }
+
+  // Answer requests, then send whatever has changed in the subscribed tables
+  pub fn serve(&mut self, core: &Core) {
+    let logged = core.store.changes_count;
+    while let Some(message) = self.transport.receive() {
+      match message {
+        MirrorMessage::Subscribe{table} => {
+          self.transport.send(MirrorMessage::Snapshot{table, contents: snapshot(core, table)});
+          self.subscribed.insert(table, logged);
+        },
+        MirrorMessage::Unsubscribe{table} => {
+          self.subscribed.remove(&table);
+        },
+        _ => (),
+      }
+    }
+    let start = match self.subscribed.values().filter(|sent| **sent < logged).min() {
+      Some(start) => *start,
+      None => return,
+    };
+    // Go over the log once, sorting out changes by table
+    let mut changes: HashMap<u64, Vec<Change>> = HashMap::new();
+    let mut forgotten = start; // the changes before this one are gone
+    for number in start..logged {
+      match core.store.get_change(number) {
+        Some((change, _)) => {
+          let table = change.table();
+          match self.subscribed.get(&table) {
+            Some(sent) if *sent <= number => changes.entry(table).or_insert(Vec::new()).push(change),
+            _ => (),
+          }
+        },
+        None => forgotten = number + 1,
+      }
+    }
+    for (table, sent) in self.subscribed.iter_mut() {
+      if *sent >= logged {
+        continue;
+      }
+      if *sent < forgotten {
+        // Changes have been forgotten, so start the mirror over
+        self.transport.send(MirrorMessage::Snapshot{table: *table, contents: snapshot(core, *table)});
+      } else if let Some(changes) = changes.remove(table) {
+        self.transport.send(MirrorMessage::Changes{table: *table, changes});
+      }
+      *sent = logged;
+    }
+  }
+
+}
+
+fn snapshot(core: &Core, table: u64) -> Option<Box<Table>> {
+  core.store.get_table(table).map(|table| Box::new(table.clone()))
+}
+
+// ## Mirrors
+
+pub struct Mirror {
+  pub local: u64,
+  pub remote: u64, // the name of the remote core
+  pub table: u64,  // the table on the remote core
+}
+
+// The mirrored tables on this core, and the links to the cores they mirror
+pub struct Mirrors<T: Transport<MirrorMessage>> {
+  pub remotes: HashMap<u64, T>,
+  pub mirrors: Vec<Mirror>,
+}
+
+impl<T: Transport<MirrorMessage>> Mirrors<T> {
+
+  pub fn new() -> Mirrors<T> {
+    Mirrors {
+      remotes: HashMap::new(),
+      mirrors: Vec::new(),
+    }
+  }
+
+  pub fn add_remote(&mut self, name: u64, transport: T) {
+    self.remotes.insert(name, transport);
+  }
+
+  // Declare that the local table mirrors a table on the named remote core
+  pub fn mirror(&mut self, local: u64, remote: u64, table: u64) -> Result<(), ErrorType> {
+    match self.remotes.get_mut(&remote) {
+      Some(transport) => {
+        if !self.mirrors.iter().any(|mirror| mirror.remote == remote && mirror.table == table) {
+          transport.send(MirrorMessage::Subscribe{table});
+        }
+        self.mirrors.push(Mirror{local, remote, table});
+        Ok(())
+      },
+      None => Err(ErrorType::UnknownRemote(remote)),
+    }
+  }
+
+  // Stop mirroring into the local table. The table keeps its contents.
+  pub fn unmirror(&mut self, local: u64) {
+    let (gone, kept): (Vec<Mirror>, Vec<Mirror>) = self.mirrors.drain(..).partition(|mirror| mirror.local == local);
+    self.mirrors = kept;
+    for mirror in gone {
+      let still_wanted = self.mirrors.iter().any(|other| other.remote == mirror.remote && other.table == mirror.table);
+      match self.remotes.get_mut(&mirror.remote) {
+        Some(transport) if !still_wanted => transport.send(MirrorMessage::Unsubscribe{table: mirror.table}),
+        _ => (),
+      }
+    }
+  }
+
+  // Apply everything the remotes have sent as one transaction. Returns the
+  // number of changes made to mirrored tables.
+  pub fn sync(&mut self, core: &mut Core) -> usize {
+    let mut changes = Vec::new();
+    // Whether each mirrored table will be there once the changes so far are
+    // made, since a snapshot can come after other messages for its table
+    let mut present: HashMap<u64, bool> = HashMap::new();
+    for (remote, transport) in self.remotes.iter_mut() {
+      while let Some(message) = transport.receive() {
+        let (table, snapshot, updates) = match message {
+          MirrorMessage::Snapshot{table, contents} => (table, Some(contents), Vec::new()),
+          MirrorMessage::Changes{table, changes} => (table, None, changes),
+          _ => continue,
+        };
+        for mirror in self.mirrors.iter().filter(|mirror| mirror.remote == *remote && mirror.table == table) {
+          let local = mirror.local;
+          let there = *present.entry(local).or_insert_with(|| core.store.get_table(local).is_some());
+          match &snapshot {
+            Some(contents) => {
+              changes.extend(catch_up(local, there, contents.as_ref().map(|table| &**table)));
+              present.insert(local, contents.is_some());
+            },
+            None => {
+              for change in updates.iter() {
+                match change {
+                  Change::NewTable{..} => { present.insert(local, true); },
+                  Change::RemoveTable{..} => { present.insert(local, false); },
+                  _ => (),
+                }
+                changes.push(retarget(change.clone(), local));
+              }
+            },
+          }
+        }
+      }
+    }
+    let count = changes.len();
+    if count > 0 {
+      core.process_changes(changes);
+    }
+    count
+  }
+
+}
+
+impl<T: Transport<MirrorMessage>> Default for Mirrors<T> {
+  fn default() -> Mirrors<T> {
+    Mirrors::new()
+  }
+}
+
+// The changes that bring a local table in line with a snapshot. The table is
+// made over from scratch, so it takes the snapshot's shape as well as its
+// contents.
+fn catch_up(local: u64, there: bool, snapshot: Option<&Table>) -> Vec<Change> {
+  let mut changes = Vec::new();
+  if there {
+    changes.push(Change::RemoveTable{id: local, rows: 0, columns: 0, contents: Vec::new()});
+  }
+  if let Some(snapshot) = snapshot {
+    let mut snapshot = snapshot.clone();
+    snapshot.id = local;
+    changes.push(Change::NewTable{id: local, rows: snapshot.rows, columns: snapshot.columns});
+    changes.extend(Table::new(local, snapshot.rows, snapshot.columns).diff(&snapshot));
+  }
+  changes
+}
@@ -5,9 +5,10 @@
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
 #[cfg(feature = "no-std")] use alloc::fmt;
 #[cfg(not(feature = "no-std"))] use core::fmt;
-use table::{Table, Value, TableId, Index};
+use table::{Table, Value, TableId, Index, Column};
 use errors::ErrorType;
 use quantities::{Quantity, QuantityMath, ToQuantity};
+use kernels;
 
 /*
 Queries are compiled down to a Plan, which is a sequence of Operations that 
@@ -45,10 +46,16 @@ pub enum Function {
 
 #[macro_export]
 macro_rules! binary_math {
-  ($func_name:ident, $op:tt) => (
-    pub fn $func_name(lhs: &Table, lhs_rows: &Vec<Value>, lhs_columns: &Vec<Value>, 
-                      rhs: &Table, rhs_rows: &Vec<Value>, rhs_columns: &Vec<Value>,
+  ($func_name:ident, $op:tt, $kernel:ident) => (
+    pub fn $func_name(lhs: &Table, lhs_rows: &Column, lhs_columns: &Column, 
+                      rhs: &Table, rhs_rows: &Column, rhs_columns: &Column,
                       out: &mut Table, errors: &mut Vec<ErrorType>) {
+
+      // Whole tables go through the columnar kernels if they can
+      if lhs_rows.is_empty() && lhs_columns.is_empty() && rhs_rows.is_empty() && rhs_columns.is_empty() &&
+         kernels::$kernel(lhs, rhs, out) {
+        return;
+      }
       // Get the math dimensions
       let lhs_width  = if lhs_columns.is_empty() { lhs.columns }
                        else { lhs_columns.len() as u64 };
@@ -67,18 +74,18 @@ macro_rules! binary_math {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                    else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                match x.$op(*y) {
-                  Ok(op_result) => out.data[i][j] = Value::from_quantity(op_result),
+                match x.$op(y) {
+                  Ok(op_result) => { out.data[i].set(j, Value::from_quantity(op_result)); },
                   Err(error) => errors.push(error), // Throw an error here
                 }
               },
@@ -91,18 +98,18 @@ macro_rules! binary_math {
         out.grow_to_fit(rhs_height, rhs_width);        
         for i in 0..rhs_width as usize {
           let lcix = if lhs_columns.is_empty() { 0 }
-                    else { lhs_columns[0].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..rhs_height as usize {
             let lrix = if lhs_rows.is_empty() { 0 }
-                       else { lhs_rows[0].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(0).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                match x.$op(*y) {
-                  Ok(op_result) => out.data[i][j] = Value::from_quantity(op_result),
+                match x.$op(y) {
+                  Ok(op_result) => { out.data[i].set(j, Value::from_quantity(op_result)); },
                   Err(error) => errors.push(error), // Throw an error here
                 }
               },
@@ -115,18 +122,18 @@ macro_rules! binary_math {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                     else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                     else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { 0 }
-                     else { rhs_columns[0].as_u64().unwrap() as usize - 1 };
+                     else { rhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { 0 }
-                       else { rhs_rows[0].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(0).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                match x.$op(*y) {
-                  Ok(op_result) => out.data[i][j] = Value::from_quantity(op_result),
+                match x.$op(y) {
+                  Ok(op_result) => { out.data[i].set(j, Value::from_quantity(op_result)); },
                   Err(error) => errors.push(error), // Throw an error here
                 }
               },
@@ -139,13 +146,13 @@ macro_rules! binary_math {
   )
 }
 
-binary_math!{math_add, add}
-binary_math!{math_subtract, sub}
-binary_math!{math_multiply, multiply}
-binary_math!{math_divide, divide}
+binary_math!{math_add, add, add_tables}
+binary_math!{math_subtract, sub, subtract_tables}
+binary_math!{math_multiply, multiply, multiply_tables}
+binary_math!{math_divide, divide, divide_tables}
 // FIXME this isn't actually right at all. ^ is not power in Rust
-binary_math!{math_power, add}
-binary_math!{undefined, add}
+binary_math!{math_power, add, add_tables}
+binary_math!{undefined, add, add_tables}
 
 // ## Comparators
 
@@ -178,11 +185,17 @@ impl fmt::Debug for Comparator {
 
 #[macro_export]
 macro_rules! comparator {
-  ($func_name:ident, $op:tt) => (
-    pub fn $func_name(lhs: &Table, lhs_rows: &Vec<Value>, lhs_columns: &Vec<Value>, 
-                      rhs: &Table, rhs_rows: &Vec<Value>, rhs_columns: &Vec<Value>,
+  ($func_name:ident, $op:tt, $kernel:ident) => (
+    pub fn $func_name(lhs: &Table, lhs_rows: &Column, lhs_columns: &Column, 
+                      rhs: &Table, rhs_rows: &Column, rhs_columns: &Column,
                       out: &mut Table) {
 
+      // Whole tables go through the columnar kernels if they can
+      if lhs_rows.is_empty() && lhs_columns.is_empty() && rhs_rows.is_empty() && rhs_columns.is_empty() &&
+         kernels::$kernel(lhs, rhs, out) {
+        return;
+      }
+
       // Get the math dimensions
       let lhs_width  = if lhs_columns.is_empty() { lhs.columns }
                        else { lhs_columns.len() as u64 };
@@ -201,20 +214,20 @@ macro_rules! comparator {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                    else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                out.data[i][j] = Value::Bool(x.$op(*y));
+                out.data[i].set(j, Value::Bool(x.$op(y)));
               },
               (Value::String(x), Value::String(y)) => {
-                out.data[i][j] = Value::Bool(x == y);
+                out.data[i].set(j, Value::Bool(x == y));
               },
               _ => (),
             }
@@ -225,20 +238,20 @@ macro_rules! comparator {
         out.grow_to_fit(rhs_height, rhs_width);        
         for i in 0..rhs_width as usize {
           let lcix = if lhs_columns.is_empty() { 0 }
-                    else { lhs_columns[0].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..rhs_height as usize {
             let lrix = if lhs_rows.is_empty() { 0 }
-                       else { lhs_rows[0].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(0).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                out.data[i][j] = Value::Bool(x.$op(*y));
+                out.data[i].set(j, Value::Bool(x.$op(y)));
               },
               (Value::String(x), Value::String(y)) => {
-                out.data[i][j] = Value::Bool(x == y);
+                out.data[i].set(j, Value::Bool(x == y));
               },
               _ => (),
             }
@@ -249,20 +262,20 @@ macro_rules! comparator {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                    else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { 0 }
-                    else { rhs_columns[0].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { 0 }
-                       else { rhs_rows[0].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(0).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Number(x), Value::Number(y)) => {
-                out.data[i][j] = Value::Bool(x.$op(*y));
+                out.data[i].set(j, Value::Bool(x.$op(y)));
               },
               (Value::String(x), Value::String(y)) => {
-                out.data[i][j] = Value::Bool(x == y);
+                out.data[i].set(j, Value::Bool(x == y));
               },
               _ => (),
             }
@@ -273,13 +286,13 @@ macro_rules! comparator {
   )
 }
 
-comparator!{compare_not_equal, not_equal}
-comparator!{compare_equal, equal}
-comparator!{compare_less_than_equal, less_than_equal}
-comparator!{compare_greater_than_equal, greater_than_equal}
-comparator!{compare_greater_than, greater_than}
-comparator!{compare_less_than, less_than}
-comparator!{compare_undefined, greater_than}
+comparator!{compare_not_equal, not_equal, not_equal_tables}
+comparator!{compare_equal, equal, equal_tables}
+comparator!{compare_less_than_equal, less_than_equal, less_than_equal_tables}
+comparator!{compare_greater_than_equal, greater_than_equal, greater_than_equal_tables}
+comparator!{compare_greater_than, greater_than, greater_than_tables}
+comparator!{compare_less_than, less_than, less_than_tables}
+comparator!{compare_undefined, greater_than, greater_than_tables}
 
 // ## Logic
 
@@ -304,11 +317,17 @@ impl fmt::Debug for Logic {
 
 #[macro_export]
 macro_rules! logic {
-  ($func_name:ident, $op:tt) => (
-    pub fn $func_name(lhs: &Table, lhs_rows: &Vec<Value>, lhs_columns: &Vec<Value>, 
-                      rhs: &Table, rhs_rows: &Vec<Value>, rhs_columns: &Vec<Value>,
+  ($func_name:ident, $op:tt, $kernel:ident) => (
+    pub fn $func_name(lhs: &Table, lhs_rows: &Column, lhs_columns: &Column, 
+                      rhs: &Table, rhs_rows: &Column, rhs_columns: &Column,
                       out: &mut Table) {
 
+      // Whole tables go through the columnar kernels if they can
+      if lhs_rows.is_empty() && lhs_columns.is_empty() && rhs_rows.is_empty() && rhs_columns.is_empty() &&
+         kernels::$kernel(lhs, rhs, out) {
+        return;
+      }
+
       // Get the math dimensions
       let lhs_width  = if lhs_columns.is_empty() { lhs.columns }
                        else { lhs_columns.len() as u64 };
@@ -327,17 +346,17 @@ macro_rules! logic {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                    else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Bool(x), Value::Bool(y)) => {
-                out.data[i][j] = Value::Bool(*x $op *y);
+                out.data[i].set(j, Value::Bool(x $op y));
               },
               _ => (),
             }
@@ -348,17 +367,17 @@ macro_rules! logic {
         out.grow_to_fit(rhs_height, rhs_width);        
         for i in 0..rhs_width as usize {
           let lcix = if lhs_columns.is_empty() { 0 }
-                    else { lhs_columns[0].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { i }
-                    else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           for j in 0..rhs_height as usize {
             let lrix = if lhs_rows.is_empty() { 0 }
-                       else { lhs_rows[0].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(0).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { j }
-                       else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Bool(x), Value::Bool(y)) => {
-                out.data[i][j] = Value::Bool(*x $op *y);
+                out.data[i].set(j, Value::Bool(x $op y));
               },
               _ => (),
             }
@@ -369,17 +388,17 @@ macro_rules! logic {
         out.grow_to_fit(lhs_height, lhs_width);        
         for i in 0..lhs_width as usize {
           let lcix = if lhs_columns.is_empty() { i }
-                    else { lhs_columns[i].as_u64().unwrap() as usize - 1 };
+                    else { lhs_columns.value(i).as_u64().unwrap() as usize - 1 };
           let rcix = if rhs_columns.is_empty() { 0 }
-                    else { rhs_columns[0].as_u64().unwrap() as usize - 1 };
+                    else { rhs_columns.value(0).as_u64().unwrap() as usize - 1 };
           for j in 0..lhs_height as usize {
             let lrix = if lhs_rows.is_empty() { j }
-                       else { lhs_rows[j].as_u64().unwrap() as usize - 1 };
+                       else { lhs_rows.value(j).as_u64().unwrap() as usize - 1 };
             let rrix = if rhs_rows.is_empty() { 0 }
-                       else { rhs_rows[0].as_u64().unwrap() as usize - 1 };
-            match (&lhs.data[lcix][lrix], &rhs.data[rcix][rrix]) {
+                       else { rhs_rows.value(0).as_u64().unwrap() as usize - 1 };
+            match (lhs.data[lcix].value(lrix), rhs.data[rcix].value(rrix)) {
               (Value::Bool(x), Value::Bool(y)) => {
-                out.data[i][j] = Value::Bool(*x $op *y);
+                out.data[i].set(j, Value::Bool(x $op y));
               },
               _ => (),
             }
@@ -390,6 +409,6 @@ macro_rules! logic {
   )
 }
 
-logic!{logic_and, &&}
-logic!{logic_or, ||}
-logic!{logic_undefined, &&}
\ No newline at end of file
+logic!{logic_and, &&, and_tables}
+logic!{logic_or, ||, or_tables}
+logic!{logic_undefined, &&, and_tables}
\ No newline at end of file
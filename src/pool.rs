@@ -0,0 +1,58 @@
+// # Pool
+
+// Evaluates blocks side by side on scoped threads. The threads borrow the
+// blocks and the store for as long as one batch takes, so the blocks never
+// leave the runtime, even if one of them panics. There are no threads without
+// std.
+
+// ## Prelude
+
+use std::thread;
+use database::Interner;
+use runtime::Block;
+
+// ## Shared Store
+
+// A store that several threads read at once. Everything in a store can be
+// shared between threads except its archive, which is whatever the host
+// plugged in, so a store is only shared while it doesn't have one.
+#[derive(Clone, Copy)]
+struct Shared<'a> {
+  store: &'a Interner,
+}
+
+unsafe impl<'a> Send for Shared<'a> {}
+
+impl<'a> Shared<'a> {
+
+  fn new(store: &'a Interner) -> Shared<'a> {
+    assert!(store.archive.is_none(), "can't share a store that has an archive");
+    Shared{store}
+  }
+
+  fn get(self) -> &'a Interner {
+    self.store
+  }
+
+}
+
+// ## Evaluate
+
+// Split the blocks into as many chunks as there are threads, and evaluate
+// each chunk on its own thread. Waits for every thread before it returns, and
+// hands back the first panic if there was one.
+pub fn evaluate(mut blocks: Vec<&mut Block>, threads: usize, store: &Interner, evaluate: fn(&mut Block, &Interner)) -> thread::Result<()> {
+  let store = Shared::new(store);
+  let chunk_size = blocks.len().div_ceil(threads);
+  let results: Vec<thread::Result<()>> = thread::scope(|scope| {
+    let running: Vec<_> = blocks.chunks_mut(chunk_size.max(1)).map(|chunk| {
+      scope.spawn(move || {
+        for block in chunk.iter_mut() {
+          evaluate(block, store.get());
+        }
+      })
+    }).collect();
+    running.into_iter().map(|thread| thread.join()).collect()
+  });
+  results.into_iter().collect()
+}
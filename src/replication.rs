@@ -0,0 +1,243 @@
+// # Replication
+
+// Keeps follower cores in step with a primary by streaming the primary's
+// change log to them. Everything the primary logs is shipped, including the
+// changes its blocks make, and followers apply the changes in the order they
+// were logged. Followers don't run their own blocks on them, since what the
+// blocks would compute is in the log already.
+
+// Both ends keep a rolling hash of the change log. Every batch of changes
+// carries the primary's hash through the end of the batch, so a follower
+// whose log doesn't match the primary's finds out right away. A follower that
+// falls behind, or starts late, asks to catch up from the last change it has.
+
+// The transport is pluggable. A Message derives Serialize and Deserialize, so
+// a transport between processes can put it in whatever encoding it likes.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::rc::Rc;
+#[cfg(feature = "no-std")] use alloc::collections::VecDeque;
+#[cfg(not(feature = "no-std"))] use std::rc::Rc;
+#[cfg(not(feature = "no-std"))] use std::collections::VecDeque;
+use core::cell::RefCell;
+use database::{Change, Transaction};
+use errors::ErrorType;
+use Core;
+
+// ## Rolling Hash
+
+// FNV-1a, chained from one change to the next over their wire encoding
+pub const INITIAL_HASH: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+pub fn roll_hash(hash: u64, change: &Change) -> u64 {
+  let mut hash = hash;
+  for byte in Transaction::from_change(change.clone()).encode() {
+    hash ^= byte as u64;
+    hash = hash.wrapping_mul(FNV_PRIME);
+  }
+  hash
+}
+
+// ## Messages
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+  // Changes from the primary, numbered from first. The hash covers the log
+  // through the last of them.
+  Changes{first: usize, changes: Vec<Change>, hash: u64},
+  // A follower asks for every change from the given number on. The hash
+  // covers its log up to there.
+  CatchUp{from: usize, hash: u64},
+  // The primary can't find the follower's log in its own history
+  Diverged{at: usize},
+}
+
+// Carries messages one way and the other between two cores. Replication
+// sends Messages by default, but other protocols can ride on the same trait.
+pub trait Transport<M = Message> {
+  fn send(&mut self, message: M);
+  fn receive(&mut self) -> Option<M>;
+}
+
+// ## Primary
+
+pub struct Primary<T: Transport> {
+  pub transport: T,
+  pub sent: usize, // the number of the next change to send
+  pub hash: u64,   // the hash of the log through the last change sent
+  checkpoints: Vec<(usize, u64)>, // where each batch ended, and the hash there
+}
+
+impl<T: Transport> Primary<T> {
+
+  pub fn new(transport: T) -> Primary<T> {
+    Primary {
+      transport,
+      sent: 0,
+      hash: INITIAL_HASH,
+      checkpoints: vec![(0, INITIAL_HASH)],
+    }
+  }
+
+  // Answer catch up requests, then send the follower every change logged
+  // since the last time.
+  pub fn publish(&mut self, core: &Core) -> Result<(), ErrorType> {
+    while let Some(message) = self.transport.receive() {
+      if let Message::CatchUp{from, hash} = message {
+        match self.checkpoints.iter().position(|checkpoint| *checkpoint == (from, hash)) {
+          Some(ix) => {
+            self.checkpoints.truncate(ix + 1);
+            self.sent = from;
+            self.hash = hash;
+          },
+          None => self.transport.send(Message::Diverged{at: from}),
+        }
+      }
+    }
+    let logged = core.store.changes_count;
+    if self.sent < logged {
+      let mut changes = Vec::with_capacity(logged - self.sent);
+      let mut hash = self.hash;
+      for number in self.sent..logged {
+        match core.store.get_change(number) {
+          Some((change, _)) => {
+            hash = roll_hash(hash, &change);
+            changes.push(change);
+          },
+          None => return Err(ErrorType::ChangeNotRetained(number)),
+        }
+      }
+      self.transport.send(Message::Changes{first: self.sent, changes, hash});
+      self.sent = logged;
+      self.hash = hash;
+      self.checkpoints.push((logged, hash));
+      // Followers can't catch up from before the oldest change we still have
+      let oldest = core.store.oldest_change();
+      if core.store.archive.is_none() {
+        self.checkpoints.retain(|(number, _)| *number >= oldest);
+      }
+    }
+    Ok(())
+  }
+
+}
+
+// ## Follower
+
+pub struct Follower<T: Transport> {
+  pub transport: T,
+  pub applied: usize, // the number of the next change we expect
+  pub hash: u64,      // the hash of our log through the last change applied
+}
+
+impl<T: Transport> Follower<T> {
+
+  pub fn new(transport: T) -> Follower<T> {
+    Follower {
+      transport,
+      applied: 0,
+      hash: INITIAL_HASH,
+    }
+  }
+
+  // Ask the primary for everything after the last change we applied
+  pub fn catch_up(&mut self) {
+    self.transport.send(Message::CatchUp{from: self.applied, hash: self.hash});
+  }
+
+  // Apply whatever the primary has sent, each batch as one transaction.
+  // Returns the number of changes applied. Nothing is received while the
+  // core is paused or rewound.
+  pub fn sync(&mut self, core: &mut Core) -> Result<usize, ErrorType> {
+    if core.paused || core.offset > 0 {
+      return Ok(0);
+    }
+    let mut applied = 0;
+    while let Some(message) = self.transport.receive() {
+      match message {
+        Message::Changes{first, changes, hash} => {
+          if first > self.applied {
+            // We missed something. Ask again, and drop everything until the
+            // primary starts over from where we are.
+            self.catch_up();
+            continue;
+          } else if first + changes.len() <= self.applied {
+            continue;
+          }
+          let skip = self.applied - first;
+          let changes: Vec<Change> = changes.into_iter().skip(skip).collect();
+          let mut expected = self.hash;
+          for change in changes.iter() {
+            expected = roll_hash(expected, change);
+          }
+          if expected != hash || core.store.changes_count != self.applied {
+            return Err(ErrorType::ReplicaDiverged(self.applied));
+          }
+          let count = changes.len();
+          core.apply_logged_changes(&changes);
+          // Our own log must come out exactly like the primary's
+          for (ix, change) in changes.iter().enumerate() {
+            match core.store.get_change(self.applied + ix) {
+              Some((ref logged, _)) if logged == change => (),
+              _ => return Err(ErrorType::ReplicaDiverged(self.applied + ix)),
+            }
+          }
+          if core.store.changes_count != self.applied + count {
+            return Err(ErrorType::ReplicaDiverged(self.applied + count));
+          }
+          self.applied += count;
+          self.hash = hash;
+          applied += count;
+        },
+        Message::Diverged{at} => return Err(ErrorType::ReplicaDiverged(at)),
+        Message::CatchUp{..} => (),
+      }
+    }
+    Ok(applied)
+  }
+
+}
+
+// ## Memory Transport
+
+// Two ends of an in-process link. What one end sends, the other receives.
+#[derive(Clone, Debug)]
+pub struct MemoryTransport<M = Message> {
+  inbox: Rc<RefCell<VecDeque<M>>>,
+  outbox: Rc<RefCell<VecDeque<M>>>,
+}
+
+impl<M> MemoryTransport<M> {
+
+  pub fn pair() -> (MemoryTransport<M>, MemoryTransport<M>) {
+    let a = Rc::new(RefCell::new(VecDeque::new()));
+    let b = Rc::new(RefCell::new(VecDeque::new()));
+    (MemoryTransport{inbox: a.clone(), outbox: b.clone()}, MemoryTransport{inbox: b, outbox: a})
+  }
+
+  // Messages sent to the other end that it hasn't received yet
+  pub fn in_flight(&self) -> usize {
+    self.outbox.borrow().len()
+  }
+
+  // Lose everything in flight to the other end
+  pub fn drop_in_flight(&mut self) {
+    self.outbox.borrow_mut().clear();
+  }
+
+}
+
+impl<M> Transport<M> for MemoryTransport<M> {
+
+  fn send(&mut self, message: M) {
+    self.outbox.borrow_mut().push_back(message);
+  }
+
+  fn receive(&mut self) -> Option<M> {
+    self.inbox.borrow_mut().pop_front()
+  }
+
+}
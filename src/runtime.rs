@@ -9,7 +9,7 @@
 
 // ## Prelude
 
-use table::{Table, TableId, Value, Index};
+use table::{Table, TableId, Value, Index, Column};
 #[cfg(feature = "no-std")] use alloc::fmt;
 #[cfg(feature = "no-std")] use alloc::string::String;
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
@@ -18,11 +18,17 @@ use database::{Transaction, Interner, Change};
 use hashbrown::hash_map::{HashMap, Entry};
 use hashbrown::hash_set::HashSet;
 use indexes::TableIndex;
+use schema::{Schema, Kind};
 use operations;
 use operations::{Function, Comparator, Parameter, Logic};
 use quantities::{Quantity, ToQuantity, QuantityMath, make_quantity};
 use libm::{sin, cos, fmod, round, floor};
 use errors::{Error, ErrorType};
+use core::cmp;
+use core::mem;
+use core::slice;
+#[cfg(not(feature = "no-std"))] use pool;
+#[cfg(not(feature = "no-std"))] use std::panic;
 
 // ## Runtime
 
@@ -34,6 +40,10 @@ pub struct Runtime {
   pub ready_blocks: HashSet<usize>,
   pub changed_this_round: HashSet<(u64, Index)>,
   pub errors: Vec<Error>,
+  pub warnings: Vec<Error>, // things that aren't wrong yet, like feedback loops that might not settle
+  pub transactions: usize, // the number of the transaction being run, counting from one
+  pub schedule: Vec<Component>,
+  pub threads: usize, // how many blocks can be evaluated at once. Without std, blocks run one at a time.
 }
 
 impl Runtime {
@@ -46,6 +56,10 @@ impl Runtime {
       tables_map: HashMap::new(),
       changed_this_round: HashSet::new(),
       errors: Vec::new(),
+      warnings: Vec::new(),
+      transactions: 0,
+      schedule: Vec::new(),
+      threads: 1,
     }
   }
 
@@ -53,14 +67,32 @@ impl Runtime {
     self.blocks.clear();
     self.ready_blocks.clear();
     self.pipes_map.clear();
+    self.transactions = 0;
+    self.schedule.clear();
   }
 
   // Register a new block with the runtime
-  pub fn register_block(&mut self, mut block: Block, store: &mut Interner) {
+  pub fn register_block(&mut self, block: Block, store: &mut Interner) {
+    self.add_block(block, store);
+    self.schedule_blocks();
+  }
+
+  pub fn register_blocks(&mut self, blocks: Vec<Block>, store: &mut Interner) {
+    for block in blocks {
+      self.add_block(block, store);
+    }
+    self.schedule_blocks();
+  }
+
+  fn add_block(&mut self, mut block: Block, store: &mut Interner) {
     if block.id == 0 {
       // TODO Better auto ID. Maybe hash constraints?
       block.id = self.blocks.len() + 1;
     }
+    // Errors found while the block was put together didn't know its id
+    for error in block.errors.iter_mut().chain(block.shape_errors.iter_mut()) {
+      error.block = block.id as u64;
+    }
     // Take the input registers from the block and add them to the pipes map
     for register in block.input_registers.iter() {
       let table = register.table;
@@ -80,6 +112,16 @@ impl Runtime {
     for local_table in block.memory.map.keys() {
       self.tables_map.insert(*local_table, block.id as u64);
     }
+    // Keep the previous values of tables the block reads with #x'
+    for step in block.plan.iter() {
+      match step {
+        Constraint::Previous{table, ..} => store.remember(*table),
+        _ => (),
+      }
+    }
+    // Check what the block writes against the tables' schemas
+    let mut mismatches = block.check_schemas(&store.schemas);
+    block.errors.append(&mut mismatches);
     // Register all errors on the block with the runtime
     self.errors.append(&mut block.errors.clone());
 
@@ -89,12 +131,6 @@ impl Runtime {
     }
     // Add the block to our list of blocks
     self.blocks.insert(block.id, block.clone());
-  } 
-
-  pub fn register_blocks(&mut self, blocks: Vec<Block>, store: &mut Interner) {
-    for block in blocks {
-      self.register_block(block, store);
-    }
   }
 
   pub fn remove_block(&mut self, block_id: &usize) {
@@ -115,66 +151,400 @@ impl Runtime {
     self.blocks.remove(&block_id);
   }
 
+  // Bring the local memory of every block up to date with the store. Blocks
+  // that read tables which don't exist in the store are left as they are.
+  pub fn refresh_blocks(&mut self, store: &Interner) {
+    for block in self.blocks.values_mut() {
+      if block.state == BlockState::Error {
+        continue;
+      }
+      let satisfied = block.input_registers.iter().all(|register| {
+        store.get_table(register.table).is_some()
+      });
+      if satisfied {
+        block.refresh(store);
+      }
+    }
+  }
+
   // We've just interned some changes, and now we react to them by running the 
   // block graph. The graph is run until the tables reach a steady state or 
   // we hit the max_iteration limit
+  // Run the blocks that are ready, in the order of the schedule. Each block
+  // outside a feedback loop runs at most once. The blocks in a feedback loop
+  // run over and over until they settle, or until they've run 
+  // max_iterations times.
   pub fn run_network(&mut self, store: &mut Interner, max_iterations: u64) {
-    
-    let mut iteration_count = 0; 
-    // Note: The way this while loop is written, it's actually a do-while loop.
-    // This is a little trick in Rust. This means the network will always run
-    // at least one time, and if there are no more ready blocks after that run,
-    // the loop will terminate.
-    while {
-      let mut ready_blocks: Vec<usize> = self.ready_blocks.drain().map(|arg| {
-        arg.clone()
-      }).collect::<Vec<usize>>();
-      ready_blocks.sort();
-      for block_id in ready_blocks {
-        let block = &mut self.blocks.get_mut(&block_id).unwrap();
-        block.solve(store);
-        // Register any new inputs
-        for register in block.input_registers.iter() {
-          let table = register.table;
-          let column = register.column.clone();
-          let new_address = Address{block: block.id, register: register.clone()};
-          let listeners = self.pipes_map.entry(register.clone()).or_insert(HashSet::new());
-          listeners.insert(new_address);
-        }
+    // Blocks that run every so many transactions are due whatever changed
+    for (id, block) in self.blocks.iter_mut() {
+      if block.is_due(self.transactions) && block.is_ready() {
+        self.ready_blocks.insert(*id);
       }
-      // Queue up the next blocks based on tables that changed during this round.
-      for (table, column) in store.tables.changed_this_round.drain() {
-        self.changed_this_round.insert((table.clone(), column.clone()));
-        let register = Register::new(table,column);
-        match self.pipes_map.get(&register) {
-          Some(register_addresses) => {
-            for register_address in register_addresses.iter() {
-              let mut block = &mut self.blocks.get_mut(&register_address.block).unwrap();
-              block.ready.insert(register_address.register.clone());
-              if block.is_ready() {
-                self.ready_blocks.insert(register_address.block);
-              }
+    }
+    // The changes that started this transaction
+    self.propagate(store);
+    let mut passes = 0;
+    // A block can pick up new inputs while it runs, and then a block earlier 
+    // in the schedule might be ready again. If so, redo the schedule and go 
+    // around again.
+    while !self.ready_blocks.is_empty() && passes < max_iterations {
+      let schedule = self.schedule.clone();
+      let mut batch = Batch::new();
+      for component in schedule.iter() {
+        if !component.feedback {
+          let block_id = component.blocks[0];
+          if !batch.blocks.is_empty() && (self.threads <= 1 || batch.conflicts(&self.blocks[&block_id])) {
+            self.run_batch(&mut batch, store);
+          }
+          if self.ready_blocks.remove(&block_id) {
+            batch.add(&self.blocks[&block_id]);
+          }
+          continue;
+        }
+        self.run_batch(&mut batch, store);
+        let mut iteration_count = 0;
+        loop {
+          let ready: Vec<usize> = component.blocks.iter().filter(|id| self.ready_blocks.contains(id)).cloned().collect();
+          if ready.is_empty() {
+            break;
+          }
+          for block_id in ready {
+            self.ready_blocks.remove(&block_id);
+            self.run_block(block_id, store);
+          }
+          iteration_count += 1;
+          if iteration_count == max_iterations {
+            for block_id in component.blocks.iter() {
+              self.ready_blocks.remove(block_id);
             }
-          },
-          _ => (), // No listeners
+            let error = self.cycle_error(component, ErrorType::Oscillation);
+            self.errors.push(error);
+            break;
+          }
         }
       }
-      // Halt iterating if we've exceeded the maximum number of allowed iterations.
-      iteration_count += 1;
-      if iteration_count == max_iterations {
-        // TODO Insert an error into the db here instead.
-        //println!("Reached iteration limit {:?}", iteration_count);
-        self.ready_blocks.clear();        
+      self.run_batch(&mut batch, store);
+      passes += 1;
+      if !self.ready_blocks.is_empty() {
+        self.schedule_blocks();
       }
-      // Terminate if no blocks are ready to execute next round.
-      !self.ready_blocks.is_empty()
-    } {}
+    }
+    self.ready_blocks.clear();
     // Reset blocks' updated status
     for mut block in &mut self.blocks.values_mut() {
       block.updated = false;
     }
   }
 
+  fn run_block(&mut self, block_id: usize, store: &mut Interner) {
+    self.blocks.get_mut(&block_id).unwrap().evaluate(store);
+    self.finish_block(block_id, store);
+  }
+
+  // Write what the block computed to the store, and pass the changes on
+  fn finish_block(&mut self, block_id: usize, store: &mut Interner) {
+    {
+      let block = &mut self.blocks.get_mut(&block_id).unwrap();
+      block.commit(store);
+      // Register any new inputs
+      for register in block.input_registers.iter() {
+        let new_address = Address{block: block.id, register: register.clone()};
+        let listeners = self.pipes_map.entry(register.clone()).or_insert(HashSet::new());
+        listeners.insert(new_address);
+      }
+    }
+    self.propagate(store);
+  }
+
+  // Run a batch of blocks that don't depend on each other or write the same
+  // tables. With more than one thread, they're evaluated side by side
+  // against the store as it is now. Their changes go into the store in
+  // schedule order afterwards, so the result is the same as running them
+  // one after another.
+  fn run_batch(&mut self, batch: &mut Batch, store: &mut Interner) {
+    if batch.blocks.len() > 1 && self.threads > 1 {
+      self.evaluate_in_parallel(&batch.blocks, store);
+      for block_id in batch.blocks.drain(..) {
+        self.finish_block(block_id, store);
+      }
+    } else {
+      for block_id in batch.blocks.drain(..) {
+        self.run_block(block_id, store);
+      }
+    }
+    batch.clear();
+  }
+
+  // The blocks are evaluated where they are, on threads that borrow them for
+  // the length of the batch. Blocks don't read the archive, so it's set aside
+  // while the store is shared, and put back before any panic is passed on.
+  #[cfg(not(feature = "no-std"))]
+  fn evaluate_in_parallel(&mut self, batch: &[usize], store: &mut Interner) {
+    let wanted: HashSet<usize> = batch.iter().cloned().collect();
+    let blocks: Vec<&mut Block> = self.blocks.iter_mut().filter(|(id, _)| wanted.contains(id)).map(|(_, block)| block).collect();
+    let archive = store.archive.take();
+    let result = pool::evaluate(blocks, self.threads, store, Block::evaluate);
+    store.archive = archive;
+    if let Err(panic) = result {
+      panic::resume_unwind(panic);
+    }
+  }
+
+  // There are no threads without std
+  #[cfg(feature = "no-std")]
+  fn evaluate_in_parallel(&mut self, batch: &[usize], store: &mut Interner) {
+    for block_id in batch {
+      self.blocks.get_mut(block_id).unwrap().evaluate(store);
+    }
+  }
+
+  // Queue up the next blocks based on tables that changed since last time
+  fn propagate(&mut self, store: &mut Interner) {
+    // Blocks that read a whole table hear which of its rows changed
+    let changed_rows: Vec<(u64, Option<Vec<(usize, Index)>>)> = store.tables.changed_rows.drain().collect();
+    for (table, rows) in changed_rows {
+      match self.pipes_map.get(&Register::new(table, Index::Index(0))) {
+        Some(register_addresses) => {
+          for register_address in register_addresses.iter() {
+            self.blocks.get_mut(&register_address.block).unwrap().hear_rows(table, &rows);
+          }
+        },
+        None => (),
+      }
+    }
+    let changed: Vec<(u64, Index)> = store.tables.changed_this_round.drain().collect();
+    for (table, column) in changed {
+      self.changed_this_round.insert((table.clone(), column.clone()));
+      let register = Register::new(table,column);
+      match self.pipes_map.get(&register) {
+        Some(register_addresses) => {
+          for register_address in register_addresses.iter() {
+            let mut block = &mut self.blocks.get_mut(&register_address.block).unwrap();
+            // A block that only writes the table runs when it first shows up
+            let runs = if block.destinations.contains(&register_address.register) {
+              block.triggers.is_empty() && !block.ready.contains(&register_address.register)
+            } else {
+              block.is_triggered(&register_address.register, store)
+            };
+            block.ready.insert(register_address.register.clone());
+            if runs && block.is_ready() {
+              self.ready_blocks.insert(register_address.block);
+            }
+          }
+        },
+        _ => (), // No listeners
+      }
+    }
+  }
+
+  // Order the blocks so that every block comes after the blocks that write 
+  // its inputs. Blocks that feed into each other can't be put in order, so 
+  // they're grouped into a feedback loop that takes the place of one block.
+  pub fn schedule_blocks(&mut self) {
+    let mut ids: Vec<usize> = self.blocks.keys().cloned().collect();
+    ids.sort();
+    let mut readers: HashMap<u64, Vec<usize>> = HashMap::new();
+    for id in ids.iter() {
+      for table in self.blocks[id].reads() {
+        readers.entry(table).or_insert(Vec::new()).push(*id);
+      }
+    }
+    let mut dependents: HashMap<usize, Vec<usize>> = HashMap::new();
+    for id in ids.iter() {
+      let mut reading: Vec<usize> = self.blocks[id].writes().iter().flat_map(|table| {
+        readers.get(table).cloned().unwrap_or(Vec::new())
+      }).collect();
+      reading.sort();
+      reading.dedup();
+      dependents.insert(*id, reading);
+    }
+    let mut components = StronglyConnected::new(&dependents);
+    for id in ids.iter() {
+      if !components.indices.contains_key(id) {
+        components.visit(*id);
+      }
+    }
+    // Components come out with the last blocks to run first
+    self.schedule = components.components.drain(..).rev().map(|mut blocks| {
+      blocks.sort();
+      let feedback = blocks.len() > 1 || dependents[&blocks[0]].contains(&blocks[0]);
+      Component{blocks, feedback}
+    }).collect();
+    // Warn about every loop through more than one block, once for as long as
+    // it's there. They still run, and only if they don't settle are they 
+    // stopped at the iteration limit with an error. A block that writes its
+    // own input is left alone, since that's how a block keeps state.
+    let loops: Vec<Error> = self.schedule.iter().filter(|component| component.blocks.len() > 1).map(|component| {
+      self.cycle_error(component, ErrorType::CycleDetected)
+    }).collect();
+    self.warnings.retain(|warning| match warning.error_id {
+      ErrorType::CycleDetected(..) => loops.contains(warning),
+      _ => true,
+    });
+    for warning in loops {
+      if !self.warnings.contains(&warning) {
+        self.warnings.push(warning);
+      }
+    }
+  }
+
+  // An error naming the blocks in a feedback loop, and the registers they
+  // read that other blocks in the loop write
+  fn cycle_error(&self, component: &Component, error_type: fn(Vec<u64>, Vec<Register>) -> ErrorType) -> Error {
+    let writes: HashSet<u64> = component.blocks.iter().flat_map(|id| self.blocks[id].writes()).collect();
+    let mut registers = Vec::new();
+    for id in component.blocks.iter() {
+      let block = &self.blocks[id];
+      let mut read: Vec<Register> = block.input_registers.difference(&block.destinations).filter(|register| {
+        writes.contains(&register.table) && !registers.contains(*register)
+      }).cloned().collect();
+      read.sort_by_key(|register| register.table);
+      registers.append(&mut read);
+    }
+    let blocks: Vec<u64> = component.blocks.iter().map(|id| *id as u64).collect();
+    Error{
+      block: blocks[0],
+      constraint: Constraint::Null,
+      error_id: error_type(blocks, registers),
+    }
+  }
+
+}
+
+// ## Scheduling
+
+// Blocks that run together. A feedback loop is a set of blocks that write
+// each other's inputs, or a block that writes its own.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+  pub blocks: Vec<usize>,
+  pub feedback: bool,
+}
+
+// Tarjan's algorithm
+struct StronglyConnected<'a> {
+  dependents: &'a HashMap<usize, Vec<usize>>,
+  index: usize,
+  indices: HashMap<usize, usize>,
+  lowlinks: HashMap<usize, usize>,
+  stack: Vec<usize>,
+  on_stack: HashSet<usize>,
+  components: Vec<Vec<usize>>,
+}
+
+impl<'a> StronglyConnected<'a> {
+
+  fn new(dependents: &'a HashMap<usize, Vec<usize>>) -> StronglyConnected<'a> {
+    StronglyConnected {
+      dependents,
+      index: 0,
+      indices: HashMap::new(),
+      lowlinks: HashMap::new(),
+      stack: Vec::new(),
+      on_stack: HashSet::new(),
+      components: Vec::new(),
+    }
+  }
+
+  // Visit a block and everything downstream of it. This walks the graph with
+  // its own stack instead of recursing, so a long chain of blocks can't
+  // overflow the thread's stack. Each entry is a block along with how many
+  // of its dependents have been looked at so far.
+  fn visit(&mut self, root: usize) {
+    let dependents = self.dependents;
+    let mut calls = vec![(root, 0)];
+    self.open(root);
+    while let Some((id, next)) = calls.pop() {
+      match dependents[&id].get(next) {
+        Some(dependent) => {
+          calls.push((id, next + 1));
+          if !self.indices.contains_key(dependent) {
+            self.open(*dependent);
+            calls.push((*dependent, 0));
+          } else if self.on_stack.contains(dependent) {
+            let lowlink = cmp::min(self.lowlinks[&id], self.indices[dependent]);
+            self.lowlinks.insert(id, lowlink);
+          }
+        },
+        None => {
+          // Done with this block, so the block that reached it hears its lowlink
+          match calls.last() {
+            Some((caller, _)) => {
+              let lowlink = cmp::min(self.lowlinks[caller], self.lowlinks[&id]);
+              self.lowlinks.insert(*caller, lowlink);
+            },
+            None => (),
+          }
+          if self.lowlinks[&id] == self.indices[&id] {
+            self.close(id);
+          }
+        },
+      }
+    }
+  }
+
+  fn open(&mut self, id: usize) {
+    self.indices.insert(id, self.index);
+    self.lowlinks.insert(id, self.index);
+    self.index += 1;
+    self.stack.push(id);
+    self.on_stack.insert(id);
+  }
+
+  // Pop the component rooted at this block off the stack
+  fn close(&mut self, id: usize) {
+    let mut component = Vec::new();
+    loop {
+      let member = self.stack.pop().unwrap();
+      self.on_stack.remove(&member);
+      component.push(member);
+      if member == id {
+        break;
+      }
+    }
+    self.components.push(component);
+  }
+
+}
+
+// Blocks waiting to run together, along with every table they read and
+// write between them, so a new block is checked against the batch once
+// rather than against each block in it.
+struct Batch {
+  blocks: Vec<usize>,
+  reads: HashSet<u64>,
+  writes: HashSet<u64>,
+}
+
+impl Batch {
+
+  fn new() -> Batch {
+    Batch {
+      blocks: Vec::new(),
+      reads: HashSet::new(),
+      writes: HashSet::new(),
+    }
+  }
+
+  fn add(&mut self, block: &Block) {
+    self.blocks.push(block.id);
+    self.reads.extend(block.reads());
+    self.writes.extend(block.writes());
+  }
+
+  // Would running the block alongside the batch give a different answer
+  // than running it after?
+  fn conflicts(&self, block: &Block) -> bool {
+    let (reads, writes) = (block.reads(), block.writes());
+    !self.writes.is_disjoint(&reads) || !self.writes.is_disjoint(&writes) || !self.reads.is_disjoint(&writes)
+  }
+
+  fn clear(&mut self) {
+    self.blocks.clear();
+    self.reads.clear();
+    self.writes.clear();
+  }
+
 }
 
 impl fmt::Debug for Runtime {
@@ -228,6 +598,19 @@ impl fmt::Debug for Register {
   }
 }
 
+// What makes a block run. A block with no triggers runs whenever any of its
+// inputs change. A block with triggers runs only when one of them fires, and
+// reads the rest of its inputs without being scheduled by them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+  // Any of these registers changed
+  Change(Vec<Register>),
+  // The first cell of the register went from anything but true to true
+  RisingEdge(Register),
+  // Every n-th transaction
+  Every(usize),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum BlockState {
   Ready,
@@ -250,14 +633,21 @@ pub struct Block {
   pub plan: Vec<Constraint>,
   pub input_registers: HashSet<Register>,
   pub output_registers: HashSet<Register>,
+  pub destinations: HashSet<Register>, // inputs the block only writes
+  pub triggers: Vec<Trigger>,
   pub constraints: Vec<(String, Vec<Constraint>)>,
   pub errors: Vec<Error>,
+  shape_errors: Vec<Error>, // what the last check of the plan's shapes found
+  levels: HashMap<Register, bool>, // the last value seen by each rising edge trigger
+  last_run: Option<usize>, // the size of the change log when memory last caught up with the store
+  pending: HashMap<u64, Option<Vec<(usize, Index)>>>, // rows of global tables changed since then
+  sums: HashMap<u64, ExactSum>, // sums, by output, that changes can be added to exactly
   memory: TableIndex,
   scratch: Table,
-  lhs_rows_empty: Vec<Value>,
-  lhs_columns_empty: Vec<Value>,
-  rhs_rows_empty: Vec<Value>,
-  rhs_columns_empty: Vec<Value>,
+  lhs_rows_empty: Column,
+  lhs_columns_empty: Column,
+  rhs_rows_empty: Column,
+  rhs_columns_empty: Column,
   block_changes: Vec<Change>,
 }
 
@@ -274,15 +664,22 @@ impl Block {
       plan: Vec::new(),
       input_registers: HashSet::with_capacity(1),
       output_registers: HashSet::with_capacity(1),
+      destinations: HashSet::new(),
+      triggers: Vec::new(),
+      levels: HashMap::new(),
+      last_run: None,
+      pending: HashMap::new(),
+      sums: HashMap::new(),
       constraints: Vec::with_capacity(1),
       memory: TableIndex::new(1),
       errors: Vec::new(),
+      shape_errors: Vec::new(),
       scratch: Table::new(0,0,0),
       // allocate empty indices so we don't have to do this on each iteration
-      lhs_rows_empty: Vec::new(),
-      lhs_columns_empty: Vec::new(),
-      rhs_rows_empty: Vec::new(),
-      rhs_columns_empty: Vec::new(),
+      lhs_rows_empty: Column::new(0),
+      lhs_columns_empty: Column::new(0),
+      rhs_rows_empty: Column::new(0),
+      rhs_columns_empty: Column::new(0),
       block_changes: Vec::new(),
     }
   }
@@ -295,24 +692,6 @@ impl Block {
     self.constraints.push(constraint_tuple.clone());
     let (constraint_text, constraints) = constraint_tuple;
 
-    // Add relevant constraints to plan
-    let mut reversed = constraints.clone();
-    reversed.reverse();
-    for constraint in reversed {
-      match constraint {
-        Constraint::Filter{..} |
-        Constraint::Logic{..} |
-        Constraint::Function{..} |
-        Constraint::CopyTable{..} |
-        Constraint::Range{..} |
-        Constraint::ChangeScan{..} |
-        Constraint::Append{..} |
-        Constraint::Scan{..} |
-        Constraint::Insert{..} => self.plan.push(constraint.clone()),
-        _ => (),
-      }
-    }
-
     // Do any work we can up front
     for (constraint_ix, constraint) in constraints.iter().enumerate() {
       match constraint {
@@ -322,8 +701,7 @@ impl Block {
         Constraint::Append{from_table, to_table} => {
           match to_table {
             TableId::Global(id) => {
-              self.input_registers.insert(Register::new(*id, Index::Index(0)));
-              self.output_registers.insert(Register::new(*id, Index::Index(0)));
+              self.add_destination(Register::new(*id, Index::Index(0)));
             }
             _ => (),
           };
@@ -331,8 +709,7 @@ impl Block {
         Constraint::Insert{from: (from_table, ..), to: (to_table, ..)} => {
           match to_table {
             TableId::Global(id) => {
-              self.input_registers.insert(Register::new(*id, Index::Index(0)));
-              self.output_registers.insert(Register::new(*id, Index::Index(0)));
+              self.add_destination(Register::new(*id, Index::Index(0)));
             }, 
             _ => (),
           };
@@ -342,7 +719,7 @@ impl Block {
           //self.input_registers.push(Register::input(table, 1));
           match table {
             TableId::Global(id) => {
-              self.input_registers.insert(Register{table: *id, column: Index::Index(0)});
+              self.add_input(Register{table: *id, column: Index::Index(0)});
             },
             _ => (),
           }
@@ -350,10 +727,10 @@ impl Block {
         Constraint::ChangeScan{table, column} => {
           match (table, column.as_slice()) {
             (TableId::Global(id), [None, Some(Parameter::Index(index))]) => {
-              self.input_registers.insert(Register{table: *id, column: index.clone()});
+              self.add_input(Register{table: *id, column: index.clone()});
             },
             (TableId::Global(id), [None, None]) => {
-              self.input_registers.insert(Register{table: *id, column: Index::Index(0)});
+              self.add_input(Register{table: *id, column: Index::Index(0)});
             },
             _ => (),
           }
@@ -380,7 +757,7 @@ impl Block {
           for (table, rows, columns) in parameters {
             match table {
               TableId::Global(id) => {
-                self.input_registers.insert(Register{table: *id, column: Index::Index(0)});
+                self.add_input(Register{table: *id, column: Index::Index(0)});
               },
               _ => (),
             }
@@ -392,8 +769,8 @@ impl Block {
           match lhs_table {
             TableId::Global(id) => {
               match lhs_columns {
-                Some(Parameter::Index(index)) => self.input_registers.insert(Register{table: *id, column: index.clone()}),
-                _ => self.input_registers.insert(Register{table: *id, column: Index::Index(0)}),
+                Some(Parameter::Index(index)) => self.add_input(Register{table: *id, column: index.clone()}),
+                _ => self.add_input(Register{table: *id, column: Index::Index(0)}),
               };
             }
             _ => (),
@@ -401,8 +778,8 @@ impl Block {
           match rhs_table {
             TableId::Global(id) => {
               match rhs_columns {
-                Some(Parameter::Index(index)) => self.input_registers.insert(Register{table: *id, column: index.clone()}),
-                _ => self.input_registers.insert(Register{table: *id, column: Index::Index(0)}),
+                Some(Parameter::Index(index)) => self.add_input(Register{table: *id, column: index.clone()}),
+                _ => self.add_input(Register{table: *id, column: Index::Index(0)}),
               };
             }
             _ => (),
@@ -494,11 +871,94 @@ impl Block {
       }
     }
 
+    // Work out the plan again now that there's more to it
+    self.plan_steps();
+
+    // Errors in the plan can go away as more constraints fill it in
     if self.errors.len() > 0 {
       self.state = BlockState::Error;
+    } else if self.state == BlockState::Error {
+      self.state = BlockState::New;
+    }
+  }
+
+  // The block reads the register, so it runs when the register changes
+  fn add_input(&mut self, register: Register) {
+    self.destinations.remove(&register);
+    self.input_registers.insert(register);
+  }
+
+  // The block writes the table. It waits for the table to exist before it
+  // runs, but unless it reads the table too, changes to it don't run the 
+  // block again.
+  fn add_destination(&mut self, register: Register) {
+    if !self.input_registers.contains(&register) {
+      self.destinations.insert(register.clone());
+      self.input_registers.insert(register.clone());
+    }
+    self.output_registers.insert(register);
+  }
+
+  // The global tables the block reads
+  pub fn reads(&self) -> HashSet<u64> {
+    self.input_registers.difference(&self.destinations).map(|register| register.table).collect()
+  }
+
+  // The global tables the block writes
+  pub fn writes(&self) -> HashSet<u64> {
+    self.output_registers.iter().map(|register| register.table).collect()
+  }
+
+  // Registers named by a trigger are inputs of the block, so it hears about
+  // them changing
+  pub fn add_trigger(&mut self, trigger: Trigger) {
+    match &trigger {
+      Trigger::Change(registers) => {
+        for register in registers {
+          self.add_input(register.clone());
+        }
+      },
+      Trigger::RisingEdge(register) => {
+        self.add_input(register.clone());
+      },
+      Trigger::Every(_) => (),
     }
+    self.triggers.push(trigger);
+  }
 
+  // Does a change to the register fire one of the block's triggers?
+  pub fn is_triggered(&mut self, register: &Register, store: &Interner) -> bool {
+    if self.triggers.is_empty() {
+      return true;
+    }
+    let mut triggered = false;
+    for trigger in self.triggers.iter() {
+      match trigger {
+        Trigger::Change(registers) => {
+          triggered |= registers.contains(register);
+        },
+        Trigger::RisingEdge(edge) if edge == register => {
+          let high = match store.get_table(register.table) {
+            Some(table) if table.rows > 0 && table.get_column_index(&register.column).is_some() => {
+              table.get_value(&Index::Index(1), &register.column) == Some(Value::Bool(true))
+            },
+            _ => false,
+          };
+          let was_high = self.levels.insert(register.clone(), high).unwrap_or(false);
+          triggered |= high && !was_high;
+        },
+        _ => (),
+      }
+    }
+    triggered
+  }
 
+  // Is the block due to run on the given transaction?
+  pub fn is_due(&self, transaction: usize) -> bool {
+    self.triggers.iter().any(|trigger| match trigger {
+      Trigger::Every(n) => *n > 0 && transaction % n == 0,
+      _ => false,
+    })
   }
 
   pub fn is_ready(&mut self) -> bool {
@@ -517,7 +977,69 @@ impl Block {
   }
 
   pub fn solve(&mut self, store: &mut Interner) {
-    'solve_loop: for step in &self.plan {
+    self.evaluate(store);
+    self.commit(store);
+  }
+
+  // Write the changes from the last evaluation to the store
+  fn commit(&mut self, store: &mut Interner) {
+    if self.errors.len() > 0 {
+      self.state = BlockState::Error;
+    } else {
+      store.process_transaction(&Transaction::from_changeset(self.block_changes.clone()));
+      self.updated = true;
+    }
+    self.block_changes.clear();
+  }
+
+  // Recompute the block's local memory from the store as it is now, without
+  // writing anything back to it. This brings memory in line with the store
+  // after it has been rewound or replayed.
+  pub fn refresh(&mut self, store: &Interner) {
+    self.evaluate(store);
+    self.block_changes.clear();
+  }
+
+  // Run the plan, leaving the results in local memory and any changes to
+  // global tables in block_changes.
+  fn evaluate(&mut self, store: &Interner) {
+    let plan = mem::replace(&mut self.plan, Vec::new());
+    // Without a record of what changed since last time, go over everything
+    let mut deltas = match self.changes_since_last_run(store) {
+      Some(changes) => Some(Deltas::new(changes)),
+      None => None,
+    };
+    let mut finished = true;
+    for step in plan.iter() {
+      let handled = match deltas {
+        Some(ref mut deltas) => self.evaluate_delta(step, store, deltas),
+        None => false,
+      };
+      if !handled {
+        if !self.evaluate_steps(slice::from_ref(step), store) {
+          finished = false;
+          break;
+        }
+        self.note_sum(step);
+        match deltas {
+          Some(ref mut deltas) => deltas.everything_changed(step),
+          None => (),
+        }
+      }
+    }
+    self.plan = plan;
+    // Memory only agrees with the store if the whole plan ran against it now
+    self.last_run = if finished && store.offset == 0 && self.errors.is_empty() {
+      Some(store.changes_count)
+    } else {
+      None
+    };
+  }
+
+  // Run steps of the plan over whole tables. Returns false if the rest of
+  // the plan shouldn't run.
+  fn evaluate_steps(&mut self, steps: &[Constraint], store: &Interner) -> bool {
+    for step in steps {
       match step {
         Constraint::Scan{table, indices, output} => {
           let out_table = &output;
@@ -529,7 +1051,7 @@ impl Block {
             // TODO fix the way deep references work. This part is hacked and hard coded.
             // What needs to happen is this: We can recursively next any level of access on tables.
             // Then we can draw them easily. 
-            let id = match table_ref.data[0][0] {
+            let id = match table_ref.data[0].value(0) {
               Value::Reference(id) => {
                 self.ready.insert(Register::new(id, Index::Index(0)));
                 self.input_registers.insert(Register::new(id, Index::Index(0)));
@@ -542,10 +1064,10 @@ impl Block {
               None => table_ref
             };
             // If we only have one index, it's like this #x{3}
-            let one = vec![Value::from_u64(1)];
+            let one = Column::from_values(vec![Value::from_u64(1)]);
             let (row_ixes, column_ixes) = if indices.len() == 1 {
               // Get the ixes
-              let ixes: &Vec<Value> = match &indices[0] {
+              let ixes: &Column = match &indices[0] {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 _ => &self.rhs_rows_empty,
@@ -558,7 +1080,7 @@ impl Block {
                 (rows, 1) => (ixes, &one),
                 _ => {
                   // TODO Report an error here... or do matlab style ind2sub
-                  break 'solve_loop;
+                  return false;
                 }
               };
               (row_ixes, column_ixes)
@@ -566,12 +1088,12 @@ impl Block {
             // #x{1,2}
             // #x.y{1}
             } else {
-              let row_ixes: &Vec<Value> = match &indices[0] {
+              let row_ixes: &Column = match &indices[0] {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 _ => &self.rhs_rows_empty,
               };
-              let column_ixes: &Vec<Value> = match &indices[1] {
+              let column_ixes: &Column = match &indices[1] {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 Some(Parameter::Index(index)) => {
@@ -586,7 +1108,7 @@ impl Block {
                           error_id: ErrorType::MissingAttribute(index.clone()),
                         }
                       );
-                      break 'solve_loop;
+                      return false;
                     }, 
                   };
                   self.lhs_columns_empty.push(Value::from_u64(ix));
@@ -608,7 +1130,7 @@ impl Block {
               let mut column_mask = true;
               let cix = if column_ixes.is_empty() { i }
                         else { 
-                          match column_ixes[i] {
+                          match column_ixes.value(i) {
                             Value::Number(n) => n.to_u64() as usize  - 1,
                             Value::Bool(true) => i,
                             _ => {
@@ -622,7 +1144,7 @@ impl Block {
                 let mut row_mask = true;
                 let rix = if row_ixes.is_empty() { j }
                           else { 
-                            match row_ixes[j] {
+                            match row_ixes.value(j) {
                               Value::Number(n) => n.to_u64() as usize - 1,
                               Value::Bool(true) => j,
                               _ => {
@@ -632,7 +1154,7 @@ impl Block {
                             }
                           };
                 if column_mask == true && row_mask == true {
-                  //let value = table_ref.data[cix][rix];
+                  //let value = table_ref.data[cix].value(rix);
                   // Check bounds
                   if cix + 1 > table_ref.columns as usize || rix + 1 > table_ref.rows as usize {
                     self.errors.push(
@@ -642,9 +1164,9 @@ impl Block {
                         error_id: ErrorType::IndexOutOfBounds(((rix as u64 + 1, cix as u64 + 1),(table_ref.rows, table_ref.columns))),
                       }
                     );
-                    break 'solve_loop;
+                    return false;
                   }
-                  self.scratch.data[iix][jix] = table_ref.data[cix][rix].clone();
+                  self.scratch.data[iix].set(jix, table_ref.data[cix].value(rix));
                   jix += 1;
                   actual_height = jix;
                 }
@@ -677,10 +1199,10 @@ impl Block {
             (TableId::Local(id), _) => {
               // test value at table
               let table = self.memory.get(*id).unwrap();
-              if table.data[0][0] == Value::Bool(false) {
+              if table.data[0].value(0) == Value::Bool(false) {
                 self.block_changes.clear();
                 self.state = BlockState::Unsatisfied;
-                break 'solve_loop;
+                return false;
               }
             },
             _ => (),
@@ -688,7 +1210,7 @@ impl Block {
         },
         // TODO move most of this into Operations.rs
         Constraint::Function{operation, parameters, output} => { 
-          
+        
           // Concat Functions  
           if *operation == Function::HorizontalConcatenate {
             let out_table = &output[0];
@@ -697,12 +1219,12 @@ impl Block {
                 TableId::Local(id) => self.memory.get(*id).unwrap(),
                 TableId::Global(id) => store.get_table(*id).unwrap(),
               };
-              let row_ixes: &Vec<Value> = match rows {
+              let row_ixes: &Column = match rows {
                   Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                   Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                   _ => &self.rhs_rows_empty,
                 };
-              let column_ixes: &Vec<Value> = match columns {
+              let column_ixes: &Column = match columns {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 Some(Parameter::Index(index)) => {
@@ -730,7 +1252,7 @@ impl Block {
                   let mut column_mask = true;
                   let cix = if column_ixes.is_empty() { i }
                             else { 
-                              match column_ixes[i] {
+                              match column_ixes.value(i) {
                                 Value::Number(n) => n.mantissa() as usize  - 1,
                                 Value::Bool(true) => i,
                                 _ => {
@@ -744,7 +1266,7 @@ impl Block {
                     let mut row_mask = true;
                     let rix = if row_ixes.is_empty() { j }
                               else { 
-                                match row_ixes[j] {
+                                match row_ixes.value(j) {
                                   Value::Number(n) => n.mantissa() as usize - 1,
                                   Value::Bool(true) => j,
                                   _ => {
@@ -754,7 +1276,7 @@ impl Block {
                                 }
                               };
                     if column_mask == true && row_mask == true {
-                      self.scratch.data[iix][jix] = table_ref.data[cix][rix].clone();
+                      self.scratch.data[iix].set(jix, table_ref.data[cix].value(rix));
                       jix += 1;
                       actual_height = jix;
                     }
@@ -773,11 +1295,11 @@ impl Block {
                 self.scratch.grow_to_fit(end_row as u64, end_col as u64);
                 for i in start_col..end_col {
                   let cix = if column_ixes.is_empty() { i - start_col }
-                            else { column_ixes[i - start_col].as_u64().unwrap() as usize - 1 };
+                            else { column_ixes.value(i - start_col).as_u64().unwrap() as usize - 1 };
                   for j in 0..height as usize {
                     let rix: usize = if row_ixes.is_empty() { j }
-                              else { row_ixes[j as usize].as_u64().unwrap() as usize - 1 };
-                    self.scratch.data[i as usize][j as usize] = table_ref.data[cix][rix].clone();
+                              else { row_ixes.value(j as usize).as_u64().unwrap() as usize - 1 };
+                    self.scratch.data[i as usize].set(j as usize, table_ref.data[cix].value(rix));
                   }
                 }
               // Auto fill scalars to fit
@@ -789,11 +1311,11 @@ impl Block {
                 self.scratch.grow_to_fit(end_row as u64, end_col as u64);
                 for i in start_col..end_col {
                   let cix = if column_ixes.is_empty() { i - start_col }
-                            else { column_ixes[i - start_col].as_u64().unwrap() as usize - 1 };
+                            else { column_ixes.value(i - start_col).as_u64().unwrap() as usize - 1 };
                   for j in 0..end_row {
                     let rix: usize = if row_ixes.is_empty() { j }
-                              else { row_ixes[j as usize].as_u64().unwrap() as usize - 1 };
-                    self.scratch.data[i as usize][j as usize] = table_ref.data[cix][0].clone();
+                              else { row_ixes.value(j as usize).as_u64().unwrap() as usize - 1 };
+                    self.scratch.data[i as usize].set(j as usize, table_ref.data[cix].value(0));
                   }
                 }
               // Scale single row to fit new size
@@ -804,13 +1326,14 @@ impl Block {
                 // copy old stuff
                 for i in 0..old_width as usize {
                   for j in 1..self.scratch.rows as usize {
-                    self.scratch.data[i][j] = self.scratch.data[i][0].clone();
+                    let value = self.scratch.data[i].value(0);
+                    self.scratch.data[i].set(j, value);
                   }
                 }
                 // copy new stuff
                 for i in 0..width as usize {
                   for j in 0..height as usize {
-                    self.scratch.data[i + old_width as usize][j] = table_ref.data[i][j].clone();
+                    self.scratch.data[i + old_width as usize].set(j, table_ref.data[i].value(j));
                   }
                 }
               }
@@ -835,8 +1358,7 @@ impl Block {
               } else if self.scratch.columns == table_ref.columns {
                 let mut i = 0;
                 for column in &mut self.scratch.data {
-                  let mut col = table_ref.data[i].clone();
-                  column.append(&mut col);
+                  column.append(&table_ref.data[i]);
                   i += 1;
                 }
                 self.scratch.grow_to_fit(self.scratch.rows + table_ref.rows, self.scratch.columns);
@@ -857,19 +1379,22 @@ impl Block {
               (TableId::Local(argument), _, _) => *argument,
               _ => 0,
             };
-            let (value_table, value_rows, value_columns) = &parameters[1];            
+            let (value_table, value_rows, value_columns) = match parameters.get(1) {
+              Some(parameter) => parameter,
+              None => return false,
+            };
             let out_table = &output[0];
             {
               let rhs = match value_table {
                 TableId::Local(id) => self.memory.get(*id).unwrap(),
                 TableId::Global(id) => store.get_table(*id).unwrap(),
               };
-              let rhs_rows: &Vec<Value> = match value_rows {
+              let rhs_rows: &Column = match value_rows {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 _ => &self.rhs_rows_empty,
               };
-              let rhs_columns: &Vec<Value> = match value_columns {
+              let rhs_columns: &Column = match value_columns {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 Some(Parameter::Index(index)) => {
@@ -889,18 +1414,18 @@ impl Block {
               self.scratch.grow_to_fit(rhs_height, rhs_width);        
               for i in 0..rhs_width as usize {
                 let rcix = if rhs_columns.is_empty() { i }
-                          else { rhs_columns[i].as_u64().unwrap() as usize - 1 };
+                          else { rhs_columns.value(i).as_u64().unwrap() as usize - 1 };
                 for j in 0..rhs_height as usize {
                   let rrix = if rhs_rows.is_empty() { j }
-                            else { rhs_rows[j].as_u64().unwrap() as usize - 1 };
+                            else { rhs_rows.value(j).as_u64().unwrap() as usize - 1 };
                   let pi = 3.141592653589793238462643383279502884197169399375105820974944592307816406286;
-                  match (operation, argument, &rhs.data[rcix][rrix]) {
+                  match (operation, argument, rhs.data[rcix].value(rrix)) {
                     // column
                     (Function::StatSum, 0x756cddd0, Value::Number(x)) => {
-                      let previous = self.scratch.data[0][0].as_quantity().unwrap();
-                      match previous.add(*x) {
+                      let previous = self.scratch.data[0].value(0).as_quantity().unwrap();
+                      match previous.add(x) {
                         Ok(op_result) => {
-                          self.scratch.data[0][0] = Value::Number(op_result);
+                          self.scratch.data[0].set(0, Value::Number(op_result));
                           self.scratch.shrink_to_fit(1,1);
                         },
                         _ => (), // Throw an error here
@@ -908,10 +1433,10 @@ impl Block {
                     }
                     // row
                     (Function::StatSum, 0x776f72, Value::Number(x)) => {
-                      let previous = self.scratch.data[0][0].as_quantity().unwrap();
-                      match previous.add(*x) {
+                      let previous = self.scratch.data[0].value(0).as_quantity().unwrap();
+                      match previous.add(x) {
                         Ok(op_result) => {
-                          self.scratch.data[0][0] = Value::Number(op_result);
+                          self.scratch.data[0].set(0, Value::Number(op_result));
                           self.scratch.shrink_to_fit(1,1);
                         },
                         _ => (), // Throw an error here
@@ -920,22 +1445,22 @@ impl Block {
                     // column
                     (Function::MathRound, 0x756cddd0, Value::Number(x)) => {
                       let result = round(x.to_float());
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     // column
                     (Function::SetAny, 0x756cddd0, Value::Bool(x)) => {
-                      let new = match (x, &self.scratch.data[0][0]) {
+                      let new = match (x, self.scratch.data[0].value(0)) {
                         (false, Value::Empty) => Value::Bool(false),
                         (true, _) => Value::Bool(true),
                         (_, Value::Bool(true)) => Value::Bool(true),
                         (false, _) => Value::Bool(false),
                       };
-                      self.scratch.data[0][0] = new;
+                      self.scratch.data[0].set(0, new);
                     },
                     // column
                     (Function::MathFloor, 0x756cddd0, Value::Number(x)) => {
                       let result = floor(x.to_float());
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     // degrees
                     (Function::MathSin, 0x72dacac9, Value::Number(x)) => {
@@ -946,12 +1471,12 @@ impl Block {
                         270.0 => -1.0,
                         _ => sin(x.to_float() * pi / 180.0),
                       };
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     // radians
                     (Function::MathSin, 0x69d7cfd3, Value::Number(x)) => {
                       let result = sin(x.to_float());
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     // degrees
                     (Function::MathCos, 0x72dacac9, Value::Number(x)) => {
@@ -962,12 +1487,12 @@ impl Block {
                         270.0 => 0.0,
                         _ => cos(x.to_float() * pi / 180.0),
                       };
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     // radians
                     (Function::MathCos, 0x69d7cfd3, Value::Number(x)) => {
                       let result = cos(x.to_float());
-                      self.scratch.data[i][j] = Value::from_quantity(result.to_quantity());
+                      self.scratch.data[i].set(j, Value::from_quantity(result.to_quantity()));
                     },
                     _ => (),
                   }
@@ -1007,17 +1532,17 @@ impl Block {
                 TableId::Local(id) => self.memory.get(*id).unwrap(),
                 TableId::Global(id) => store.get_table(*id).unwrap(),
               };
-              let lhs_rows: &Vec<Value> = match lhs_rows {
+              let lhs_rows: &Column = match lhs_rows {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 _ => &self.lhs_rows_empty,
               };
-              let rhs_rows: &Vec<Value> = match rhs_rows {
+              let rhs_rows: &Column = match rhs_rows {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 _ => &self.rhs_rows_empty,
               };
-              let lhs_columns: &Vec<Value> = match lhs_columns {
+              let lhs_columns: &Column = match lhs_columns {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 Some(Parameter::Index(index)) => {
@@ -1030,7 +1555,7 @@ impl Block {
                 },
                 _ => &self.lhs_rows_empty,
               };
-              let rhs_columns: &Vec<Value> = match rhs_columns {
+              let rhs_columns: &Column = match rhs_columns {
                 Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
                 Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
                 Some(Parameter::Index(index)) => {
@@ -1068,7 +1593,7 @@ impl Block {
               );
             }
             if errors.len() > 0 {
-              break 'solve_loop;
+              return false;
             }
           }
         },
@@ -1094,18 +1619,18 @@ impl Block {
               TableId::Local(id) => self.memory.get(*id).unwrap(),
               TableId::Global(id) => store.get_table(*id).unwrap(),
             };
-            let lhs_rows: &Vec<Value> = match lhs_rows {
+            let lhs_rows: &Column = match lhs_rows {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               _ => &self.lhs_rows_empty,
             };
-            let rhs_rows: &Vec<Value> = match rhs_rows {
+            let rhs_rows: &Column = match rhs_rows {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               _ => &self.rhs_rows_empty,
             };
-            
-            let lhs_columns: &Vec<Value> = match lhs_columns {
+          
+            let lhs_columns: &Column = match lhs_columns {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1118,7 +1643,7 @@ impl Block {
               },
               _ => &self.lhs_rows_empty,
             };
-            let rhs_columns: &Vec<Value> = match rhs_columns {
+            let rhs_columns: &Column = match rhs_columns {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1160,17 +1685,17 @@ impl Block {
               TableId::Local(id) => self.memory.get(*id).unwrap(),
               TableId::Global(id) => store.get_table(*id).unwrap(),
             };
-            let lhs_rows: &Vec<Value> = match lhs_rows {
+            let lhs_rows: &Column = match lhs_rows {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               _ => &self.lhs_rows_empty,
             };
-            let rhs_rows: &Vec<Value> = match rhs_rows {
+            let rhs_rows: &Column = match rhs_rows {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               _ => &self.rhs_rows_empty,
             };
-            let lhs_columns: &Vec<Value> = match lhs_columns {
+            let lhs_columns: &Column = match lhs_columns {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1183,7 +1708,7 @@ impl Block {
               },
               _ => &self.lhs_rows_empty,
             };
-            let rhs_columns: &Vec<Value> = match rhs_columns {
+            let rhs_columns: &Column = match rhs_columns {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1209,11 +1734,11 @@ impl Block {
         },
         Constraint::Range{table, start, end} => {
           {
-            let start_value = &self.memory.get(*start.unwrap()).unwrap().data[0][0].as_u64().unwrap().clone();
-            let end_value = &self.memory.get(*end.unwrap()).unwrap().data[0][0].as_u64().unwrap().clone();
+            let start_value = self.memory.get(*start.unwrap()).unwrap().data[0].value(0).as_u64().unwrap();
+            let end_value = self.memory.get(*end.unwrap()).unwrap().data[0].value(0).as_u64().unwrap();
             self.scratch.grow_to_fit(end_value - start_value + 1, 1);
             let mut row = 1;
-            for i in *start_value..*end_value + 1 {
+            for i in start_value..end_value + 1 {
               self.scratch.set_cell(&Index::Index(row), &Index::Index(1), Value::from_u64(i));
               row += 1;
             }
@@ -1238,7 +1763,7 @@ impl Block {
             TableId::Global(id) => (store.get_table(*id).unwrap(), id.clone()),
           };
 
-          let from_column_values: &Vec<Value> = match &from_ixes[1] {
+          let from_column_values: &Column = match &from_ixes[1] {
             Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
             Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
             Some(Parameter::Index(index)) => {
@@ -1252,7 +1777,7 @@ impl Block {
             _ => &self.rhs_columns_empty,
           };
 
-          let from_row_values: &Vec<Value> = match &from_ixes[0] {
+          let from_row_values: &Column = match &from_ixes[0] {
             Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
             Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
             Some(Parameter::Index(index)) => {
@@ -1268,10 +1793,10 @@ impl Block {
 
 
          // If we only have one index, it's like this #x{3} := ...
-          let one = vec![Value::from_u64(1)];
+          let one = Column::from_values(vec![Value::from_u64(1)]);
           let (to_row_values, to_column_values) = if to_ixes.len() == 1 {
             // Get the ixes
-            let ixes: &Vec<Value> = match &to_ixes[0] {
+            let ixes: &Column = match &to_ixes[0] {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               _ => &self.rhs_rows_empty,
@@ -1284,14 +1809,14 @@ impl Block {
               (rows, 1) => (ixes, &one),
               _ => {
                 // TODO Report an error here... or do matlab style ind2sub
-                break 'solve_loop;
+                return false;
               }
             };
             (row_ixes, column_ixes)
           // Otherwise we have a couple choices:
           // #x{1,2}
           } else {
-            let to_column_values: &Vec<Value> = match &to_ixes[1] {
+            let to_column_values: &Column = match &to_ixes[1] {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1305,7 +1830,7 @@ impl Block {
               _ => &self.lhs_columns_empty,
             };
 
-            let to_row_values: &Vec<Value> = match &to_ixes[0] {
+            let to_row_values: &Column = match &to_ixes[0] {
               Some(Parameter::TableId(TableId::Local(id))) => &self.memory.get(*id).unwrap().data[0],
               Some(Parameter::TableId(TableId::Global(id))) => &store.get_table(*id).unwrap().data[0],
               Some(Parameter::Index(index)) => {
@@ -1336,21 +1861,21 @@ impl Block {
           if from_is_scalar {
             for i in 0..to_width as usize {
               let cix = if to_column_values.is_empty() { i }
-                        else { to_column_values[i].as_u64().unwrap() as usize - 1 };
+                        else { to_column_values.value(i).as_u64().unwrap() as usize - 1 };
               for j in 0..to_height as usize {
                 // If to_row_values are empty, it means we're matching over all the rows
                 // otherwise we take the truth value from the vector and use it
                 let truth = if to_row_values.is_empty() {
                   &Value::Bool(true)
                 } else {
-                  &to_row_values[j]
+                  &to_row_values.value(j)
                 };
                 match truth {
                   Value::Bool(true) => {
                     let change = Change::Set{table: to_table_id.clone(), 
                                              row: Index::Index(j as u64 + 1), 
                                              column: Index::Index(cix as u64 + 1),
-                                             value: from.data[0][0].clone() 
+                                             value: from.data[0].value(0) 
                                             };
                     self.block_changes.push(change);
                   },
@@ -1360,7 +1885,7 @@ impl Block {
                       let change = Change::Set{table: to_table_id.clone(), 
                                               row: Index::Index(ix as u64), 
                                               column: Index::Index(cix as u64 + 1),
-                                              value: from.data[0][0].clone() 
+                                              value: from.data[0].value(0) 
                                               };
                       self.block_changes.push(change); 
                     }
@@ -1374,7 +1899,7 @@ impl Block {
             for i in 0..from_width as usize {
               let fcix = if from_column_values.is_empty() { i }
                          else {
-                           match from_column_values[i] {
+                           match from_column_values.value(i) {
                              Value::Number(x) => x.mantissa() as usize  - 1,
                              Value::Bool(true) => i,
                              _ => {continue; 0}, // This continues before the return
@@ -1382,7 +1907,7 @@ impl Block {
                          };
               let tcix = if to_column_values.is_empty() { i }
                          else {
-                           match to_column_values[i] {
+                           match to_column_values.value(i) {
                              Value::Number(x) => x.mantissa() as usize  - 1,
                              Value::Bool(true) => i,
                              _ => {continue; 0},
@@ -1391,7 +1916,7 @@ impl Block {
               for j in 0..from_height as usize {
                 let frix = if from_row_values.is_empty() { j }
                            else {
-                             match from_row_values[j] {
+                             match from_row_values.value(j) {
                                Value::Number(x) => x.mantissa() as usize  - 1,
                                Value::Bool(true) => j,
                                _ => {continue; 0},
@@ -1400,7 +1925,7 @@ impl Block {
 
                 let trix = if to_row_values.is_empty() { j }
                            else {
-                             match to_row_values[j] {
+                             match to_row_values.value(j) {
                                Value::Number(x) => x.mantissa() as usize  - 1,
                                Value::Bool(true) => j,
                                _ => {continue; 0},
@@ -1409,7 +1934,7 @@ impl Block {
                 let change = Change::Set{table: to_table_id.clone(), 
                                           row: Index::Index(trix as u64 + 1), 
                                           column: Index::Index(tcix as u64 + 1),
-                                          value: from.data[fcix][frix].clone() 
+                                          value: from.data[fcix].value(frix) 
                                         };
                 self.block_changes.push(change);
               }
@@ -1437,11 +1962,29 @@ impl Block {
           if from_width == to_width {
             for i in 0..from_width as usize {
               for j in 0..from.rows as usize {
-                self.block_changes.push(Change::Set{table: *to_id, row: Index::Index((j as u64 + to.rows) + 1), column: Index::Index(i as u64 + 1), value: from.data[i][j].clone() });
+                self.block_changes.push(Change::Set{table: *to_id, row: Index::Index((j as u64 + to.rows) + 1), column: Index::Index(i as u64 + 1), value: from.data[i].value(j) });
               }
             }
           }
         },
+        Constraint::Previous{table, output} => {
+          // A table that didn't exist at the last boundary reads as it is now.
+          // Previous values don't change during a transaction, so reading
+          // one doesn't make the block an input of the table.
+          let source = match store.previous.get(*table) {
+            Some(table_ref) => Some(table_ref),
+            None => store.get_table(*table),
+          };
+          match (source, output) {
+            (Some(table_ref), TableId::Local(id)) => {
+              let mut copy = table_ref.clone();
+              copy.id = *id;
+              self.memory.remove(id);
+              self.memory.insert(copy);
+            },
+            _ => (),
+          }
+        },
         Constraint::CopyTable{from_table, to_table} => {
           let from_table_ref = self.memory.get(*from_table).unwrap();
           let mut changes = vec![Change::NewTable{id: *to_table, rows: from_table_ref.rows, columns: from_table_ref.columns}];
@@ -1450,11 +1993,23 @@ impl Block {
           }
           for (col_ix, column) in from_table_ref.data.iter().enumerate() {
             for (row_ix, data) in column.iter().enumerate() {
-              changes.push(Change::Set{table: *to_table, row: Index::Index(row_ix as u64 + 1), column: Index::Index(col_ix as u64 + 1), value: data.clone()});
+              changes.push(Change::Set{table: *to_table, row: Index::Index(row_ix as u64 + 1), column: Index::Index(col_ix as u64 + 1), value: data});
             }
           }
           self.block_changes.append(&mut changes);
         },
+        Constraint::Fused{steps} => {
+          match self.evaluate_fused(steps, store) {
+            Some(true) => (),
+            Some(false) => return false,
+            // The shapes don't line up for one pass, so take the steps one at a time
+            None => {
+              if !self.evaluate_steps(steps, store) {
+                return false;
+              }
+            },
+          }
+        },
         Constraint::NewTable{id, rows, columns} => {
           match id {
             TableId::Global(id) => {
@@ -1464,56 +2019,1452 @@ impl Block {
           }
         },
         _ => (),
-      } 
-      
-    }
-    if self.errors.len() > 0 {
-      self.state = BlockState::Error;
-    } else {
-      store.process_transaction(&Transaction::from_changeset(self.block_changes.clone()));
-      self.updated = true;
+      }
     }
-    self.block_changes.clear();
+    true
   }
 }
 
-impl fmt::Debug for Block {
-  #[inline]
-  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    write!(f, "┌────────────────────────────────────────┐\n").unwrap();
-    write!(f, "│ Block {:?} ({:#x})\n", self.name, self.id).unwrap();
-    write!(f, "├────────────────────────────────────────┤\n").unwrap();
-    write!(f, "│ \n{}\n",self.text).unwrap();
-    write!(f, "├────────────────────────────────────────┤\n").unwrap();
-    write!(f, "│ Errors:\n").unwrap();
-    write!(f, "│ {:?}\n", self.errors).unwrap();
-    write!(f, "├────────────────────────────────────────┤\n").unwrap();
-    write!(f, "│ State: {:?}\n", self.state).unwrap();
-    write!(f, "│ Ready: {:?}\n", self.ready).unwrap();
-    write!(f, "│ Updated: {:?}\n", self.updated).unwrap();
-    write!(f, "│ Input: {:?}\n", self.input_registers.len()).unwrap();
-    for (ix, register) in self.input_registers.iter().enumerate() {
-      write!(f, "│  {:?}. {:?}\n", ix + 1, register).unwrap();
+// ## Incremental Evaluation
+
+// Once a block has run, it doesn't have to go over whole tables again. The
+// change log says which rows of which tables changed since the last run, and
+// those changes flow through the plan as deltas. Scans copy only the rows
+// that changed, elementwise math and comparisons recompute only those rows,
+// sums are adjusted by the difference when that comes out exactly the same
+// as adding everything up again, and inserts write only those rows back. A step that can't work from a delta runs in full, and everything 
+// downstream of it sees its whole output as changed.
+
+struct Deltas {
+  // Rows and columns changed in global tables. None means the table changed
+  // shape, or was replaced, so there's no telling which rows changed.
+  global: HashMap<u64, Option<Vec<(usize, Index)>>>,
+  // Rows changed in local tables during this run. Tables that aren't here 
+  // didn't change at all.
+  local: HashMap<u64, Option<Vec<usize>>>,
+  // Rows of local tables as they were before this run first overwrote them
+  replaced: HashMap<u64, HashMap<usize, Vec<Value>>>,
+}
+
+impl Deltas {
+
+  fn new(global: HashMap<u64, Option<Vec<(usize, Index)>>>) -> Deltas {
+    Deltas {
+      global,
+      local: HashMap::new(),
+      replaced: HashMap::new(),
     }
-    write!(f, "│ Output: {:?}\n", self.output_registers.len()).unwrap();
-    for (ix, register) in self.output_registers.iter().enumerate() {
-      write!(f, "│  {:?}. {:?}\n", ix + 1, register).unwrap();
+  }
+
+  fn global_rows(&self, table: u64) -> Option<Vec<(usize, Index)>> {
+    match self.global.get(&table) {
+      Some(rows) => rows.clone(),
+      None => Some(Vec::new()),
     }
-    write!(f, "│ Constraints: {:?}\n", self.constraints.len()).unwrap();
-    for (ix, (text, constraint)) in self.constraints.iter().enumerate() {
-      write!(f, "│  {}. {}\n", ix + 1, text).unwrap();
-      for constraint_step in constraint {
-        write!(f, "│    > {:?}\n", constraint_step).unwrap();
+  }
+
+  fn local_rows(&self, table: u64) -> Option<Vec<usize>> {
+    match self.local.get(&table) {
+      Some(rows) => rows.clone(),
+      None => Some(Vec::new()),
+    }
+  }
+
+  // The step ran over whole tables, so all of its output is new
+  fn everything_changed(&mut self, step: &Constraint) {
+    match step {
+      Constraint::Fused{steps} => {
+        for inner in steps {
+          self.everything_changed(inner);
+        }
+        return;
+      },
+      _ => (),
+    }
+    for output in step_writes(step) {
+      match output {
+        TableId::Local(id) => {
+          self.local.insert(id, None);
+        },
+        _ => (),
       }
     }
-    write!(f, "│ Plan: {:?}\n", self.plan.len()).unwrap();
-    for (ix, step) in self.plan.iter().enumerate() {
-      write!(f, "│  {:?}. {:?}\n", ix + 1, step).unwrap();
+  }
+
+}
+
+// Adding numbers up is only the same in any order, and so only the same
+// from a delta as over the whole table, when it's whole number arithmetic on
+// their mantissas: they all have the same range, they have no domain (adding
+// up anything else comes out empty), and they're small enough together that
+// no running total can overflow.
+#[derive(Clone, Debug, PartialEq)]
+struct ExactSum {
+  range: i64,
+  count: usize, // how many numbers are added up
+  magnitude: u64, // their mantissas' absolute values, added up
+}
+
+impl ExactSum {
+
+  const LIMIT: u64 = 1 << 47;
+
+  fn of(table: &Table) -> Option<ExactSum> {
+    let mut exact: Option<ExactSum> = None;
+    for column in table.data.iter().take(table.columns as usize) {
+      for row in 0..table.rows as usize {
+        if let Value::Number(number) = column.value(row) {
+          let exact = exact.get_or_insert(ExactSum{range: number.range(), count: 0, magnitude: 0});
+          if !exact.add(number) {
+            return None;
+          }
+        }
+      }
     }
-    write!(f, "└────────────────────────────────────────┘\n").unwrap();
-    write!(f, "{:?}\n", self.memory).unwrap();
-    Ok(())
+    exact
+  }
+
+  // Returns false if the sum isn't exact any more
+  fn add(&mut self, number: Quantity) -> bool {
+    self.count += 1;
+    self.magnitude += number.mantissa().unsigned_abs();
+    number.domain() == 0 && number.range() == self.range && self.magnitude < ExactSum::LIMIT
   }
+
+  fn take(&mut self, number: Quantity) {
+    self.count -= 1;
+    self.magnitude -= number.mantissa().unsigned_abs();
+  }
+
+}
+
+// The input and output of a sum down a column or along a row
+fn summed(step: &Constraint) -> Option<(u64, u64)> {
+  match step {
+    Constraint::Function{operation: Function::StatSum, parameters, output} => {
+      match (parameters.first(), parameters.get(1), output.as_slice()) {
+        (Some((TableId::Local(0x756cddd0), _, _)), Some((TableId::Local(input), None, None)), [TableId::Local(output)]) |
+        (Some((TableId::Local(0x776f72), _, _)), Some((TableId::Local(input), None, None)), [TableId::Local(output)]) => Some((*input, *output)),
+        _ => None,
+      }
+    },
+    _ => None,
+  }
+}
+
+fn sorted_rows<I: Iterator<Item=usize>>(rows: I) -> Vec<usize> {
+  let mut rows: Vec<usize> = rows.collect();
+  rows.sort();
+  rows.dedup();
+  rows
+}
+
+impl Block {
+
+  // The rows and columns of global tables that changed since the block last
+  // ran, as the runtime passed them on. None if the block has to start over.
+  fn changes_since_last_run(&mut self, store: &Interner) -> Option<HashMap<u64, Option<Vec<(usize, Index)>>>> {
+    let pending = mem::replace(&mut self.pending, HashMap::new());
+    let since = self.last_run?;
+    if store.offset > 0 || since > store.changes_count {
+      return None;
+    }
+    Some(pending)
+  }
+
+  // Rows of a table the block reads changed. They're only kept while memory
+  // is caught up with the store, since otherwise the block starts over.
+  fn hear_rows(&mut self, table: u64, rows: &Option<Vec<(usize, Index)>>) {
+    if self.last_run.is_none() {
+      return;
+    }
+    let pending = self.pending.entry(table).or_insert(Some(Vec::new()));
+    match (pending, rows) {
+      (Some(pending), Some(rows)) => pending.extend(rows.iter().cloned()),
+      (pending, _) => *pending = None,
+    }
+  }
+
+  // Overwrite rows of a local table, keeping what was there before
+  fn replace_rows(&mut self, table: u64, rows: Vec<(usize, Vec<Value>)>, deltas: &mut Deltas) {
+    let out = self.memory.get_mut(table).unwrap();
+    let replaced = deltas.replaced.entry(table).or_insert(HashMap::new());
+    let mut changed = Vec::with_capacity(rows.len());
+    for (row, values) in rows {
+      let old: Vec<Value> = out.data.iter().map(|column| column.value(row)).collect();
+      for (column, value) in values.into_iter().enumerate() {
+        out.data[column].set(row, value);
+      }
+      // A row written twice in one run replaced what it held before either
+      replaced.entry(row).or_insert(old);
+      changed.push(row);
+    }
+    match deltas.local.entry(table).or_insert(Some(Vec::new())) {
+      Some(rows) => {
+        rows.extend(changed);
+        rows.sort();
+        rows.dedup();
+      },
+      None => (),
+    }
+  }
+
+  // Try to run the step from deltas. Returns false if it has to run in full.
+  fn evaluate_delta(&mut self, step: &Constraint, store: &Interner, deltas: &mut Deltas) -> bool {
+    match step {
+      Constraint::Scan{table: TableId::Global(table), indices, output: TableId::Local(output)} => {
+        self.scan_delta(*table, indices, *output, store, deltas)
+      },
+      Constraint::Function{operation: Function::StatSum, ..} => {
+        match summed(step) {
+          Some((input, output)) => self.sum_delta(input, output, deltas),
+          None => false,
+        }
+      },
+      Constraint::Function{operation, parameters, output} => {
+        let op_fun = match operation {
+          Function::Add => operations::math_add,
+          Function::Subtract => operations::math_subtract,
+          Function::Multiply => operations::math_multiply,
+          Function::Divide => operations::math_divide,
+          Function::Power => operations::math_power,
+          _ => return false,
+        };
+        match (parameters.as_slice(), output.as_slice()) {
+          ([(TableId::Local(lhs), None, None), (TableId::Local(rhs), None, None)], [TableId::Local(output)]) => {
+            self.elementwise_delta(*lhs, *rhs, *output, deltas, &op_fun)
+          },
+          _ => false,
+        }
+      },
+      Constraint::Filter{comparator, lhs: (TableId::Local(lhs), None, None), rhs: (TableId::Local(rhs), None, None), output: TableId::Local(output)} => {
+        let op_fun = match comparator {
+          Comparator::NotEqual => operations::compare_not_equal,
+          Comparator::Equal => operations::compare_equal,
+          Comparator::LessThanEqual => operations::compare_less_than_equal,
+          Comparator::GreaterThanEqual => operations::compare_greater_than_equal,
+          Comparator::GreaterThan => operations::compare_greater_than,
+          Comparator::LessThan => operations::compare_less_than,
+          _ => return false,
+        };
+        let compare = |lhs: &Table, lhs_rows: &Column, lhs_columns: &Column,
+                       rhs: &Table, rhs_rows: &Column, rhs_columns: &Column,
+                       out: &mut Table, _errors: &mut Vec<ErrorType>| {
+          op_fun(lhs, lhs_rows, lhs_columns, rhs, rhs_rows, rhs_columns, out)
+        };
+        self.elementwise_delta(*lhs, *rhs, *output, deltas, &compare)
+      },
+      // The chain keeps every step's output, so it can go a step at a time. If 
+      // a step can't, the whole chain runs again.
+      Constraint::Fused{steps} => {
+        steps.iter().all(|inner| self.evaluate_delta(inner, store, deltas))
+      },
+      Constraint::Insert{from: (TableId::Local(from), from_ixes), to: (TableId::Global(to), to_ixes)} => {
+        if from_ixes.iter().chain(to_ixes.iter()).any(|ix| ix.is_some()) {
+          return false;
+        }
+        self.insert_delta(*from, *to, store, deltas)
+      },
+      _ => false,
+    }
+  }
+
+  // #x or #x.y, copying only the rows that changed
+  fn scan_delta(&mut self, table: u64, indices: &Vec<Option<Parameter>>, output: u64, store: &Interner, deltas: &mut Deltas) -> bool {
+    let table_ref = match store.get_table(table) {
+      Some(table_ref) => table_ref,
+      None => return false,
+    };
+    let changes = match deltas.global_rows(table) {
+      Some(changes) => changes,
+      None => return false,
+    };
+    // References are followed by the full scan
+    match table_ref.data.get(0).and_then(|column| column.get(0)) {
+      Some(Value::Reference(_)) => return false,
+      _ => (),
+    }
+    let columns: Vec<usize> = match indices.as_slice() {
+      [None, None] => (0..table_ref.columns as usize).collect(),
+      [None, Some(Parameter::Index(index))] => match table_ref.get_column_index(index) {
+        Some(ix) if ix > 0 => vec![ix as usize - 1],
+        _ => return false,
+      },
+      _ => return false,
+    };
+    {
+      let out = self.memory.get(output).unwrap();
+      if out.rows != table_ref.rows || out.columns != columns.len() as u64 {
+        return false;
+      }
+    }
+    let changed = sorted_rows(changes.iter().filter(|(row, column)| {
+      *row < table_ref.rows as usize && (columns.len() == table_ref.columns as usize || {
+        match table_ref.get_column_index(column) {
+          Some(ix) => columns[0] + 1 == ix as usize,
+          None => false,
+        }
+      })
+    }).map(|(row, _)| *row));
+    let rows = changed.iter().map(|row| {
+      (*row, columns.iter().map(|column| table_ref.data[*column].value(*row)).collect())
+    }).collect();
+    self.replace_rows(output, rows, deltas);
+    true
+  }
+
+  // Recompute the rows of an elementwise operation whose inputs changed. A
+  // scalar broadcast over the other side changes every row when it changes.
+  fn elementwise_delta(&mut self, lhs: u64, rhs: u64, output: u64, deltas: &mut Deltas,
+                       op_fun: &dyn Fn(&Table, &Column, &Column, &Table, &Column, &Column, &mut Table, &mut Vec<ErrorType>)) -> bool {
+    let (lhs_rows, rhs_rows) = match (deltas.local_rows(lhs), deltas.local_rows(rhs)) {
+      (Some(lhs_rows), Some(rhs_rows)) => (lhs_rows, rhs_rows),
+      _ => return false,
+    };
+    let mut rows = Vec::new();
+    {
+      let lhs_table = self.memory.get(lhs).unwrap();
+      let rhs_table = self.memory.get(rhs).unwrap();
+      let out = self.memory.get(output).unwrap();
+      let lhs_is_scalar = lhs_table.rows == 1 && lhs_table.columns == 1;
+      let rhs_is_scalar = rhs_table.rows == 1 && rhs_table.columns == 1;
+      let same_size = lhs_table.rows == rhs_table.rows && lhs_table.columns == rhs_table.columns;
+      let (shape, changed) = if same_size {
+        ((lhs_table.rows, lhs_table.columns), sorted_rows(lhs_rows.into_iter().chain(rhs_rows.into_iter())))
+      } else if lhs_is_scalar && lhs_rows.is_empty() {
+        ((rhs_table.rows, rhs_table.columns), rhs_rows)
+      } else if rhs_is_scalar && rhs_rows.is_empty() {
+        ((lhs_table.rows, lhs_table.columns), lhs_rows)
+      } else {
+        return false;
+      };
+      if (out.rows, out.columns) != shape {
+        return false;
+      }
+      let mut scratch = Table::new(0, 0, 0);
+      let mut errors = Vec::new();
+      let all = Column::new(0);
+      for row in changed {
+        let this_row = Column::from_values(vec![Value::from_u64(row as u64 + 1)]);
+        let lhs_row = if same_size || !lhs_is_scalar { &this_row } else { &all };
+        let rhs_row = if same_size || !rhs_is_scalar { &this_row } else { &all };
+        op_fun(lhs_table, lhs_row, &all, rhs_table, rhs_row, &all, &mut scratch, &mut errors);
+        if !errors.is_empty() {
+          // Let the full step report the errors
+          return false;
+        }
+        rows.push((row, scratch.data.iter().map(|column| column.value(0)).collect()));
+        scratch.clear();
+      }
+    }
+    self.replace_rows(output, rows, deltas);
+    true
+  }
+
+  // Adjust a sum by the difference between the rows that changed and what
+  // they replaced. Only sums noted as exact are adjusted, and only while they
+  // stay that way.
+  fn sum_delta(&mut self, input: u64, output: u64, deltas: &mut Deltas) -> bool {
+    let changed = match deltas.local_rows(input) {
+      Some(changed) => changed,
+      None => return false,
+    };
+    let mut exact = match self.sums.get(&output) {
+      Some(exact) => exact.clone(),
+      None => return false,
+    };
+    let mut mantissa = match self.memory.get(output).unwrap().data.first().and_then(|column| column.get(0)) {
+      Some(Value::Number(sum)) => sum.mantissa(),
+      _ => return false,
+    };
+    if changed.is_empty() {
+      deltas.local.insert(output, Some(Vec::new()));
+      return true;
+    }
+    {
+      let input_table = self.memory.get(input).unwrap();
+      let replaced = match deltas.replaced.get(&input) {
+        Some(replaced) => replaced,
+        None => return false,
+      };
+      for (row, old) in replaced.iter() {
+        for (column, old_value) in old.iter().enumerate() {
+          if let Value::Number(old) = old_value {
+            exact.take(*old);
+            mantissa -= old.mantissa();
+          }
+          if let Value::Number(new) = input_table.data[column].value(*row) {
+            if !exact.add(new) {
+              return false;
+            }
+            mantissa += new.mantissa();
+          }
+        }
+      }
+    }
+    // With nothing left to add up, the sum is empty
+    if exact.count == 0 {
+      return false;
+    }
+    let sum = make_quantity(mantissa, exact.range, 0);
+    self.sums.insert(output, exact);
+    self.replace_rows(output, vec![(0, vec![Value::Number(sum)])], deltas);
+    true
+  }
+
+  // After a sum runs in full, note whether it can be adjusted exactly later
+  fn note_sum(&mut self, step: &Constraint) {
+    let (input, output) = match summed(step) {
+      Some(tables) => tables,
+      None => return,
+    };
+    match ExactSum::of(self.memory.get(input).unwrap()) {
+      Some(exact) => self.sums.insert(output, exact),
+      None => self.sums.remove(&output),
+    };
+  }
+
+  // Write back the rows that changed, along with any rows of the destination
+  // that something else changed since the last run
+  fn insert_delta(&mut self, from: u64, to: u64, store: &Interner, deltas: &mut Deltas) -> bool {
+    let (from_rows, to_rows) = match (deltas.local_rows(from), deltas.global_rows(to)) {
+      (Some(from_rows), Some(to_rows)) => (from_rows, to_rows),
+      _ => return false,
+    };
+    let from_table = self.memory.get(from).unwrap();
+    let to_table = match store.get_table(to) {
+      Some(to_table) => to_table,
+      None => return false,
+    };
+    if from_table.rows != to_table.rows || from_table.columns != to_table.columns {
+      return false;
+    }
+    let rows = sorted_rows(from_rows.into_iter().chain(to_rows.into_iter().map(|(row, _)| row)));
+    for column in 0..from_table.columns as usize {
+      for row in rows.iter().filter(|row| **row < from_table.rows as usize) {
+        self.block_changes.push(Change::Set{
+          table: to,
+          row: Index::Index(*row as u64 + 1),
+          column: Index::Index(column as u64 + 1),
+          value: from_table.data[column].value(*row),
+        });
+      }
+    }
+    true
+  }
+
+}
+
+impl fmt::Debug for Block {
+  #[inline]
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "┌────────────────────────────────────────┐\n").unwrap();
+    write!(f, "│ Block {:?} ({:#x})\n", self.name, self.id).unwrap();
+    write!(f, "├────────────────────────────────────────┤\n").unwrap();
+    write!(f, "│ \n{}\n",self.text).unwrap();
+    write!(f, "├────────────────────────────────────────┤\n").unwrap();
+    write!(f, "│ Errors:\n").unwrap();
+    write!(f, "│ {:?}\n", self.errors).unwrap();
+    write!(f, "├────────────────────────────────────────┤\n").unwrap();
+    write!(f, "│ State: {:?}\n", self.state).unwrap();
+    write!(f, "│ Ready: {:?}\n", self.ready).unwrap();
+    write!(f, "│ Updated: {:?}\n", self.updated).unwrap();
+    write!(f, "│ Input: {:?}\n", self.input_registers.len()).unwrap();
+    for (ix, register) in self.input_registers.iter().enumerate() {
+      write!(f, "│  {:?}. {:?}\n", ix + 1, register).unwrap();
+    }
+    write!(f, "│ Triggers: {:?}\n", self.triggers).unwrap();
+    write!(f, "│ Output: {:?}\n", self.output_registers.len()).unwrap();
+    for (ix, register) in self.output_registers.iter().enumerate() {
+      write!(f, "│  {:?}. {:?}\n", ix + 1, register).unwrap();
+    }
+    write!(f, "│ Constraints: {:?}\n", self.constraints.len()).unwrap();
+    for (ix, (text, constraint)) in self.constraints.iter().enumerate() {
+      write!(f, "│  {}. {}\n", ix + 1, text).unwrap();
+      for constraint_step in constraint {
+        write!(f, "│    > {:?}\n", constraint_step).unwrap();
+      }
+    }
+    write!(f, "│ Plan: {:?}\n", self.plan.len()).unwrap();
+    for (ix, step) in self.plan.iter().enumerate() {
+      write!(f, "│  {:?}. {:?}\n", ix + 1, step).unwrap();
+    }
+    write!(f, "└────────────────────────────────────────┘\n").unwrap();
+    write!(f, "{:?}\n", self.memory).unwrap();
+    Ok(())
+  }
+}
+
+// ## Planning
+
+// The plan is worked out again from every constraint each time more are 
+// added. Steps are put in the order their data flows, functions of constants
+// are computed once up front, repeated expressions are computed once, steps
+// whose results nobody reads are dropped, and chains of elementwise math are
+// fused so they run in a single pass over their inputs.
+
+impl Block {
+
+  fn plan_steps(&mut self) {
+    // Memory was computed by the old plan, so the next run goes over everything
+    self.last_run = None;
+    self.pending.clear();
+    self.sums.clear();
+    let mut steps = Vec::new();
+    for (_, constraints) in self.constraints.iter() {
+      for constraint in constraints.iter().rev() {
+        match constraint {
+          Constraint::Filter{..} |
+          Constraint::Logic{..} |
+          Constraint::Function{..} |
+          Constraint::CopyTable{..} |
+          Constraint::Range{..} |
+          Constraint::ChangeScan{..} |
+          Constraint::Append{..} |
+          Constraint::Scan{..} |
+          Constraint::Previous{..} |
+          Constraint::Insert{..} => steps.push(constraint.clone()),
+          _ => (),
+        }
+      }
+    }
+    let pinned = self.pinned_tables();
+    let steps = self.order_steps(steps);
+    // Shapes are checked before anything is computed up front, since a step
+    // that doesn't fit would fail, or panic, when it's folded
+    let mismatches = self.check_shapes(&steps);
+    let previous = mem::replace(&mut self.shape_errors, mismatches);
+    self.errors.retain(|error| !previous.contains(error));
+    self.errors.extend(self.shape_errors.iter().cloned());
+    let steps = if self.shape_errors.is_empty() { self.fold_constants(steps) } else { steps };
+    let steps = self.share_subexpressions(steps, &pinned);
+    let steps = self.eliminate_dead_steps(steps, &pinned);
+    self.plan = self.fuse_elementwise(steps, &pinned);
+  }
+
+  // The plan the block runs, one step to a line
+  pub fn explain(&self) -> String {
+    let mut text = String::new();
+    for (ix, step) in self.plan.iter().enumerate() {
+      match step {
+        Constraint::Fused{steps} => {
+          text.push_str(&format!("{}. Fused\n", ix + 1));
+          for inner in steps {
+            text.push_str(&format!("   > {:?}\n", inner));
+          }
+        },
+        _ => text.push_str(&format!("{}. {:?}\n", ix + 1, step)),
+      }
+    }
+    text
+  }
+
+  fn resolve(&self, table: TableId) -> TableId {
+    match table {
+      TableId::Local(id) => match self.memory.aliases.get(&id) {
+        Some(aliased) => TableId::Local(*aliased),
+        None => table,
+      },
+      _ => table,
+    }
+  }
+
+  fn tables_read(&self, step: &Constraint) -> Vec<TableId> {
+    step_reads(step).into_iter().map(|table| self.resolve(table)).collect()
+  }
+
+  fn tables_written(&self, step: &Constraint) -> Vec<TableId> {
+    step_writes(step).into_iter().map(|table| self.resolve(table)).collect()
+  }
+
+  fn count_writers(&self, steps: &Vec<Constraint>) -> HashMap<TableId, usize> {
+    let mut writers = HashMap::new();
+    for step in steps {
+      for table in dedup(self.tables_written(step)) {
+        *writers.entry(table).or_insert(0) += 1;
+      }
+    }
+    writers
+  }
+
+  // Local tables that are reachable other than through the plan, so the
+  // planner leaves alone the steps that write them
+  fn pinned_tables(&self) -> HashSet<TableId> {
+    let mut pinned = HashSet::new();
+    for (_, constraints) in self.constraints.iter() {
+      for constraint in constraints {
+        match constraint {
+          Constraint::Reference{table, destination} => {
+            pinned.insert(self.resolve(TableId::Local(*table)));
+            pinned.insert(self.resolve(TableId::Local(*destination)));
+          },
+          Constraint::AliasTable{table, ..} => {
+            pinned.insert(self.resolve(*table));
+          },
+          _ => (),
+        }
+      }
+    }
+    pinned
+  }
+
+  // Steps that read a local table come after every step that writes it. 
+  // Otherwise steps keep the order they were added in. If the steps can't be
+  // ordered, they're left as they are.
+  fn order_steps(&self, steps: Vec<Constraint>) -> Vec<Constraint> {
+    let reads: Vec<Vec<TableId>> = steps.iter().map(|step| self.tables_read(step)).collect();
+    let writes: Vec<Vec<TableId>> = steps.iter().map(|step| self.tables_written(step)).collect();
+    let precedes = |i: usize, j: usize| -> bool {
+      for table in writes[i].iter() {
+        match table {
+          TableId::Local(_) => {
+            if reads[j].contains(table) && !writes[j].contains(table) {
+              return true;
+            }
+            if i < j && writes[j].contains(table) {
+              return true;
+            }
+          },
+          TableId::Global(_) => {
+            if i < j && (reads[j].contains(table) || writes[j].contains(table)) {
+              return true;
+            }
+          },
+        }
+      }
+      for table in reads[i].iter() {
+        match table {
+          TableId::Global(_) if i < j && writes[j].contains(table) => return true,
+          _ => (),
+        }
+      }
+      false
+    };
+    let mut waiting_on = vec![0; steps.len()];
+    let mut unblocks = vec![Vec::new(); steps.len()];
+    for i in 0..steps.len() {
+      for j in 0..steps.len() {
+        if i != j && precedes(i, j) {
+          waiting_on[j] += 1;
+          unblocks[i].push(j);
+        }
+      }
+    }
+    let mut order = Vec::with_capacity(steps.len());
+    let mut placed = vec![false; steps.len()];
+    while order.len() < steps.len() {
+      let next = (0..steps.len()).find(|ix| !placed[*ix] && waiting_on[*ix] == 0);
+      match next {
+        Some(ix) => {
+          placed[ix] = true;
+          order.push(ix);
+          for j in unblocks[ix].iter() {
+            waiting_on[*j] -= 1;
+          }
+        },
+        None => return steps,
+      }
+    }
+    order.into_iter().map(|ix| steps[ix].clone()).collect()
+  }
+
+  // Functions that only read tables filled in by constants are computed now,
+  // and their outputs become constants too
+  fn fold_constants(&mut self, steps: Vec<Constraint>) -> Vec<Constraint> {
+    let mut writers = self.count_writers(&steps);
+    // Nothing folded reads the store
+    let store = Interner::new(0, 0);
+    let mut folded = Vec::with_capacity(steps.len());
+    for step in steps {
+      let output = match step {
+        Constraint::Function{ref output, ..} if output.len() == 1 => Some(self.resolve(output[0])),
+        _ => None,
+      };
+      match output {
+        Some(TableId::Local(id)) if writers.get(&TableId::Local(id)) == Some(&1) => {
+          let constant = self.tables_read(&step).iter().all(|table| match table {
+            TableId::Local(read) => !writers.contains_key(table) && self.memory.get(*read).is_some(),
+            TableId::Global(_) => false,
+          });
+          if constant {
+            let errors = self.errors.len();
+            if self.evaluate_steps(slice::from_ref(&step), &store) && self.errors.len() == errors {
+              writers.remove(&TableId::Local(id));
+              continue;
+            }
+            // Leave the error for when the block runs
+            self.errors.truncate(errors);
+          }
+        },
+        _ => (),
+      }
+      folded.push(step);
+    }
+    folded
+  }
+
+  // When two steps compute the same thing from the same inputs, the second
+  // is dropped and its readers read the first one's output instead
+  fn share_subexpressions(&self, steps: Vec<Constraint>, pinned: &HashSet<TableId>) -> Vec<Constraint> {
+    let writers = self.count_writers(&steps);
+    let mut renamed: HashMap<TableId, TableId> = HashMap::new();
+    let mut seen: Vec<(Constraint, TableId)> = Vec::new();
+    let mut shared = Vec::with_capacity(steps.len());
+    for mut step in steps {
+      for_each_read(&mut step, &mut |table: &mut TableId| {
+        match renamed.get(&self.resolve(*table)) {
+          Some(replacement) => *table = *replacement,
+          None => (),
+        }
+      });
+      match expression_key(&step) {
+        Some((key, output)) => {
+          let output = self.resolve(output);
+          let shareable = match output {
+            TableId::Local(_) => writers.get(&output) == Some(&1) && !pinned.contains(&output),
+            TableId::Global(_) => false,
+          };
+          if shareable && self.tables_read(&step).iter().all(|table| stable(table, &writers)) {
+            match seen.iter().find(|(seen_key, _)| *seen_key == key) {
+              Some((_, first)) => {
+                renamed.insert(output, *first);
+                continue;
+              },
+              None => seen.push((key, output)),
+            }
+          }
+        },
+        None => (),
+      }
+      shared.push(step);
+    }
+    shared
+  }
+
+  // Working back from the steps that write global tables, keep only the 
+  // steps whose output something still needs
+  fn eliminate_dead_steps(&self, steps: Vec<Constraint>, pinned: &HashSet<TableId>) -> Vec<Constraint> {
+    let mut needed = pinned.clone();
+    let mut live = Vec::with_capacity(steps.len());
+    for step in steps.into_iter().rev() {
+      let writes = self.tables_written(&step);
+      let keep = writes.is_empty() || writes.iter().any(|table| match table {
+        TableId::Global(_) => true,
+        TableId::Local(_) => needed.contains(table),
+      });
+      if keep {
+        for table in self.tables_read(&step) {
+          needed.insert(table);
+        }
+        live.push(step);
+      }
+    }
+    live.reverse();
+    live
+  }
+
+  // An elementwise step whose only reader is another elementwise step is
+  // fused into it, so the intermediate table is never filled in
+  fn fuse_elementwise(&self, steps: Vec<Constraint>, pinned: &HashSet<TableId>) -> Vec<Constraint> {
+    let writers = self.count_writers(&steps);
+    let mut readers: HashMap<TableId, usize> = HashMap::new();
+    for step in steps.iter() {
+      for table in dedup(self.tables_read(step)) {
+        *readers.entry(table).or_insert(0) += 1;
+      }
+    }
+    let mut groups: Vec<Vec<Constraint>> = Vec::with_capacity(steps.len());
+    // Outputs of groups that could still be fused into a later step
+    let mut open: HashMap<TableId, usize> = HashMap::new();
+    for step in steps {
+      let fusable = match step {
+        Constraint::Function{ref operation, ref parameters, ref output} => {
+          elementwise(operation) && parameters.len() == 2 && output.len() == 1 &&
+          match output[0] { TableId::Local(_) => true, _ => false } &&
+          parameters.iter().all(|(table, rows, columns)| {
+            rows.is_none() && columns.is_none() && stable(&self.resolve(*table), &writers)
+          })
+        },
+        _ => false,
+      };
+      if !fusable {
+        groups.push(vec![step]);
+        continue;
+      }
+      let mut group = Vec::new();
+      for table in dedup(self.tables_read(&step)) {
+        if readers.get(&table) == Some(&1) && writers.get(&table) == Some(&1) && !pinned.contains(&table) {
+          match open.remove(&table) {
+            Some(ix) => group.append(&mut groups[ix]),
+            None => (),
+          }
+        }
+      }
+      open.insert(self.tables_written(&step)[0], groups.len());
+      group.push(step);
+      groups.push(group);
+    }
+    groups.into_iter().filter(|group| !group.is_empty()).map(|mut group| {
+      if group.len() == 1 {
+        group.remove(0)
+      } else {
+        Constraint::Fused{steps: group}
+      }
+    }).collect()
+  }
+
+  // Run a fused chain cell by cell. Every input has to be a scalar or the 
+  // same shape as the others. Returns None if they aren't.
+  fn evaluate_fused(&mut self, steps: &Vec<Constraint>, store: &Interner) -> Option<bool> {
+    let outputs: Vec<TableId> = steps.iter().flat_map(|step| step_writes(step)).collect();
+    let mut errors: Vec<(usize, ErrorType)> = Vec::new();
+    let (rows, columns, data) = {
+      let mut operands: Vec<(&Function, Vec<Operand>)> = Vec::with_capacity(steps.len());
+      let mut shape = None;
+      for step in steps {
+        match step {
+          Constraint::Function{operation, parameters, ..} => {
+            let mut sides = Vec::with_capacity(2);
+            for (table, _, _) in parameters {
+              match outputs.iter().position(|output| output == table) {
+                Some(ix) => sides.push(Operand::Step(ix)),
+                None => {
+                  let table_ref = match table {
+                    TableId::Local(id) => self.memory.get(*id),
+                    TableId::Global(id) => store.get_table(*id),
+                  }?;
+                  if table_ref.rows != 1 || table_ref.columns != 1 {
+                    match shape {
+                      None => shape = Some((table_ref.rows, table_ref.columns)),
+                      Some(dimensions) if dimensions == (table_ref.rows, table_ref.columns) => (),
+                      _ => return None,
+                    }
+                  }
+                  sides.push(Operand::Table(table_ref));
+                },
+              }
+            }
+            operands.push((operation, sides));
+          },
+          _ => return None,
+        }
+      }
+      let (rows, columns) = shape.unwrap_or((1, 1));
+      // Every step's output is filled in, just as if the steps ran one at a 
+      // time
+      let mut data = vec![vec![Column::new(rows as usize); columns as usize]; operands.len()];
+      let mut values: Vec<Option<Quantity>> = vec![None; operands.len()];
+      for column in 0..columns as usize {
+        for row in 0..rows as usize {
+          for (ix, (operation, sides)) in operands.iter().enumerate() {
+            let lhs = sides[0].value(&values, column, row);
+            let rhs = sides[1].value(&values, column, row);
+            values[ix] = match (lhs, rhs) {
+              (Some(x), Some(y)) => match apply_math(operation, x, y) {
+                Ok(result) => Some(result),
+                Err(error) => {
+                  errors.push((ix, error));
+                  None
+                },
+              },
+              _ => None,
+            };
+          }
+          for (ix, value) in values.iter().enumerate() {
+            match value {
+              Some(result) => { data[ix][column].set(row, Value::from_quantity(*result)); },
+              None => (),
+            }
+          }
+        }
+      }
+      (rows, columns, data)
+    };
+    if errors.is_empty() {
+      for (output, data) in outputs.iter().zip(data.into_iter()) {
+        let out = self.memory.get_mut(*output.unwrap()).unwrap();
+        out.rows = rows;
+        out.columns = columns;
+        out.data = data;
+      }
+      return Some(true);
+    }
+    // Report the errors from the first step that failed, as if the steps ran 
+    // one at a time
+    let failed = errors[0].0;
+    for (ix, error) in errors {
+      if ix == failed {
+        self.errors.push(Error{
+          block: self.id as u64,
+          constraint: steps[ix].clone(),
+          error_id: error,
+        });
+      }
+    }
+    Some(false)
+  }
+
+}
+
+// Where a value in a fused chain comes from: an earlier step in the chain, or
+// a table
+enum Operand<'a> {
+  Step(usize),
+  Table(&'a Table),
+}
+
+impl<'a> Operand<'a> {
+
+  fn value(&self, values: &Vec<Option<Quantity>>, column: usize, row: usize) -> Option<Quantity> {
+    match self {
+      Operand::Step(ix) => values[*ix],
+      Operand::Table(table) => {
+        let value = if table.rows == 1 && table.columns == 1 { table.data[0].value(0) }
+                    else { table.data[column].value(row) };
+        match value {
+          Value::Number(x) => Some(x),
+          _ => None,
+        }
+      },
+    }
+  }
+
+}
+
+fn elementwise(operation: &Function) -> bool {
+  match operation {
+    Function::Add | Function::Subtract | Function::Multiply | Function::Divide => true,
+    _ => false,
+  }
+}
+
+fn apply_math(operation: &Function, lhs: Quantity, rhs: Quantity) -> Result<Quantity, ErrorType> {
+  match operation {
+    Function::Subtract => lhs.sub(rhs),
+    Function::Multiply => lhs.multiply(rhs),
+    Function::Divide => lhs.divide(rhs),
+    _ => lhs.add(rhs),
+  }
+}
+
+// Functions whose first parameter names an argument rather than a table
+fn takes_argument(operation: &Function) -> bool {
+  match operation {
+    Function::MathSin | Function::MathCos | Function::MathRound | 
+    Function::MathFloor | Function::StatSum | Function::SetAny => true,
+    _ => false,
+  }
+}
+
+// A local table is stable if at most one step writes it, and a global table
+// if no step in the block writes it. Reading a stable table gives the same 
+// thing wherever the read is in the plan.
+fn stable(table: &TableId, writers: &HashMap<TableId, usize>) -> bool {
+  match table {
+    TableId::Local(_) => writers.get(table).map_or(true, |count| *count <= 1),
+    TableId::Global(_) => !writers.contains_key(table),
+  }
+}
+
+fn dedup(tables: Vec<TableId>) -> Vec<TableId> {
+  let mut unique = Vec::with_capacity(tables.len());
+  for table in tables {
+    if !unique.contains(&table) {
+      unique.push(table);
+    }
+  }
+  unique
+}
+
+// The step with its output blanked out, so steps that compute the same thing
+// compare equal
+fn expression_key(step: &Constraint) -> Option<(Constraint, TableId)> {
+  let mut key = step.clone();
+  let output = match key {
+    Constraint::Function{ref mut output, ..} if output.len() == 1 => mem::replace(&mut output[0], TableId::Local(0)),
+    Constraint::Filter{ref mut output, ..} |
+    Constraint::Logic{ref mut output, ..} => mem::replace(output, TableId::Local(0)),
+    _ => return None,
+  };
+  Some((key, output))
+}
+
+fn parameter_read(parameter: &mut Option<Parameter>, f: &mut dyn FnMut(&mut TableId)) {
+  match parameter {
+    Some(Parameter::TableId(table)) => f(table),
+    _ => (),
+  }
+}
+
+// Visit every table the step reads
+fn for_each_read(step: &mut Constraint, f: &mut dyn FnMut(&mut TableId)) {
+  match step {
+    Constraint::Scan{table, indices, ..} => {
+      f(table);
+      for index in indices.iter_mut() {
+        parameter_read(index, f);
+      }
+    },
+    Constraint::ChangeScan{table, column} => {
+      f(table);
+      for index in column.iter_mut() {
+        parameter_read(index, f);
+      }
+    },
+    Constraint::Previous{table, ..} => f(&mut TableId::Global(*table)),
+    Constraint::Range{start, end, ..} => {
+      f(start);
+      f(end);
+    },
+    Constraint::Filter{lhs, rhs, ..} |
+    Constraint::Logic{lhs, rhs, ..} => {
+      for operand in vec![lhs, rhs] {
+        f(&mut operand.0);
+        parameter_read(&mut operand.1, f);
+        parameter_read(&mut operand.2, f);
+      }
+    },
+    Constraint::Function{operation, parameters, ..} => {
+      let skip = if takes_argument(operation) { 1 } else { 0 };
+      for parameter in parameters.iter_mut().skip(skip) {
+        f(&mut parameter.0);
+        parameter_read(&mut parameter.1, f);
+        parameter_read(&mut parameter.2, f);
+      }
+    },
+    Constraint::CopyTable{from_table, ..} => {
+      let mut table = TableId::Local(*from_table);
+      f(&mut table);
+      *from_table = *table.unwrap();
+    },
+    Constraint::Insert{from, to} => {
+      f(&mut from.0);
+      for index in from.1.iter_mut().chain(to.1.iter_mut()) {
+        parameter_read(index, f);
+      }
+      // Writing into a local table keeps whatever the write doesn't cover
+      match to.0 {
+        TableId::Local(_) => f(&mut to.0),
+        _ => (),
+      }
+    },
+    Constraint::Append{from_table, ..} => f(from_table),
+    Constraint::Fused{steps} => {
+      let outputs: Vec<TableId> = steps.iter().flat_map(|inner| step_writes(inner)).collect();
+      for inner in steps.iter_mut() {
+        for_each_read(inner, &mut |table: &mut TableId| {
+          if !outputs.contains(table) {
+            f(table);
+          }
+        });
+      }
+    },
+    _ => (),
+  }
+}
+
+fn step_reads(step: &Constraint) -> Vec<TableId> {
+  let mut tables = Vec::new();
+  for_each_read(&mut step.clone(), &mut |table: &mut TableId| tables.push(*table));
+  tables
+}
+
+fn step_writes(step: &Constraint) -> Vec<TableId> {
+  match step {
+    Constraint::Scan{output, ..} |
+    Constraint::Filter{output, ..} |
+    Constraint::Logic{output, ..} |
+    Constraint::Previous{output, ..} => vec![*output],
+    Constraint::Function{output, ..} => output.clone(),
+    Constraint::Range{table, ..} => vec![*table],
+    Constraint::CopyTable{to_table, ..} => vec![TableId::Global(*to_table)],
+    Constraint::Insert{to: (to_table, _), ..} => vec![*to_table],
+    Constraint::Append{to_table, ..} => vec![*to_table],
+    Constraint::Fused{steps} => match steps.last() {
+      Some(last) => step_writes(last),
+      None => Vec::new(),
+    },
+    _ => Vec::new(),
+  }
+}
+
+// ## Schemas
+
+// When a block is registered, the kind of value in each of its local tables 
+// is worked out from the steps that fill it, and whatever it writes to a
+// table with a schema is checked against the schema.
+
+impl Block {
+
+  pub fn check_schemas(&self, schemas: &HashMap<u64, Schema>) -> Vec<Error> {
+    let kinds = self.local_kinds(&self.plan, schemas);
+    let mut errors = Vec::new();
+    for step in self.plan.iter() {
+      let (from, to, column) = match step {
+        Constraint::Insert{from: (from, _), to: (TableId::Global(to), to_ixes)} => match to_ixes.len() {
+          0 => (*from, *to, None),
+          2 => (*from, *to, to_ixes[1].clone()),
+          _ => continue,
+        },
+        Constraint::Append{from_table, to_table: TableId::Global(to)} => (*from_table, *to, None),
+        _ => continue,
+      };
+      let (schema, kind) = match (schemas.get(&to), self.kind_of(&from, &kinds, schemas)) {
+        (Some(schema), Some(kind)) => (schema, kind),
+        _ => continue,
+      };
+      // A whole table is checked against every column it could land in
+      let columns: Vec<Index> = match column {
+        Some(Parameter::Index(index)) => vec![index],
+        None => (1..schema.columns.len() as u64 + 1).map(Index::Index).collect(),
+        Some(Parameter::TableId(_)) => continue,
+      };
+      for column in columns {
+        match schema.column(&column) {
+          Some(declared) if declared.kind != kind => {
+            errors.push(Error{
+              block: self.id as u64,
+              constraint: step.clone(),
+              error_id: ErrorType::TypeMismatch(to, column, declared.kind, Some(kind)),
+            });
+          },
+          _ => (),
+        }
+      }
+    }
+    errors
+  }
+
+  // Local tables that hold one kind of value, and what it is
+  fn local_kinds(&self, steps: &Vec<Constraint>, schemas: &HashMap<u64, Schema>) -> HashMap<TableId, Kind> {
+    let mut kinds = HashMap::new();
+    let written: HashSet<TableId> = steps.iter().flat_map(|step| self.tables_written(step)).collect();
+    // Tables that were filled in when the block was made, like constants
+    for (id, table) in self.memory.map.iter() {
+      if !written.contains(&TableId::Local(*id)) {
+        if let Some(kind) = uniform_kind(table) {
+          kinds.insert(TableId::Local(*id), kind);
+        }
+      }
+    }
+    for step in steps.iter() {
+      self.infer_kind(step, schemas, &mut kinds);
+    }
+    kinds
+  }
+
+  fn infer_kind(&self, step: &Constraint, schemas: &HashMap<u64, Schema>, kinds: &mut HashMap<TableId, Kind>) {
+    let (output, kind) = match step {
+      Constraint::Filter{output, ..} |
+      Constraint::Logic{output, ..} => (*output, Some(Kind::Bool)),
+      Constraint::Range{table, ..} => (*table, Some(Kind::Number)),
+      Constraint::Function{operation, parameters, output} if output.len() == 1 => {
+        let kind = match operation {
+          Function::HorizontalConcatenate |
+          Function::VerticalConcatenate => {
+            let mut parts = parameters.iter().map(|(table, _, _)| self.kind_of(table, kinds, schemas));
+            match parts.next() {
+              Some(Some(first)) if parts.all(|kind| kind == Some(first)) => Some(first),
+              _ => None,
+            }
+          },
+          Function::SetAny => Some(Kind::Bool),
+          Function::Undefined => None,
+          _ => Some(Kind::Number),
+        };
+        (output[0], kind)
+      },
+      // A column picked out by name has the kind its schema gives it
+      Constraint::Scan{table, indices, output} => {
+        let kind = match (table, indices.get(1)) {
+          (TableId::Global(id), Some(Some(Parameter::Index(column)))) => {
+            schemas.get(id).and_then(|schema| schema.column(column)).map(|declared| declared.kind)
+          },
+          _ => self.kind_of(table, kinds, schemas),
+        };
+        (*output, kind)
+      },
+      Constraint::Previous{table, output} => (*output, self.kind_of(&TableId::Global(*table), kinds, schemas)),
+      Constraint::Fused{steps} => {
+        for step in steps {
+          self.infer_kind(step, schemas, kinds);
+        }
+        return;
+      },
+      _ => return,
+    };
+    let output = self.resolve(output);
+    match kind {
+      Some(kind) => kinds.insert(output, kind),
+      None => kinds.remove(&output),
+    };
+  }
+
+  fn kind_of(&self, table: &TableId, kinds: &HashMap<TableId, Kind>, schemas: &HashMap<u64, Schema>) -> Option<Kind> {
+    match self.resolve(*table) {
+      TableId::Global(id) => schemas.get(&id).and_then(|schema| schema.kind()),
+      local => kinds.get(&local).cloned(),
+    }
+  }
+
+}
+
+// The kind of every value in a table, if they're all the same
+fn uniform_kind(table: &Table) -> Option<Kind> {
+  let mut kinds = table.data.iter().flat_map(|column| column.iter()).filter_map(|value| Kind::of(&value));
+  let first = kinds.next()?;
+  if kinds.all(|kind| kind == first) {
+    Some(first)
+  } else {
+    None
+  }
+}
+
+// ## Shapes
+
+// Each time constraints are added, the size of every local table is worked 
+// out from the steps that fill it, in the order they'll run. Math and 
+// comparisons between tables whose sizes don't fit, and indices past the end
+// of a table, are reported then, before the block ever runs. Sizes that
+// depend on the store aren't known, and steps that read them aren't checked.
+
+type Shape = (Option<u64>, Option<u64>);
+
+// What's known about the local tables partway through the plan
+struct Analysis {
+  shapes: HashMap<TableId, Shape>,
+  kinds: HashMap<TableId, Kind>,
+  fixed: HashSet<TableId>, // tables no step writes, so they hold what they were made with
+}
+
+impl Block {
+
+  fn check_shapes(&self, steps: &Vec<Constraint>) -> Vec<Error> {
+    let written: HashSet<TableId> = steps.iter().flat_map(|step| self.tables_written(step)).collect();
+    let mut analysis = Analysis {
+      shapes: HashMap::new(),
+      kinds: self.local_kinds(steps, &HashMap::new()),
+      fixed: HashSet::new(),
+    };
+    for (id, table) in self.memory.map.iter() {
+      let table_id = TableId::Local(*id);
+      if !written.contains(&table_id) {
+        analysis.shapes.insert(table_id, (Some(table.rows), Some(table.columns)));
+        analysis.fixed.insert(table_id);
+      }
+    }
+    let mut errors = Vec::new();
+    for step in steps {
+      let mut step_errors = Vec::new();
+      self.infer_shape(step, &mut analysis, &mut step_errors);
+      for error_id in step_errors {
+        errors.push(Error{
+          block: self.id as u64,
+          constraint: step.clone(),
+          error_id,
+        });
+      }
+    }
+    errors
+  }
+
+  fn infer_shape(&self, step: &Constraint, analysis: &mut Analysis, errors: &mut Vec<ErrorType>) {
+    let shape = match step {
+      Constraint::Scan{table, indices, ..} => {
+        let source = self.source_shape(table, analysis);
+        match indices.as_slice() {
+          // #x{3} picks along whichever way #x is a vector
+          [ixes] => match source {
+            (Some(1), columns) => {
+              let (width, past) = self.select(ixes, columns, false, analysis);
+              errors.extend(out_of_bounds(source, &[], &past));
+              (Some(1), width)
+            },
+            (rows, Some(1)) => {
+              let (height, past) = self.select(ixes, rows, false, analysis);
+              errors.extend(out_of_bounds(source, &past, &[]));
+              (height, Some(1))
+            },
+            _ => (None, None),
+          },
+          [row_ixes, column_ixes] => self.select_shape(source, row_ixes, column_ixes, analysis, errors),
+          _ => (None, None),
+        }
+      },
+      Constraint::Filter{lhs, rhs, ..} |
+      Constraint::Logic{lhs, rhs, ..} => {
+        let lhs = self.operand_shape(lhs, analysis, errors);
+        let rhs = self.operand_shape(rhs, analysis, errors);
+        broadcast(lhs, rhs, errors)
+      },
+      Constraint::Function{operation, parameters, output} if output.len() == 1 => match operation {
+        Function::Add |
+        Function::Subtract |
+        Function::Multiply |
+        Function::Divide |
+        Function::Power |
+        Function::Undefined if parameters.len() == 2 => {
+          let lhs = self.operand_shape(&parameters[0], analysis, errors);
+          let rhs = self.operand_shape(&parameters[1], analysis, errors);
+          broadcast(lhs, rhs, errors)
+        },
+        // Parts that are a single row are stretched to fit the others
+        Function::HorizontalConcatenate => {
+          let mut shape: Shape = (Some(0), Some(0));
+          for parameter in parameters {
+            let part = self.operand_shape(parameter, analysis, errors);
+            let rows = match (shape.0, part.0) {
+              (Some(0), rows) | (Some(1), rows) | (rows, Some(1)) => rows,
+              (Some(rows), Some(height)) if rows != height => {
+                errors.push(ErrorType::DimensionMismatch(((rows, shape.1.unwrap_or(0)), (height, part.1.unwrap_or(0)))));
+                shape = (None, None);
+                break;
+              },
+              (rows, height) if rows == height => rows,
+              _ => None,
+            };
+            shape = (rows, add_sizes(shape.1, part.1));
+          }
+          shape
+        },
+        // Whole tables are stacked, and they have to be the same width
+        Function::VerticalConcatenate => {
+          let mut shape: Shape = (Some(0), Some(0));
+          for (table, _, _) in parameters {
+            let part = self.source_shape(table, analysis);
+            let columns = match (shape.1, part.1) {
+              (Some(0), columns) => columns,
+              (Some(columns), Some(width)) if columns != width => {
+                errors.push(ErrorType::DimensionMismatch(((shape.0.unwrap_or(0), columns), (part.0.unwrap_or(0), width))));
+                shape = (None, None);
+                break;
+              },
+              (columns, width) if columns == width => columns,
+              _ => None,
+            };
+            shape = (add_sizes(shape.0, part.0), columns);
+          }
+          shape
+        },
+        Function::MathSin |
+        Function::MathCos |
+        Function::MathRound |
+        Function::MathFloor if parameters.len() == 2 => self.operand_shape(&parameters[1], analysis, errors),
+        _ => {
+          for parameter in parameters.iter().skip(1) {
+            self.operand_shape(parameter, analysis, errors);
+          }
+          (None, None)
+        },
+      },
+      _ => (None, None),
+    };
+    for table in self.tables_written(step) {
+      analysis.shapes.insert(table, shape);
+    }
+  }
+
+  // The size of a table a step reads. Tables holding a reference stand in 
+  // for the table they point at.
+  fn source_shape(&self, table: &TableId, analysis: &Analysis) -> Shape {
+    let table = self.resolve(*table);
+    match table {
+      TableId::Local(_) if analysis.kinds.get(&table) != Some(&Kind::Reference) => {
+        analysis.shapes.get(&table).cloned().unwrap_or((None, None))
+      },
+      _ => (None, None),
+    }
+  }
+
+  // The size of a table once its rows and columns are picked out
+  fn operand_shape(&self, operand: &(TableId, Option<Parameter>, Option<Parameter>), analysis: &Analysis, errors: &mut Vec<ErrorType>) -> Shape {
+    let (table, row_ixes, column_ixes) = operand;
+    let source = self.source_shape(table, analysis);
+    self.select_shape(source, row_ixes, column_ixes, analysis, errors)
+  }
+
+  fn select_shape(&self, source: Shape, row_ixes: &Option<Parameter>, column_ixes: &Option<Parameter>, analysis: &Analysis, errors: &mut Vec<ErrorType>) -> Shape {
+    let (rows, past_rows) = self.select(row_ixes, source.0, false, analysis);
+    let (columns, past_columns) = self.select(column_ixes, source.1, true, analysis);
+    errors.extend(out_of_bounds(source, &past_rows, &past_columns));
+    (rows, columns)
+  }
+
+  // How many rows or columns an index picks out of a dimension, and the 
+  // positions it names that are past the end. Columns can be named directly;
+  // rows only through a table of indices.
+  fn select(&self, ixes: &Option<Parameter>, size: Option<u64>, by_name: bool, analysis: &Analysis) -> (Option<u64>, Vec<u64>) {
+    let (count, positions) = match ixes {
+      None => return (size, Vec::new()),
+      Some(Parameter::Index(Index::Index(ix))) if by_name => (Some(1), vec![*ix]),
+      Some(Parameter::Index(_)) if by_name => (Some(1), Vec::new()),
+      Some(Parameter::Index(_)) => return (size, Vec::new()),
+      Some(Parameter::TableId(table)) => {
+        let table = self.resolve(*table);
+        let known = match table {
+          TableId::Local(id) if analysis.fixed.contains(&table) => self.memory.get(id),
+          _ => None,
+        };
+        match known {
+          // The indices are known, so each one can be checked. Bools are a
+          // mask, and pick out a position for each true.
+          Some(ixes_table) => {
+            let mut count = 0;
+            let mut positions = Vec::new();
+            for value in ixes_table.data.iter().take(1).flat_map(|column| column.iter()) {
+              match value {
+                Value::Number(_) => {
+                  count += 1;
+                  positions.extend(value.as_u64());
+                },
+                Value::Bool(true) => count += 1,
+                _ => (),
+              }
+            }
+            (Some(count), positions)
+          },
+          _ => match (analysis.kinds.get(&table), analysis.shapes.get(&table)) {
+            (Some(Kind::Number), Some((Some(rows), _))) => (Some(*rows), Vec::new()),
+            _ => (None, Vec::new()),
+          },
+        }
+      },
+    };
+    let past = match size {
+      Some(size) => positions.into_iter().filter(|ix| *ix == 0 || *ix > size).collect(),
+      None => Vec::new(),
+    };
+    (count, past)
+  }
+
+}
+
+// Elementwise operations take tables of the same size, or a table and a 
+// single value
+fn broadcast(lhs: Shape, rhs: Shape, errors: &mut Vec<ErrorType>) -> Shape {
+  match (lhs, rhs) {
+    ((Some(1), Some(1)), _) => rhs,
+    (_, (Some(1), Some(1))) => lhs,
+    ((Some(lhs_rows), Some(lhs_columns)), (Some(rhs_rows), Some(rhs_columns))) if lhs != rhs => {
+      errors.push(ErrorType::DimensionMismatch(((lhs_rows, lhs_columns), (rhs_rows, rhs_columns))));
+      (None, None)
+    },
+    _ if lhs == rhs => lhs,
+    _ => (None, None),
+  }
+}
+
+fn add_sizes(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+  Some(a? + b?)
+}
+
+// Positions past the end of a table, given as (row, column) in the first
+// column or row, next to the size of the table. Sizes that aren't known are 0.
+fn out_of_bounds(source: Shape, rows: &[u64], columns: &[u64]) -> Vec<ErrorType> {
+  let size = (source.0.unwrap_or(0), source.1.unwrap_or(0));
+  rows.iter().map(|row| ErrorType::IndexOutOfBounds(((*row, 1), size)))
+    .chain(columns.iter().map(|column| ErrorType::IndexOutOfBounds(((1, *column), size))))
+    .collect()
 }
 
 // ## Pipe
@@ -1538,6 +3489,8 @@ pub enum Constraint {
   // Input Constraints
   Reference{table: u64, destination: u64},
   Scan {table: TableId, indices: Vec<Option<Parameter>>, output: TableId},
+  // #x' reads a global table as it was at the last transaction boundary
+  Previous {table: u64, output: TableId},
   ChangeScan {table: TableId, column: Vec<Option<Parameter>>},
   Identifier {id: u64, text: String},
   Range{table: TableId, start: TableId, end: TableId},
@@ -1554,6 +3507,9 @@ pub enum Constraint {
   Insert {from: (TableId, Vec<Option<Parameter>>), to: (TableId, Vec<Option<Parameter>>)},
   Append {from_table: TableId, to_table: TableId},
   Empty{table: TableId, row: Index, column: Index},
+  // Planned Constraints
+  // A chain of elementwise math the planner runs in one pass
+  Fused {steps: Vec<Constraint>},
   Null,
 }
 
@@ -1575,9 +3531,11 @@ impl fmt::Debug for Constraint {
       Constraint::Identifier{id, text} => write!(f, "Identifier(\"{}\" = {:#x})", text, id),
       Constraint::Insert{from, to} => write!(f, "Insert({:?} -> {:?})",  from, to),
       Constraint::Append{from_table, to_table} => write!(f, "Append({:?} -> {:?})", from_table, to_table),
+      Constraint::Previous{table, output} => write!(f, "Previous(#{:#x}' -> {:?})", table, output),
       Constraint::TableColumn{table, column_ix, column_alias}  => write!(f, "TableColumn(#{:#x}({:#x}) -> {:#x})",  table, column_ix, column_alias),
       Constraint::Range{table, start, end} => write!(f, "Range({:?} -> {:?} to {:?})", table, start, end),
       Constraint::Empty{table, row, column} => write!(f, "Empty -> #{:?} {:?} {:?}", table, row, column),
+      Constraint::Fused{steps} => write!(f, "Fused{:?}", steps),
       Constraint::Null => write!(f, "Null"),
     }
   }
@@ -0,0 +1,117 @@
+// # Schema
+
+// Tables don't need a schema. When one is declared for a table, it says what
+// goes in each column: the kind of value, the domain numbers are measured in,
+// whether cells can be left empty, and what a new row starts out holding.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+use table::{Value, Index};
+use errors::ErrorType;
+use quantities::QuantityMath;
+
+// ## Kinds
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Kind {
+  Number,
+  String,
+  Bool,
+  Reference,
+}
+
+impl Kind {
+
+  // Empty cells don't have a kind
+  pub fn of(value: &Value) -> Option<Kind> {
+    match value {
+      Value::Number(_) => Some(Kind::Number),
+      Value::String(_) => Some(Kind::String),
+      Value::Bool(_) => Some(Kind::Bool),
+      Value::Reference(_) => Some(Kind::Reference),
+      Value::Empty => None,
+    }
+  }
+
+}
+
+// ## Schemas
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+  pub name: u64, // the column's alias
+  pub kind: Kind,
+  pub domain: Option<u64>, // the unit domain numbers have to be in
+  pub nullable: bool,
+  pub default: Option<Value>,
+}
+
+impl ColumnSchema {
+
+  pub fn new(name: u64, kind: Kind) -> ColumnSchema {
+    ColumnSchema {
+      name,
+      kind,
+      domain: None,
+      nullable: true,
+      default: None,
+    }
+  }
+
+}
+
+// Columns are declared in order, so the first one is column 1 of the table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+  pub table: u64,
+  pub columns: Vec<ColumnSchema>,
+}
+
+impl Schema {
+
+  pub fn new(table: u64, columns: Vec<ColumnSchema>) -> Schema {
+    Schema {
+      table,
+      columns,
+    }
+  }
+
+  // The declared column at an index or alias, if there is one
+  pub fn column(&self, column: &Index) -> Option<&ColumnSchema> {
+    match column {
+      Index::Index(ix) if *ix > 0 => self.columns.get(*ix as usize - 1),
+      Index::Index(_) => None,
+      Index::Alias(alias) => self.columns.iter().find(|declared| declared.name == *alias),
+    }
+  }
+
+  // The kind every column holds, if they all hold the same kind
+  pub fn kind(&self) -> Option<Kind> {
+    let first = self.columns.first()?.kind;
+    if self.columns.iter().all(|declared| declared.kind == first) {
+      Some(first)
+    } else {
+      None
+    }
+  }
+
+  // Whether a value can go in a column. Columns that weren't declared take
+  // anything.
+  pub fn check(&self, column: &Index, value: &Value) -> Result<(), ErrorType> {
+    let declared = match self.column(column) {
+      Some(declared) => declared,
+      None => return Ok(()),
+    };
+    match (Kind::of(value), value) {
+      (None, _) if declared.nullable => Ok(()),
+      (Some(kind), Value::Number(x)) if kind == declared.kind => match declared.domain {
+        Some(domain) if x.domain() != domain => Err(ErrorType::DomainMismatch(domain, x.domain())),
+        _ => Ok(()),
+      },
+      (Some(kind), _) if kind == declared.kind => Ok(()),
+      (found, _) => Err(ErrorType::TypeMismatch(self.table, *column, declared.kind, found)),
+    }
+  }
+
+}
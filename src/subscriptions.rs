@@ -0,0 +1,193 @@
+// # Subscriptions
+
+// Lets a host application hear about changes to tables instead of polling for
+// them. Observers subscribe to a Register: a whole table (column 0) or one
+// column of it. When a transaction is done, the changes it made to subscribed
+// tables are read back from the change log, and the observers are handed the cells that changed, with their old and new
+// values. An overwritten cell is logged as a Remove of the old value followed
+// by a Set of the new one, and a removed table carries its contents with it,
+// so the log has both values for every cell. Changes that fall out of the
+// log before the transaction is done, and aren't in an archive, go unheard.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
+#[cfg(not(feature = "no-std"))] use std::sync::mpsc::{channel, Sender, Receiver};
+use table::{Value, Index};
+use database::{Interner, Change};
+use runtime::Register;
+use hashbrown::hash_map::HashMap;
+use hashbrown::hash_set::HashSet;
+
+// ## Cell Changes
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellChange {
+  pub table: u64,
+  pub row: u64,    // 1-indexed
+  pub column: u64, // 1-indexed
+  pub old: Value,
+  pub new: Value,
+}
+
+// ## Subscriptions
+
+pub type Callback = Box<dyn FnMut(&[CellChange])>;
+
+pub enum Observer {
+  Callback(Callback),
+  #[cfg(not(feature = "no-std"))]
+  Channel(Sender<Vec<CellChange>>),
+}
+
+impl Observer {
+
+  // Returns false if the observer has gone away and should be dropped
+  fn notify(&mut self, changes: &[CellChange]) -> bool {
+    match self {
+      Observer::Callback(callback) => {
+        callback(changes);
+        true
+      },
+      #[cfg(not(feature = "no-std"))]
+      Observer::Channel(sender) => sender.send(changes.to_vec()).is_ok(),
+    }
+  }
+
+}
+
+pub struct Subscription {
+  pub register: Register,
+  pub observer: Observer,
+}
+
+pub struct Subscriptions {
+  next_id: usize,
+  pub subscriptions: HashMap<usize, Subscription>,
+  since: usize, // the number of the first change in the transaction
+}
+
+impl Subscriptions {
+
+  pub fn new() -> Subscriptions {
+    Subscriptions {
+      next_id: 1,
+      subscriptions: HashMap::new(),
+      since: 0,
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.subscriptions.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.subscriptions.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.subscriptions.clear();
+  }
+
+  pub fn subscribe(&mut self, register: Register, observer: Observer) -> usize {
+    let id = self.next_id;
+    self.next_id += 1;
+    self.subscriptions.insert(id, Subscription{register, observer});
+    id
+  }
+
+  #[cfg(not(feature = "no-std"))]
+  pub fn subscribe_channel(&mut self, register: Register) -> (usize, Receiver<Vec<CellChange>>) {
+    let (sender, receiver) = channel();
+    (self.subscribe(register, Observer::Channel(sender)), receiver)
+  }
+
+  pub fn unsubscribe(&mut self, id: usize) -> bool {
+    self.subscriptions.remove(&id).is_some()
+  }
+
+  // Note where the changes of a transaction start in the log
+  pub fn begin(&mut self, store: &Interner) {
+    self.since = store.changes_count;
+  }
+
+  // Tell observers about cells that changed since the transaction began
+  pub fn notify(&mut self, store: &Interner) {
+    let tables: HashSet<u64> = self.subscriptions.values().map(|subscription| subscription.register.table).collect();
+    let cells = changed_cells(&tables, self.since, store);
+    if cells.is_empty() {
+      return;
+    }
+    let mut gone = Vec::new();
+    for (id, subscription) in self.subscriptions.iter_mut() {
+      let table = subscription.register.table;
+      let column = match &subscription.register.column {
+        Index::Index(0) => None,
+        Index::Index(ix) => Some(Some(*ix)),
+        column => Some(store.get_table(table).and_then(|table| table.get_column_index(column))),
+      };
+      let changes: Vec<CellChange> = cells.iter()
+                                          .filter(|cell| cell.table == table && column.is_none_or(|ix| ix == Some(cell.column)))
+                                          .cloned()
+                                          .collect();
+      if !changes.is_empty() && !subscription.observer.notify(&changes) {
+        gone.push(*id);
+      }
+    }
+    for id in gone {
+      self.subscriptions.remove(&id);
+    }
+  }
+
+}
+
+impl Default for Subscriptions {
+  fn default() -> Subscriptions {
+    Subscriptions::new()
+  }
+}
+
+// The cells of the given tables that were changed from the change numbered
+// since onwards, ordered by table, column and row. A cell changed more than
+// once goes from its first old value to its last new one, and is left out if
+// it ended up where it started.
+fn changed_cells(tables: &HashSet<u64>, since: usize, store: &Interner) -> Vec<CellChange> {
+  let mut cells: HashMap<(u64, u64, u64), (Value, Value)> = HashMap::new();
+  for number in since..store.changes_count {
+    let change = match store.get_change(number) {
+      Some((change, _)) if tables.contains(&change.table()) => change,
+      _ => continue,
+    };
+    match change {
+      Change::Set{table, row, column, value} => record(&mut cells, store, table, &row, &column, Value::Empty, value),
+      Change::Remove{table, row, column, value} => record(&mut cells, store, table, &row, &column, value, Value::Empty),
+      Change::RemoveTable{id, contents, ..} => {
+        for content in contents {
+          if let Change::Set{row, column, value, ..} = content {
+            record(&mut cells, store, id, &row, &column, value, Value::Empty);
+          }
+        }
+      },
+      _ => (),
+    }
+  }
+  let mut changes: Vec<CellChange> = cells.into_iter()
+                                          .filter(|(_, (old, new))| old != new)
+                                          .map(|((table, row, column), (old, new))| CellChange{table, row, column, old, new})
+                                          .collect();
+  changes.sort_by_key(|change| (change.table, change.column, change.row));
+  changes
+}
+
+// A cell keeps the old value it had the first time it was changed
+fn record(cells: &mut HashMap<(u64, u64, u64), (Value, Value)>, store: &Interner, table: u64, row: &Index, column: &Index, old: Value, new: Value) {
+  let (row, column) = match (row, column) {
+    (Index::Index(row), Index::Index(column)) => (*row, *column),
+    _ => match store.get_table(table).and_then(|table_ref| Some((table_ref.get_row_index(row)?, table_ref.get_column_index(column)?))) {
+      Some(cell) => cell,
+      None => return,
+    },
+  };
+  cells.entry((table, row, column)).or_insert((old, Value::Empty)).1 = new;
+}
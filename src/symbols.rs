@@ -0,0 +1,217 @@
+// # Symbols
+
+// String values are interned in a pool shared by every table, so each distinct
+// string is stored once. A Symbol is a handle to an interned string. Cloning
+// one doesn't allocate, and two symbols are equal, and hash the same, exactly
+// when they point at the same string. The string itself is only looked at when
+// it's displayed, compared for order, or serialized.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::fmt;
+#[cfg(feature = "no-std")] use alloc::string::String;
+#[cfg(feature = "no-std")] use alloc::sync::Arc;
+#[cfg(not(feature = "no-std"))] use core::fmt;
+#[cfg(not(feature = "no-std"))] use std::sync::Arc;
+use core::cell::UnsafeCell;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+use core::hint;
+use core::ops::Deref;
+use core::sync::atomic::{self, AtomicBool};
+use hashbrown::HashSet;
+use serde::*;
+
+// The pool isn't swept until it holds at least this many strings
+const SWEEP_MIN: usize = 1024;
+
+// ## Symbol
+
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+
+  pub fn new(string: &str) -> Symbol {
+    POOL.with(|strings| strings.intern(string))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  // Stays the same for as long as the string is interned
+  pub fn id(&self) -> usize {
+    self.0.as_ptr() as usize
+  }
+
+}
+
+impl Deref for Symbol {
+  type Target = str;
+  fn deref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl<'a> From<&'a str> for Symbol {
+  fn from(string: &'a str) -> Symbol {
+    Symbol::new(string)
+  }
+}
+
+impl From<String> for Symbol {
+  fn from(string: String) -> Symbol {
+    Symbol::new(&string)
+  }
+}
+
+impl PartialEq for Symbol {
+  fn eq(&self, other: &Symbol) -> bool {
+    Arc::ptr_eq(&self.0, &other.0)
+  }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.id().hash(state);
+  }
+}
+
+// Symbols are ordered by their strings, not by where they're stored, so the
+// order is the same on every core
+impl PartialOrd for Symbol {
+  fn partial_cmp(&self, other: &Symbol) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Symbol {
+  fn cmp(&self, other: &Symbol) -> Ordering {
+    self.as_str().cmp(other.as_str())
+  }
+}
+
+impl fmt::Debug for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Debug::fmt(self.as_str(), f)
+  }
+}
+
+impl fmt::Display for Symbol {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    fmt::Display::fmt(self.as_str(), f)
+  }
+}
+
+// Symbols are written as the strings they stand for, and interned again when
+// they're read back
+impl Serialize for Symbol {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let string = String::deserialize(deserializer)?;
+    Ok(Symbol::new(&string))
+  }
+}
+
+// ## Pool
+
+// Strings nothing refers to anymore are dropped from the pool in a sweep,
+// which runs when the pool has doubled in size since the last one. Symbols
+// can then be dropped without touching the pool.
+
+struct Strings {
+  interned: HashSet<Arc<str>>,
+  swept: usize,
+}
+
+impl Strings {
+
+  fn new() -> Strings {
+    Strings {
+      interned: HashSet::new(),
+      swept: 0,
+    }
+  }
+
+  fn intern(&mut self, string: &str) -> Symbol {
+    if let Some(interned) = self.interned.get(string) {
+      return Symbol(interned.clone());
+    }
+    if self.interned.len() >= SWEEP_MIN && self.interned.len() >= 2 * self.swept {
+      self.sweep();
+    }
+    let interned: Arc<str> = Arc::from(string);
+    self.interned.insert(interned.clone());
+    Symbol(interned)
+  }
+
+  // A string only the pool holds can't be reached again except by interning
+  // it, and that waits on the lock, so it's safe to drop
+  fn sweep(&mut self) -> usize {
+    let before = self.interned.len();
+    self.interned.retain(|string| Arc::strong_count(string) > 1);
+    self.swept = self.interned.len();
+    before - self.swept
+  }
+
+}
+
+// A spin lock, since there's no mutex without std. It's only held for a
+// lookup in the pool.
+struct Pool {
+  locked: AtomicBool,
+  strings: UnsafeCell<Option<Strings>>,
+}
+
+unsafe impl Sync for Pool {}
+
+impl Pool {
+
+  fn with<T, F: FnOnce(&mut Strings) -> T>(&self, f: F) -> T {
+    while self.locked.compare_exchange_weak(false, true, atomic::Ordering::Acquire, atomic::Ordering::Relaxed).is_err() {
+      hint::spin_loop();
+    }
+    let _unlock = Unlock(&self.locked);
+    let strings = unsafe { &mut *self.strings.get() };
+    f(strings.get_or_insert_with(Strings::new))
+  }
+
+}
+
+// Lets go of the lock even if the lookup panics
+struct Unlock<'a>(&'a AtomicBool);
+
+impl<'a> Drop for Unlock<'a> {
+  fn drop(&mut self) {
+    self.0.store(false, atomic::Ordering::Release);
+  }
+}
+
+static POOL: Pool = Pool {
+  locked: AtomicBool::new(false),
+  strings: UnsafeCell::new(None),
+};
+
+// Drops every string no symbol refers to, and returns how many went
+pub fn sweep_symbols() -> usize {
+  POOL.with(|strings| strings.sweep())
+}
+
+// How many strings are in the pool, including any waiting for a sweep
+pub fn interned_symbols() -> usize {
+  POOL.with(|strings| strings.interned.len())
+}
@@ -7,6 +7,11 @@
 #[cfg(feature = "no-std")] use alloc::vec::Vec;
 #[cfg(not(feature = "no-std"))] use core::fmt;
 use quantities::{Quantity, ToQuantity, QuantityMath};
+use symbols::Symbol;
+use database::Change;
+use core::cmp;
+use core::mem::size_of;
+use core::iter::FromIterator;
 use hashbrown::hash_map::{HashMap, Entry};
 use serde::*;
 use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeMap, SerializeStruct};
@@ -16,7 +21,7 @@ use serde::ser::{Serialize, Serializer, SerializeSeq, SerializeMap, SerializeStr
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum Value {
   Number(Quantity),
-  String(String),
+  String(Symbol),
   Bool(bool),
   Reference(u64),
   Empty,
@@ -25,11 +30,11 @@ pub enum Value {
 impl Value {
 
   pub fn from_string(string: String) -> Value {
-    Value::String(string)
+    Value::String(Symbol::from(string))
   }
 
   pub fn from_str(string: &str) -> Value {
-    Value::String(String::from(string))
+    Value::String(Symbol::new(string))
   }
 
   pub fn from_u64(num: u64) -> Value {
@@ -80,7 +85,7 @@ impl Value {
 
   pub fn as_string(&self) -> Option<String> {
     match self {
-      Value::String(n) => Some(n.clone()),
+      Value::String(n) => Some(String::from(n.as_str())),
       Value::Number(q) => Some(q.format()),
       Value::Reference(r) => Some(format!("{:?}", r)),
       Value::Empty => Some(String::from("")),
@@ -113,7 +118,7 @@ impl fmt::Debug for Value {
 
 // ### Table Id
 
-#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum TableId {
   Local(u64),
   Global(u64),
@@ -178,6 +183,12 @@ impl Aliases {
     }
   }
 
+  pub fn with_capacity(capacity: usize) -> Aliases {
+    Aliases{
+      0: HashMap::with_capacity(capacity)
+    }
+  }
+
   pub fn len(&self) -> usize {
     self.0.len()
   } 
@@ -186,25 +197,70 @@ impl Aliases {
     self.0.insert(key,value);
   } 
 
+  pub fn get(&self, key: &u64) -> Option<&u64> {
+    self.0.get(key)
+  }
+
+  pub fn contains_key(&self, key: &u64) -> bool {
+    self.0.contains_key(key)
+  }
+
+  pub fn remove(&mut self, key: &u64) -> Option<u64> {
+    self.0.remove(key)
+  }
+
+  pub fn clear(&mut self) {
+    self.0.clear();
+  }
+
   pub fn iter(&self) -> hashbrown::hash_map::Iter<u64,u64> {
     self.0.iter()
   } 
   
 }
 
+// Keys are written as strings so that formats like JSON, which only allow 
+// string keys in maps, can carry aliases too. Entries are sorted so the same 
+// aliases always serialize to the same bytes.
 impl Serialize for Aliases {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut x = serializer.serialize_map(Some(self.0.len()))?;
-        for (k, v) in &self.0 {
+        let mut entries: Vec<(&u64, &u64)> = self.0.iter().collect();
+        entries.sort();
+        let mut x = serializer.serialize_map(Some(entries.len()))?;
+        for (k, v) in entries {
             x.serialize_entry(&k.to_string(), &v)?;
         }
         x.end()
     }
 }
 
+struct AliasesVisitor;
+
+impl<'de> de::Visitor<'de> for AliasesVisitor {
+    type Value = Aliases;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of u64 aliases to u64 indices")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Aliases, M::Error>
+    where
+        M: de::MapAccess<'de>,
+    {
+        let mut aliases = Aliases::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some((key, value)) = access.next_entry::<String, u64>()? {
+            let key = match key.parse::<u64>() {
+                Ok(key) => key,
+                Err(_) => return Err(de::Error::invalid_value(de::Unexpected::Str(&key), &self)),
+            };
+            aliases.insert(key, value);
+        }
+        Ok(aliases)
+    }
+}
 
 impl<'de> Deserialize<'de> for Aliases
 {
@@ -212,7 +268,7 @@ impl<'de> Deserialize<'de> for Aliases
     where
         D: Deserializer<'de>,
     {
-      Ok(Aliases::new())
+      deserializer.deserialize_map(AliasesVisitor)
     }
 }
 
@@ -222,17 +278,462 @@ pub struct Bar {
   pub x: Aliases,
 }
 
+// ### Columns
+
+// Each column is stored as a vector of whichever kind of value fills it, so a
+// column of numbers takes 8 bytes a cell instead of a whole Value. Empty cells
+// are marked off in a validity bitmap. A column that ends up holding more than
+// one kind of value falls back to storing Values.
+
+// One bit per cell
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Bitmap {
+  words: Vec<u64>,
+  len: usize,
+}
+
+impl Bitmap {
+
+  pub fn new() -> Bitmap {
+    Bitmap{words: Vec::new(), len: 0}
+  }
+
+  pub fn filled(len: usize, bit: bool) -> Bitmap {
+    let word = if bit { !0 } else { 0 };
+    let mut bitmap = Bitmap{words: vec![word; (len + 63) / 64], len};
+    bitmap.clear_tail();
+    bitmap
+  }
+
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  pub fn get(&self, ix: usize) -> bool {
+    assert!(ix < self.len, "bit {} out of bounds", ix);
+    self.words[ix / 64] >> (ix % 64) & 1 == 1
+  }
+
+  pub fn set(&mut self, ix: usize, bit: bool) {
+    assert!(ix < self.len, "bit {} out of bounds", ix);
+    if bit {
+      self.words[ix / 64] |= 1 << (ix % 64);
+    } else {
+      self.words[ix / 64] &= !(1 << (ix % 64));
+    }
+  }
+
+  pub fn push(&mut self, bit: bool) {
+    if self.len % 64 == 0 {
+      self.words.push(0);
+    }
+    self.len += 1;
+    let ix = self.len - 1;
+    self.set(ix, bit);
+  }
+
+  // New bits are cleared
+  pub fn resize(&mut self, len: usize) {
+    self.words.resize((len + 63) / 64, 0);
+    self.len = len;
+    self.clear_tail();
+  }
+
+  pub fn count_ones(&self) -> usize {
+    self.words.iter().map(|word| word.count_ones() as usize).sum()
+  }
+
+  pub fn all(&self) -> bool {
+    self.count_ones() == self.len
+  }
+
+  pub fn heap_size(&self) -> usize {
+    self.words.capacity() * 8
+  }
+
+  // Bits past the end are kept cleared so whole words can be counted
+  fn clear_tail(&mut self) {
+    let used = self.len % 64;
+    if used != 0 {
+      let last = self.words.len() - 1;
+      self.words[last] &= (1 << used) - 1;
+    }
+  }
+
+}
+
+// The symbols in a column are listed once each, and cells hold a code for
+// them that's smaller than a symbol. Codes are counted, so a symbol no cell
+// holds anymore is let go of, and its code goes to the next new symbol.
+#[derive(Clone, Debug, Default)]
+pub struct Dictionary {
+  symbols: Vec<Option<Symbol>>,
+  counts: Vec<u32>,
+  codes: HashMap<Symbol, u32>,
+  free: Vec<u32>,
+}
+
+impl Dictionary {
+
+  pub fn new() -> Dictionary {
+    Dictionary{symbols: Vec::new(), counts: Vec::new(), codes: HashMap::new(), free: Vec::new()}
+  }
+
+  // The code for a symbol, counting one more cell that holds it
+  pub fn code(&mut self, symbol: Symbol) -> u32 {
+    let code = match self.codes.entry(symbol) {
+      Entry::Occupied(o) => *o.get(),
+      Entry::Vacant(v) => {
+        let code = match self.free.pop() {
+          Some(code) => {
+            self.symbols[code as usize] = Some(v.key().clone());
+            code
+          },
+          None => {
+            self.symbols.push(Some(v.key().clone()));
+            self.counts.push(0);
+            self.symbols.len() as u32 - 1
+          },
+        };
+        v.insert(code);
+        code
+      }
+    };
+    self.counts[code as usize] += 1;
+    code
+  }
+
+  // A cell doesn't hold the code anymore
+  pub fn release(&mut self, code: u32) {
+    let count = &mut self.counts[code as usize];
+    *count -= 1;
+    if *count == 0 {
+      if let Some(symbol) = self.symbols[code as usize].take() {
+        self.codes.remove(&symbol);
+      }
+      self.free.push(code);
+    }
+  }
+
+  pub fn symbol(&self, code: u32) -> &Symbol {
+    self.symbols[code as usize].as_ref().expect("code isn't in use")
+  }
+
+  // How many symbols are in use
+  pub fn len(&self) -> usize {
+    self.codes.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.codes.is_empty()
+  }
+
+  // The strings themselves are in the symbol pool, so they aren't counted
+  pub fn heap_size(&self) -> usize {
+    self.symbols.capacity() * size_of::<Option<Symbol>>()
+      + self.counts.capacity() * size_of::<u32>()
+      + self.codes.capacity() * (size_of::<Symbol>() + size_of::<u32>())
+      + self.free.capacity() * size_of::<u32>()
+  }
+
+}
+
+#[derive(Clone)]
+pub enum Column {
+  Empty(usize),
+  Number(Vec<Quantity>, Bitmap),
+  Bool(Vec<bool>, Bitmap),
+  String(Vec<u32>, Dictionary, Bitmap),
+  Reference(Vec<u64>, Bitmap),
+  Mixed(Vec<Value>),
+}
+
+impl Column {
+
+  pub fn new(len: usize) -> Column {
+    Column::Empty(len)
+  }
+
+  pub fn from_values(values: Vec<Value>) -> Column {
+    let mut column = Column::new(0);
+    for value in values {
+      column.push(value);
+    }
+    column
+  }
+
+  pub fn from_numbers(numbers: Vec<Quantity>) -> Column {
+    let valid = Bitmap::filled(numbers.len(), true);
+    Column::Number(numbers, valid)
+  }
+
+  pub fn from_bools(bools: Vec<bool>) -> Column {
+    let valid = Bitmap::filled(bools.len(), true);
+    Column::Bool(bools, valid)
+  }
+
+  pub fn len(&self) -> usize {
+    match self {
+      Column::Empty(len) => *len,
+      Column::Number(values, _) => values.len(),
+      Column::Bool(values, _) => values.len(),
+      Column::String(codes, _, _) => codes.len(),
+      Column::Reference(values, _) => values.len(),
+      Column::Mixed(values) => values.len(),
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  pub fn get(&self, ix: usize) -> Option<Value> {
+    if ix < self.len() {
+      Some(self.value(ix))
+    } else {
+      None
+    }
+  }
+
+  // The value in a cell. Panics if the cell is past the end of the column.
+  pub fn value(&self, ix: usize) -> Value {
+    match self {
+      Column::Empty(len) => {
+        assert!(ix < *len, "cell {} out of bounds", ix);
+        Value::Empty
+      },
+      Column::Number(values, valid) => match valid.get(ix) {
+        true => Value::Number(values[ix]),
+        false => Value::Empty,
+      },
+      Column::Bool(values, valid) => match valid.get(ix) {
+        true => Value::Bool(values[ix]),
+        false => Value::Empty,
+      },
+      Column::String(codes, dictionary, valid) => match valid.get(ix) {
+        true => Value::String(dictionary.symbol(codes[ix]).clone()),
+        false => Value::Empty,
+      },
+      Column::Reference(values, valid) => match valid.get(ix) {
+        true => Value::Reference(values[ix]),
+        false => Value::Empty,
+      },
+      Column::Mixed(values) => values[ix].clone(),
+    }
+  }
+
+  // Sets a cell, and returns what was there before
+  pub fn set(&mut self, ix: usize, value: Value) -> Value {
+    let old_value = self.value(ix);
+    self.store(ix, value);
+    old_value
+  }
+
+  // Adds a cell to the end of the column
+  pub fn push(&mut self, value: Value) {
+    let len = self.len();
+    self.resize(len + 1);
+    self.store(len, value);
+  }
+
+  // Grows the column with empty cells, or cuts it short
+  pub fn resize(&mut self, len: usize) {
+    match self {
+      Column::Empty(old_len) => *old_len = len,
+      Column::Number(values, valid) => { values.resize(len, 0); valid.resize(len); },
+      Column::Bool(values, valid) => { values.resize(len, false); valid.resize(len); },
+      Column::String(codes, dictionary, valid) => {
+        for ix in len..codes.len() {
+          if valid.get(ix) {
+            dictionary.release(codes[ix]);
+          }
+        }
+        codes.resize(len, 0);
+        valid.resize(len);
+      },
+      Column::Reference(values, valid) => { values.resize(len, 0); valid.resize(len); },
+      Column::Mixed(values) => values.resize(len, Value::Empty),
+    }
+  }
+
+  pub fn truncate(&mut self, len: usize) {
+    if len < self.len() {
+      self.resize(len);
+    }
+  }
+
+  pub fn clear(&mut self) {
+    *self = Column::Empty(0);
+  }
+
+  pub fn append(&mut self, other: &Column) {
+    for value in other.iter() {
+      self.push(value);
+    }
+  }
+
+  pub fn iter<'a>(&'a self) -> impl Iterator<Item=Value> + 'a {
+    (0..self.len()).map(move |ix| self.value(ix))
+  }
+
+  pub fn to_values(&self) -> Vec<Value> {
+    self.iter().collect()
+  }
+
+  // The numbers in the column, if every cell holds one
+  pub fn numbers(&self) -> Option<&[Quantity]> {
+    match self {
+      Column::Number(values, valid) if valid.all() => Some(&values[..]),
+      _ => None,
+    }
+  }
+
+  // The bools in the column, if every cell holds one
+  pub fn bools(&self) -> Option<&[bool]> {
+    match self {
+      Column::Bool(values, valid) if valid.all() => Some(&values[..]),
+      _ => None,
+    }
+  }
+
+  // Bytes the column holds on the heap
+  pub fn heap_size(&self) -> usize {
+    match self {
+      Column::Empty(_) => 0,
+      Column::Number(values, valid) => values.capacity() * size_of::<Quantity>() + valid.heap_size(),
+      Column::Bool(values, valid) => values.capacity() + valid.heap_size(),
+      Column::String(codes, dictionary, valid) => codes.capacity() * size_of::<u32>() + dictionary.heap_size() + valid.heap_size(),
+      Column::Reference(values, valid) => values.capacity() * size_of::<u64>() + valid.heap_size(),
+      Column::Mixed(values) => values.capacity() * size_of::<Value>(),
+    }
+  }
+
+  fn store(&mut self, ix: usize, value: Value) {
+    if !self.holds(&value) {
+      let len = self.len();
+      // A column with nothing in it takes on the kind of the new value. 
+      // Otherwise it goes back to holding Values.
+      *self = match (self.valid_cells(), &value) {
+        (0, Value::Number(_)) => Column::Number(vec![0; len], Bitmap::filled(len, false)),
+        (0, Value::Bool(_)) => Column::Bool(vec![false; len], Bitmap::filled(len, false)),
+        (0, Value::String(_)) => Column::String(vec![0; len], Dictionary::new(), Bitmap::filled(len, false)),
+        (0, Value::Reference(_)) => Column::Reference(vec![0; len], Bitmap::filled(len, false)),
+        _ => Column::Mixed(self.to_values()),
+      };
+    }
+    match (self, value) {
+      (Column::Mixed(values), value) => values[ix] = value,
+      (Column::Empty(_), Value::Empty) => (),
+      (Column::String(codes, dictionary, valid), Value::Empty) => {
+        if valid.get(ix) {
+          dictionary.release(codes[ix]);
+        }
+        valid.set(ix, false);
+      },
+      (Column::Number(_, valid), Value::Empty) |
+      (Column::Bool(_, valid), Value::Empty) |
+      (Column::Reference(_, valid), Value::Empty) => valid.set(ix, false),
+      (Column::Number(values, valid), Value::Number(x)) => { values[ix] = x; valid.set(ix, true); },
+      (Column::Bool(values, valid), Value::Bool(x)) => { values[ix] = x; valid.set(ix, true); },
+      (Column::String(codes, dictionary, valid), Value::String(x)) => {
+        let code = dictionary.code(x);
+        if valid.get(ix) {
+          dictionary.release(codes[ix]);
+        }
+        codes[ix] = code;
+        valid.set(ix, true);
+      },
+      (Column::Reference(values, valid), Value::Reference(x)) => { values[ix] = x; valid.set(ix, true); },
+      _ => unreachable!(),
+    }
+  }
+
+  fn holds(&self, value: &Value) -> bool {
+    match (self, value) {
+      (_, Value::Empty) |
+      (Column::Mixed(_), _) |
+      (Column::Number(..), Value::Number(_)) |
+      (Column::Bool(..), Value::Bool(_)) |
+      (Column::String(..), Value::String(_)) |
+      (Column::Reference(..), Value::Reference(_)) => true,
+      _ => false,
+    }
+  }
+
+  fn valid_cells(&self) -> usize {
+    match self {
+      Column::Empty(_) => 0,
+      Column::Number(_, valid) |
+      Column::Bool(_, valid) |
+      Column::String(_, _, valid) |
+      Column::Reference(_, valid) => valid.count_ones(),
+      Column::Mixed(values) => values.iter().filter(|value| **value != Value::Empty).count(),
+    }
+  }
+
+}
+
+impl PartialEq for Column {
+  fn eq(&self, other: &Column) -> bool {
+    self.len() == other.len() && self.iter().eq(other.iter())
+  }
+}
+
+impl fmt::Debug for Column {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.debug_list().entries(self.iter()).finish()
+  }
+}
+
+impl FromIterator<Value> for Column {
+  fn from_iter<I: IntoIterator<Item=Value>>(iter: I) -> Column {
+    let mut column = Column::new(0);
+    for value in iter {
+      column.push(value);
+    }
+    column
+  }
+}
+
+// Columns are written as a list of values, whatever they're stored as
+impl Serialize for Column {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where
+    S: Serializer,
+  {
+    let mut seq = serializer.serialize_seq(Some(self.len()))?;
+    for value in self.iter() {
+      seq.serialize_element(&value)?;
+    }
+    seq.end()
+  }
+}
+
+impl<'de> Deserialize<'de> for Column {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>,
+  {
+    let values: Vec<Value> = Vec::deserialize(deserializer)?;
+    Ok(Column::from_values(values))
+  }
+}
+
 // ### Table
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Table {
   pub id: u64,
   pub rows: u64,
   pub columns: u64,
   pub column_aliases: Aliases,
   pub column_index_to_alias: Vec<Option<u64>>, 
-  pub row_aliases: HashMap<u64, u64>,
-  pub data: Vec<Vec<Value>>,
+  pub row_aliases: Aliases,
+  pub data: Vec<Column>,
 }
 
 impl Table {
@@ -244,8 +745,8 @@ impl Table {
       columns: columns,
       column_aliases: Aliases::new(),
       column_index_to_alias: Vec::new(),
-      row_aliases: HashMap::with_capacity(rows as usize),
-      data: vec![vec![Value::Empty; rows as usize]; columns as usize], 
+      row_aliases: Aliases::with_capacity(rows as usize),
+      data: vec![Column::new(rows as usize); columns as usize], 
     }
   }
 
@@ -270,7 +771,7 @@ impl Table {
   pub fn get_column_alias(&self, column: &Index) -> Option<u64> {
     match column {
       Index::Index(ix) => {
-        if self.column_index_to_alias.len() > 0 {
+        if *ix > 0 && self.column_index_to_alias.len() >= *ix as usize {
           self.column_index_to_alias[*ix as usize - 1]
         } else {
           None
@@ -294,9 +795,7 @@ impl Table {
     let row_ix = self.get_row_index(row).unwrap() as usize;
     let column_ix = self.get_column_index(column).unwrap() as usize;
     self.grow_to_fit(row_ix as u64, column_ix as u64);
-    let old_value = self.data[column_ix - 1][row_ix - 1].clone();
-    self.data[column_ix - 1][row_ix - 1] = value;
-    old_value
+    self.data[column_ix - 1].set(row_ix - 1, value)
   }
 
   pub fn set_column_alias(&mut self, alias: u64, ix: u64) {
@@ -313,74 +812,36 @@ impl Table {
       },
     }
   }
-  /*
-  pub fn add_row(&mut self) {
-    let rows = self.rows + 1;
-    let columns = self.columns;
-    self.grow_to_fit(rows, columns);
-  }
-
-  pub fn add_column(&mut self, attribute: u64) {
-    if !self.column_aliases.contains_key(&attribute) {
-      let columns = self.columns + 1;
-      let rows = self.rows;
-      self.grow_to_fit(rows, columns);
-      self.column_aliases.insert(attribute.clone(), columns);
-      if self.column_ids[columns - 1] == None {
-        self.column_ids[columns - 1] = Some(attribute.clone());
-      }
-    };
-  }
-
-  pub fn get_columns(&self, column_ids: Vec<usize>) -> Vec<Option<&Vec<Value>>> {
-    let mut columns: Vec<Option<&Vec<Value>>> = vec![];
-    for id in column_ids{
-      let column = self.get_column_by_id(id);
-      columns.push(column);
-    }
-    columns
-  }
-
-  pub fn get_columns_by_ixes(&self, column_ixes: Vec<usize>) -> Vec<Option<&Vec<Value>>> {
-    let mut columns: Vec<Option<&Vec<Value>>> = vec![];
-    for ix in column_ixes{
-      let column = self.get_column_by_ix(ix);
-      columns.push(column);
-    }
-    columns
-  }
-
-  pub fn get_column_mut_by_ix(&mut self, column_ix: usize) -> Option<&mut Vec<Value>> {
-    if self.columns > 0 && self.columns >= column_ix {
-      let column_data = &mut self.data[column_ix - 1];      
-      Some(column_data)
-    } else {
-      None
+  pub fn remove_column_alias(&mut self, alias: u64) {
+    match self.column_aliases.remove(&alias) {
+      Some(ix) => {
+        if let Some(entry) = self.column_index_to_alias.get_mut(ix as usize - 1) {
+          *entry = None;
+        }
+        // Drop trailing unaliased columns, so the mapping is only as long as 
+        // the last aliased column, just as set_column_alias leaves it.
+        while self.column_index_to_alias.last() == Some(&None) {
+          self.column_index_to_alias.pop();
+        }
+      },
+      None => (),
     }
   }
 
-  pub fn get_column_mut(&mut self, column_id: usize) -> Option<&mut Vec<Value>> {
-    match self.column_aliases.get_mut(&(column_id as u64)) {
-      Some(column_ix) => {
-        let column_data = &mut self.data[*column_ix - 1];      
-        Some(column_data)
-      },
-      None => None,
-    }
-  }*/
+  
 
   pub fn grow_to_fit(&mut self, rows: u64, columns: u64) {
     if columns > self.columns {
       // The new row is larger than the underlying column structure
       if columns > self.data.len() as u64 {
-        let new_column = vec![Value::Empty; self.rows as usize];
+        let new_column = Column::new(self.rows as usize);
         self.data.resize(columns as usize, new_column);
       }
       self.columns = columns;
     }
     if rows > self.rows {
       for column in &mut self.data {
-        column.resize(rows as usize, Value::Empty);
+        column.resize(rows as usize);
       }
       self.rows = rows;
     }    
@@ -388,22 +849,29 @@ impl Table {
 
   pub fn shrink_to_fit(&mut self, rows: u64, columns: u64) {
     if columns < self.columns {
-      // The new row is larger than the underlying column structure
-      if columns > self.data.len() as u64 {
-        let new_column = vec![Value::Empty; self.rows as usize];
-        self.data.resize(columns as usize, new_column);
-      }
+      self.data.truncate(columns as usize);
       self.columns = columns;
     }
     if rows < self.rows {
       for column in &mut self.data {
-        column.resize(rows as usize, Value::Empty);
+        column.truncate(rows as usize);
       }
       self.rows = rows;
     }    
   }
   
-  pub fn get_column(&self, column: &Index) -> Option<&Vec<Value>> {
+  // The values in a column, copied out of it
+  pub fn get_column(&self, column: &Index) -> Option<Vec<Value>> {
+    match self.get_column_index(column) {
+      Some(column_ix) => {
+        Some(self.data[column_ix as usize - 1].to_values())
+      }
+      None => None,
+    }
+  }
+
+  // The column as it's stored, without copying its values out
+  pub fn get_column_data(&self, column: &Index) -> Option<&Column> {
     match self.get_column_index(column) {
       Some(column_ix) => {
         Some(&self.data[column_ix as usize - 1])
@@ -418,7 +886,7 @@ impl Table {
         let mut row: Vec<Value> = vec![];
         // Get the index for the given attribute
         for column_ix in 0 .. self.columns as usize {
-          let cell = self.data[column_ix][row_ix as usize - 1].clone();
+          let cell = self.data[column_ix].value(row_ix as usize - 1);
           row.push(cell);
         }
         Some(row)
@@ -428,11 +896,16 @@ impl Table {
   }
 
   // Index into a cell without having to access the data member directly
-  pub fn index(&self, row: &Index, column: &Index) -> Option<&Value> {
+  pub fn index(&self, row: &Index, column: &Index) -> Option<Value> {
+    self.get_value(row, column)
+  }
+
+  // The value in a cell, read straight out of its column
+  pub fn get_value(&self, row: &Index, column: &Index) -> Option<Value> {
     let row_ix = self.get_row_index(row).unwrap();
     let column_ix = self.get_column_index(column).unwrap();
     if column_ix <= self.columns && row_ix <= self.rows {
-      Some(&self.data[column_ix as usize - 1][row_ix as usize - 1])
+      Some(self.data[column_ix as usize - 1].value(row_ix as usize - 1))
     } else {
       None
     }
@@ -443,6 +916,49 @@ impl Table {
     self.set_cell(row, column, Value::Empty);
   }
 
+  // The changes that turn this table into the other one: a RenameColumn for
+  // each alias it's missing, then a Remove for every cell the other table 
+  // leaves empty, and a Set for every cell with a different value. Tables 
+  // don't shrink, so cells beyond the other table are emptied instead.
+  pub fn diff(&self, other: &Table) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut aliases: Vec<(&u64, &u64)> = other.column_aliases.iter().collect();
+    aliases.sort_by_key(|(_, ix)| **ix);
+    for (alias, ix) in aliases {
+      if self.column_aliases.get(alias) != Some(ix) {
+        changes.push(Change::RenameColumn{table: self.id, column_ix: *ix, column_alias: *alias});
+      }
+    }
+    let rows = cmp::max(self.rows, other.rows);
+    let columns = cmp::max(self.columns, other.columns);
+    for column in 1..columns + 1 {
+      for row in 1..rows + 1 {
+        let old = self.cell(row, column);
+        let new = other.cell(row, column);
+        if old != new {
+          let (row, column) = (Index::Index(row), Index::Index(column));
+          match new {
+            Value::Empty => changes.push(Change::Remove{table: self.id, row, column, value: old}),
+            _ => changes.push(Change::Set{table: self.id, row, column, value: new}),
+          }
+        }
+      }
+    }
+    changes
+  }
+
+  fn cell(&self, row: u64, column: u64) -> Value {
+    match self.data.get(column as usize - 1).and_then(|values| values.get(row as usize - 1)) {
+      Some(value) => value,
+      None => Value::Empty,
+    }
+  }
+
+  // Bytes the table's cells take up on the heap
+  pub fn heap_size(&self) -> usize {
+    self.data.iter().map(|column| column.heap_size()).sum()
+  }
+
 }
 
 // ### Pretty Printing Tables
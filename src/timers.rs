@@ -0,0 +1,181 @@
+// # Timers
+
+// Time as an input. The #time/timer table has a row for each timer, with a
+// period column and a ticks column. Blocks or the host ask for a timer by
+// writing a period, in milliseconds, into a row. The timer machine then
+// counts ticks into that row as the periods go by, and blocks that read the
+// ticks run on every tick. Writing an empty period, or zero, stops a timer.
+
+// Time comes from a TimeSource, so tests can drive timers with a clock they
+// move by hand.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+#[cfg(feature = "no-std")] use alloc::rc::Rc;
+#[cfg(feature = "no-std")] use alloc::boxed::Box;
+#[cfg(not(feature = "no-std"))] use std::rc::Rc;
+use core::cell::Cell;
+use table::{Value, Index, Table};
+use database::Change;
+use indexes::Hasher;
+use runtime::Register;
+use machines::Machine;
+
+// ## Time Sources
+
+pub trait TimeSource {
+  // Milliseconds since some fixed point. It should never go backwards.
+  fn now(&mut self) -> u64;
+}
+
+// Milliseconds since the Unix epoch
+#[cfg(not(feature = "no-std"))]
+pub struct SystemTime;
+
+#[cfg(not(feature = "no-std"))]
+impl TimeSource for SystemTime {
+  fn now(&mut self) -> u64 {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+      Ok(duration) => duration.as_secs() * 1000 + duration.subsec_millis() as u64,
+      Err(_) => 0,
+    }
+  }
+}
+
+// A clock that only moves when it's told to. Clones share the same time.
+#[derive(Clone, Debug)]
+pub struct ManualTime {
+  time: Rc<Cell<u64>>,
+}
+
+impl ManualTime {
+
+  pub fn new(time: u64) -> ManualTime {
+    ManualTime {
+      time: Rc::new(Cell::new(time)),
+    }
+  }
+
+  pub fn advance(&self, milliseconds: u64) {
+    self.time.set(self.time.get() + milliseconds);
+  }
+
+  pub fn set(&self, time: u64) {
+    self.time.set(time);
+  }
+
+}
+
+impl TimeSource for ManualTime {
+  fn now(&mut self) -> u64 {
+    self.time.get()
+  }
+}
+
+// ## Timer Machine
+
+pub fn timer_table() -> u64 {
+  Hasher::hash_str("time/timer")
+}
+
+pub fn period_column() -> u64 {
+  Hasher::hash_str("period")
+}
+
+pub fn ticks_column() -> u64 {
+  Hasher::hash_str("ticks")
+}
+
+struct Timer {
+  row: u64,
+  period: u64,
+  next_tick: u64,
+  ticks: u64,
+}
+
+pub struct TimerMachine {
+  source: Box<dyn TimeSource>,
+  timers: Vec<Timer>,
+}
+
+impl TimerMachine {
+
+  pub fn new(source: Box<dyn TimeSource>) -> TimerMachine {
+    TimerMachine {
+      source,
+      timers: Vec::new(),
+    }
+  }
+
+}
+
+impl Machine for TimerMachine {
+
+  fn inputs(&self) -> Vec<u64> {
+    vec![timer_table()]
+  }
+
+  fn outputs(&self) -> Vec<Register> {
+    vec![Register::new(timer_table(), Index::Alias(period_column()))]
+  }
+
+  fn setup(&mut self) -> Vec<Change> {
+    let table = timer_table();
+    vec![
+      Change::NewTable{id: table, rows: 0, columns: 2},
+      Change::RenameColumn{table, column_ix: 1, column_alias: period_column()},
+      Change::RenameColumn{table, column_ix: 2, column_alias: ticks_column()},
+    ]
+  }
+
+  // Tick every timer whose period is up. A timer that missed several ticks
+  // catches up on all of them at once.
+  fn poll(&mut self) -> Vec<Change> {
+    let now = self.source.now();
+    let mut changes = Vec::new();
+    for timer in self.timers.iter_mut() {
+      if now >= timer.next_tick {
+        let missed = (now - timer.next_tick) / timer.period + 1;
+        timer.ticks += missed;
+        timer.next_tick += missed * timer.period;
+        changes.push(Change::Set{
+          table: timer_table(),
+          row: Index::Index(timer.row),
+          column: Index::Alias(ticks_column()),
+          value: Value::from_u64(timer.ticks),
+        });
+      }
+    }
+    changes
+  }
+
+  // Start, stop and change timers to match their periods
+  fn output(&mut self, _register: &Register, table: &Table) {
+    let now = self.source.now();
+    let column = match table.get_column_index(&Index::Alias(period_column())) {
+      Some(column) => column,
+      None => return,
+    };
+    let mut timers = Vec::new();
+    for row in 1..table.rows + 1 {
+      let period = match table.get_value(&Index::Index(row), &Index::Index(column)) {
+        Some(value) => value.as_u64().unwrap_or(0),
+        None => 0,
+      };
+      if period == 0 {
+        continue;
+      }
+      match self.timers.iter().position(|timer| timer.row == row) {
+        Some(ix) if self.timers[ix].period == period => timers.push(self.timers.remove(ix)),
+        Some(ix) => {
+          let timer = self.timers.remove(ix);
+          timers.push(Timer{row, period, next_tick: now + period, ticks: timer.ticks});
+        },
+        None => timers.push(Timer{row, period, next_tick: now + period, ticks: 0}),
+      }
+    }
+    self.timers = timers;
+  }
+
+}
@@ -0,0 +1,73 @@
+// # View
+
+// A read-only snapshot of the tables as they were at some point in time. It's
+// reconstructed from the change log, so looking at the past doesn't disturb
+// the live store.
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::vec::{self, Vec};
+#[cfg(not(feature = "no-std"))] use std::vec;
+use table::{Value, Index, Table, Column};
+use indexes::TableIndex;
+use hashbrown::hash_map::Values;
+
+// ## View
+
+#[derive(Clone, Debug)]
+pub struct View {
+  pub time: usize, // an offset from now, like Core::offset
+  pub tables: TableIndex,
+}
+
+impl View {
+
+  pub fn new(time: usize, tables: TableIndex) -> View {
+    View {
+      time,
+      tables,
+    }
+  }
+
+  pub fn get_table(&self, table: u64) -> Option<&Table> {
+    self.tables.get(table)
+  }
+
+  pub fn index(&self, table: u64, row: &Index, column: &Index) -> Option<Value> {
+    match self.tables.get(table) {
+      Some(table_ref) => table_ref.index(row, column),
+      None => None,
+    }
+  }
+
+  pub fn get_value(&self, table: u64, row: &Index, column: &Index) -> Option<Value> {
+    match self.tables.get(table) {
+      Some(table_ref) => table_ref.get_value(row, column),
+      None => None,
+    }
+  }
+
+  pub fn get_column(&self, table: u64, column: &Index) -> Option<Vec<Value>> {
+    match self.tables.get(table) {
+      Some(table_ref) => table_ref.get_column(column),
+      None => None,
+    }
+  }
+
+  pub fn get_column_data(&self, table: u64, column: &Index) -> Option<&Column> {
+    match self.tables.get(table) {
+      Some(table_ref) => table_ref.get_column_data(column),
+      None => None,
+    }
+  }
+
+  // Iterate over the values of one column, top to bottom
+  pub fn column(&self, table: u64, column: &Index) -> Option<vec::IntoIter<Value>> {
+    self.get_column(table, column).map(|values| values.into_iter())
+  }
+
+  pub fn tables(&self) -> Values<'_, u64, Table> {
+    self.tables.map.values()
+  }
+
+}
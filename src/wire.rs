@@ -0,0 +1,433 @@
+// # Wire Format
+
+/*
+A compact, versioned binary encoding for Transactions, suitable for sending
+over serial links and UDP where every byte counts. The layout is:
+
+  version: u8
+  strings: varint count, then (varint length, utf8 bytes) for each
+  tables, names, removes, adds: varint count, then each change
+
+A RemoveTable is followed by the changes that fill the table back in, as a
+varint count and then each change. Only Sets into numbered cells and
+RenameColumns can appear there. Anything else in a RemoveTable's contents
+can't fill a table in, so it's left out.
+
+Each change starts with a tag byte:
+
+  |VVV|C|R|KKK|
+  K: change kind
+  R: the row is an alias
+  C: the column is an alias
+  V: value kind (Set and Remove only)
+
+Ids, aliases and lengths are LEB128 varints. Row and column indices are
+zigzag-encoded deltas from the previous index in the same section, so runs of
+neighbouring cells cost a byte each. Quantities are packed as a zigzagged
+mantissa, with the domain and range appended only when they are non-zero.
+String values are interned into the string table at the head of the message
+and referenced by position.
+*/
+
+// ## Prelude
+
+#[cfg(feature = "no-std")] use alloc::string::String;
+#[cfg(feature = "no-std")] use alloc::vec::Vec;
+use core::str;
+use table::{Value, Index};
+use symbols::Symbol;
+use database::{Transaction, Change};
+use hashbrown::hash_map::{HashMap, Entry};
+use errors::ErrorType;
+
+pub const WIRE_VERSION: u8 = 2;
+
+const MANTISSA_BITS: u64 = 49;
+const MANTISSA_MASK: u64 = (1 << MANTISSA_BITS) - 1;
+
+// Change kinds
+const SET: u8 = 0;
+const REMOVE: u8 = 1;
+const NEW_TABLE: u8 = 2;
+const RENAME_COLUMN: u8 = 3;
+const REMOVE_TABLE: u8 = 4;
+
+// Value kinds
+const NUMBER: u8 = 0;
+const STRING: u8 = 1;
+const FALSE: u8 = 2;
+const TRUE: u8 = 3;
+const REFERENCE: u8 = 4;
+const EMPTY: u8 = 5;
+
+const KIND_MASK: u8 = 0b0000_0111;
+const ROW_ALIAS: u8 = 0b0000_1000;
+const COLUMN_ALIAS: u8 = 0b0001_0000;
+const VALUE_SHIFT: u8 = 5;
+
+impl Transaction {
+
+  pub fn encode(&self) -> Vec<u8> {
+    let mut encoder = Encoder::new();
+    let mut body = Vec::new();
+    for section in &[&self.tables, &self.names, &self.removes, &self.adds] {
+      encoder.encode_section(section, &mut body);
+    }
+    let mut bytes = Vec::with_capacity(body.len() + 16);
+    bytes.push(WIRE_VERSION);
+    write_varint(encoder.strings.len() as u64, &mut bytes);
+    for string in &encoder.strings {
+      write_varint(string.len() as u64, &mut bytes);
+      bytes.extend_from_slice(string.as_bytes());
+    }
+    bytes.append(&mut body);
+    bytes
+  }
+
+  pub fn decode(bytes: &[u8]) -> Result<Transaction, ErrorType> {
+    let mut decoder = Decoder::new(bytes);
+    let version = decoder.read_byte()?;
+    if version != WIRE_VERSION {
+      return Err(ErrorType::UnsupportedWireVersion(version));
+    }
+    let string_count = decoder.read_count()?;
+    let mut strings = Vec::with_capacity(string_count);
+    for _ in 0..string_count {
+      let length = decoder.read_count()?;
+      let start = decoder.position;
+      let raw = decoder.read_bytes(length)?;
+      match str::from_utf8(raw) {
+        Ok(string) => strings.push(Symbol::new(string)),
+        Err(_) => return Err(ErrorType::MalformedWireData(start)),
+      }
+    }
+    let mut txn = Transaction::new();
+    txn.tables = decoder.decode_section(&strings)?;
+    txn.names = decoder.decode_section(&strings)?;
+    txn.removes = decoder.decode_section(&strings)?;
+    txn.adds = decoder.decode_section(&strings)?;
+    if decoder.position != bytes.len() {
+      return Err(ErrorType::MalformedWireData(decoder.position));
+    }
+    Ok(txn)
+  }
+
+}
+
+// The changes a RemoveTable can carry to fill the table back in
+fn fills_table(change: &Change) -> bool {
+  matches!(change, Change::Set{row: Index::Index(1..), column: Index::Index(1..), ..} | Change::RenameColumn{..})
+}
+
+// ## Encoder
+
+struct Encoder {
+  strings: Vec<Symbol>,
+  string_ids: HashMap<Symbol, u64>,
+  last_row: u64,
+  last_column: u64,
+}
+
+impl Encoder {
+
+  fn new() -> Encoder {
+    Encoder {
+      strings: Vec::new(),
+      string_ids: HashMap::new(),
+      last_row: 0,
+      last_column: 0,
+    }
+  }
+
+  fn intern(&mut self, string: &Symbol) -> u64 {
+    match self.string_ids.entry(string.clone()) {
+      Entry::Occupied(o) => *o.get(),
+      Entry::Vacant(v) => {
+        let id = self.strings.len() as u64;
+        self.strings.push(string.clone());
+        v.insert(id);
+        id
+      },
+    }
+  }
+
+  fn encode_section(&mut self, changes: &Vec<Change>, out: &mut Vec<u8>) {
+    self.last_row = 0;
+    self.last_column = 0;
+    write_varint(changes.len() as u64, out);
+    for change in changes {
+      self.encode_change(change, out);
+    }
+  }
+
+  fn encode_change(&mut self, change: &Change, out: &mut Vec<u8>) {
+    match change {
+      Change::Set{table, row, column, value} => self.encode_cell(SET, *table, row, column, value, out),
+      Change::Remove{table, row, column, value} => self.encode_cell(REMOVE, *table, row, column, value, out),
+      Change::NewTable{id, rows, columns} => {
+        out.push(NEW_TABLE);
+        write_varint(*id, out);
+        write_varint(*rows, out);
+        write_varint(*columns, out);
+      },
+      Change::RenameColumn{table, column_ix, column_alias} => {
+        out.push(RENAME_COLUMN);
+        write_varint(*table, out);
+        write_varint(*column_ix, out);
+        write_varint(*column_alias, out);
+      },
+      Change::RemoveTable{id, rows, columns, contents} => {
+        out.push(REMOVE_TABLE);
+        write_varint(*id, out);
+        write_varint(*rows, out);
+        write_varint(*columns, out);
+        let contents: Vec<&Change> = contents.iter().filter(|change| fills_table(change)).collect();
+        write_varint(contents.len() as u64, out);
+        for change in contents {
+          self.encode_change(change, out);
+        }
+      },
+    }
+  }
+
+  fn encode_cell(&mut self, kind: u8, table: u64, row: &Index, column: &Index, value: &Value, out: &mut Vec<u8>) {
+    let mut tag = kind;
+    if let Index::Alias(_) = row { tag |= ROW_ALIAS; }
+    if let Index::Alias(_) = column { tag |= COLUMN_ALIAS; }
+    let value_kind = match value {
+      Value::Number(_) => NUMBER,
+      Value::String(_) => STRING,
+      Value::Bool(false) => FALSE,
+      Value::Bool(true) => TRUE,
+      Value::Reference(_) => REFERENCE,
+      Value::Empty => EMPTY,
+    };
+    out.push(tag | value_kind << VALUE_SHIFT);
+    write_varint(table, out);
+    self.last_row = write_index(row, self.last_row, out);
+    self.last_column = write_index(column, self.last_column, out);
+    match value {
+      Value::Number(quantity) => {
+        let meta = quantity >> MANTISSA_BITS;
+        let mantissa = zigzag(sign_extend_mantissa(*quantity));
+        write_varint(mantissa << 1 | (meta != 0) as u64, out);
+        if meta != 0 {
+          write_varint(meta, out);
+        }
+      },
+      Value::String(string) => {
+        let id = self.intern(string);
+        write_varint(id, out);
+      },
+      Value::Reference(id) => write_varint(*id, out),
+      Value::Bool(_) |
+      Value::Empty => (),
+    }
+  }
+
+}
+
+// Aliases are hashes, so they are written as they are. Indices are written as
+// a delta from the previous index, returning the new base.
+fn write_index(index: &Index, last: u64, out: &mut Vec<u8>) -> u64 {
+  match index {
+    Index::Alias(alias) => {
+      write_varint(*alias, out);
+      last
+    },
+    Index::Index(ix) => {
+      write_varint(zigzag(ix.wrapping_sub(last) as i64), out);
+      *ix
+    },
+  }
+}
+
+// ## Decoder
+
+struct Decoder<'a> {
+  bytes: &'a [u8],
+  position: usize,
+  last_row: u64,
+  last_column: u64,
+}
+
+impl<'a> Decoder<'a> {
+
+  fn new(bytes: &'a [u8]) -> Decoder<'a> {
+    Decoder {
+      bytes,
+      position: 0,
+      last_row: 0,
+      last_column: 0,
+    }
+  }
+
+  fn malformed(&self) -> ErrorType {
+    ErrorType::MalformedWireData(self.position)
+  }
+
+  fn read_byte(&mut self) -> Result<u8, ErrorType> {
+    match self.bytes.get(self.position) {
+      Some(byte) => {
+        self.position += 1;
+        Ok(*byte)
+      },
+      None => Err(self.malformed()),
+    }
+  }
+
+  fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], ErrorType> {
+    if length > self.bytes.len() - self.position {
+      return Err(self.malformed());
+    }
+    let bytes = &self.bytes[self.position..self.position + length];
+    self.position += length;
+    Ok(bytes)
+  }
+
+  fn read_varint(&mut self) -> Result<u64, ErrorType> {
+    let start = self.position;
+    let mut result: u64 = 0;
+    let mut shift = 0;
+    loop {
+      let byte = self.read_byte()?;
+      let bits = (byte & 0x7f) as u64;
+      // The tenth byte may only carry the single remaining bit
+      if shift == 63 && bits > 1 {
+        return Err(ErrorType::MalformedWireData(start));
+      }
+      result |= bits << shift;
+      if byte & 0x80 == 0 {
+        return Ok(result);
+      }
+      shift += 7;
+      if shift > 63 {
+        return Err(ErrorType::MalformedWireData(start));
+      }
+    }
+  }
+
+  // Counts are bounded by the bytes left in the message, since every element
+  // takes at least one byte. This keeps hostile input from making us allocate.
+  fn read_count(&mut self) -> Result<usize, ErrorType> {
+    let start = self.position;
+    let count = self.read_varint()?;
+    if count > (self.bytes.len() - self.position) as u64 {
+      return Err(ErrorType::MalformedWireData(start));
+    }
+    Ok(count as usize)
+  }
+
+  fn read_index(&mut self, alias: bool, last: u64) -> Result<(Index, u64), ErrorType> {
+    let raw = self.read_varint()?;
+    if alias {
+      Ok((Index::Alias(raw), last))
+    } else {
+      let ix = last.wrapping_add(unzigzag(raw) as u64);
+      Ok((Index::Index(ix), ix))
+    }
+  }
+
+  fn decode_section(&mut self, strings: &Vec<Symbol>) -> Result<Vec<Change>, ErrorType> {
+    self.last_row = 0;
+    self.last_column = 0;
+    let count = self.read_count()?;
+    let mut changes = Vec::with_capacity(count);
+    for _ in 0..count {
+      changes.push(self.decode_change(strings)?);
+    }
+    Ok(changes)
+  }
+
+  fn decode_change(&mut self, strings: &Vec<Symbol>) -> Result<Change, ErrorType> {
+    let start = self.position;
+    let tag = self.read_byte()?;
+    let kind = tag & KIND_MASK;
+    match kind {
+      SET | REMOVE => {
+        let table = self.read_varint()?;
+        let (row, last_row) = self.read_index(tag & ROW_ALIAS != 0, self.last_row)?;
+        self.last_row = last_row;
+        let (column, last_column) = self.read_index(tag & COLUMN_ALIAS != 0, self.last_column)?;
+        self.last_column = last_column;
+        let value = match tag >> VALUE_SHIFT {
+          NUMBER => {
+            let packed = self.read_varint()?;
+            let mantissa = unzigzag(packed >> 1);
+            let limit = 1 << (MANTISSA_BITS - 1);
+            if !(-limit..limit).contains(&mantissa) {
+              return Err(ErrorType::MalformedWireData(start));
+            }
+            let meta = if packed & 1 == 1 { self.read_varint()? } else { 0 };
+            if meta == 0 && packed & 1 == 1 || meta >> (64 - MANTISSA_BITS) != 0 {
+              return Err(ErrorType::MalformedWireData(start));
+            }
+            Value::Number(meta << MANTISSA_BITS | (mantissa as u64 & MANTISSA_MASK))
+          },
+          STRING => {
+            let id = self.read_varint()?;
+            if id >= strings.len() as u64 {
+              return Err(ErrorType::MalformedWireData(start));
+            }
+            Value::String(strings[id as usize].clone())
+          },
+          FALSE => Value::Bool(false),
+          TRUE => Value::Bool(true),
+          REFERENCE => Value::Reference(self.read_varint()?),
+          EMPTY => Value::Empty,
+          _ => return Err(ErrorType::MalformedWireData(start)),
+        };
+        if kind == SET {
+          Ok(Change::Set{table, row, column, value})
+        } else {
+          Ok(Change::Remove{table, row, column, value})
+        }
+      },
+      _ if tag & !KIND_MASK != 0 => Err(ErrorType::MalformedWireData(start)),
+      NEW_TABLE => Ok(Change::NewTable{id: self.read_varint()?, rows: self.read_varint()?, columns: self.read_varint()?}),
+      RENAME_COLUMN => Ok(Change::RenameColumn{table: self.read_varint()?, column_ix: self.read_varint()?, column_alias: self.read_varint()?}),
+      REMOVE_TABLE => {
+        let (id, rows, columns) = (self.read_varint()?, self.read_varint()?, self.read_varint()?);
+        let count = self.read_count()?;
+        let mut contents = Vec::with_capacity(count);
+        for _ in 0..count {
+          let start = self.position;
+          match self.bytes.get(start).map(|tag| tag & KIND_MASK) {
+            Some(SET) | Some(RENAME_COLUMN) => (),
+            _ => return Err(ErrorType::MalformedWireData(start)),
+          }
+          let change = self.decode_change(strings)?;
+          if !fills_table(&change) {
+            return Err(ErrorType::MalformedWireData(start));
+          }
+          contents.push(change);
+        }
+        Ok(Change::RemoveTable{id, rows, columns, contents})
+      },
+      _ => Err(ErrorType::MalformedWireData(start)),
+    }
+  }
+
+}
+
+// ## Utility
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+  while value >= 0x80 {
+    out.push((value as u8) | 0x80);
+    value >>= 7;
+  }
+  out.push(value as u8);
+}
+
+fn zigzag(value: i64) -> u64 {
+  ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+  ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn sign_extend_mantissa(quantity: u64) -> i64 {
+  ((quantity << (64 - MANTISSA_BITS)) as i64) >> (64 - MANTISSA_BITS)
+}
@@ -0,0 +1,191 @@
+extern crate mech_core;
+extern crate bincode;
+#[macro_use]
+extern crate proptest;
+
+use mech_core::{Core, Transaction, Change, Value, Index, Hasher, ErrorType, make_quantity};
+use mech_core::{Clock, LamportClock, HybridClock, Stamp, Stamped, Version, MergePolicy, Reconciler};
+use proptest::prelude::*;
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+struct Site {
+  core: Core,
+  clock: LamportClock,
+  reconciler: Reconciler,
+}
+
+fn make_site(site: u64) -> Site {
+  let mut core = Core::new(1000, 10);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: Hasher::hash_str("lww"), rows: 2, columns: 2},
+    Change::NewTable{id: Hasher::hash_str("set"), rows: 1, columns: 1},
+    Change::NewTable{id: Hasher::hash_str("count"), rows: 1, columns: 1},
+    Change::NewTable{id: Hasher::hash_str("max"), rows: 1, columns: 1},
+  ]));
+  let mut reconciler = Reconciler::new();
+  reconciler.set_policy(Hasher::hash_str("lww"), MergePolicy::LastWriterWins);
+  reconciler.set_policy(Hasher::hash_str("set"), MergePolicy::GrowOnlySet);
+  reconciler.set_policy(Hasher::hash_str("count"), MergePolicy::Counter);
+  reconciler.set_policy(Hasher::hash_str("max"), MergePolicy::Custom(Box::new(|current: &Version, incoming: &Version| {
+    match (current.value.as_u64(), incoming.value.as_u64()) {
+      (Some(a), Some(b)) => Value::from_u64(std::cmp::max(a, b)),
+      (Some(_), None) => current.value.clone(),
+      _ => incoming.value.clone(),
+    }
+  })));
+  Site{core, clock: LamportClock::new(site), reconciler}
+}
+
+impl Site {
+
+  fn write(&mut self, changes: Vec<Change>) -> Vec<Stamped> {
+    let writes = self.reconciler.stamp(&mut self.clock, changes);
+    let resolved = self.reconciler.resolve(&writes);
+    self.core.process_changes(resolved);
+    writes
+  }
+
+  fn receive(&mut self, writes: &[Stamped]) {
+    let resolved = self.reconciler.receive(&mut self.clock, writes);
+    self.core.process_changes(resolved);
+  }
+
+  fn tables(&self) -> Vec<u8> {
+    bincode::serialize(&self.core.store.tables).unwrap()
+  }
+
+}
+
+fn value(site: &Site, table: &str, row: u64, column: u64) -> Value {
+  site.core.store.get_table(Hasher::hash_str(table)).unwrap().data[column as usize - 1].value(row as usize - 1)
+}
+
+#[test]
+fn last_writer_wins() {
+  let lww = Hasher::hash_str("lww");
+  let mut a = make_site(1);
+  let mut b = make_site(2);
+  let from_a = a.write(vec![set(lww, 1, 1, Value::from_u64(1))]);
+  let from_b = b.write(vec![set(lww, 1, 1, Value::from_u64(2))]);
+  a.receive(&from_b);
+  b.receive(&from_a);
+  // Both stamps have time 1, so the tie goes to the higher site
+  assert_eq!(value(&a, "lww", 1, 1), Value::from_u64(2));
+  assert_eq!(a.tables(), b.tables());
+  // A write made after seeing the other site's wins
+  let from_a = a.write(vec![Change::Remove{table: lww, row: Index::Index(1), column: Index::Index(1), value: Value::Empty}]);
+  b.receive(&from_a);
+  assert_eq!(value(&b, "lww", 1, 1), Value::Empty);
+}
+
+#[test]
+fn counters_add_up_concurrent_writes() {
+  let count = Hasher::hash_str("count");
+  let mut a = make_site(1);
+  let mut b = make_site(2);
+  let from_a = a.write(vec![set(count, 1, 1, Value::from_u64(2)), set(count, 1, 1, Value::from_u64(3))]);
+  let from_b = b.write(vec![set(count, 1, 1, Value::from_i64(-1))]);
+  a.receive(&from_b);
+  b.receive(&from_a);
+  // Delivering a write twice doesn't count it twice
+  b.receive(&from_a);
+  assert_eq!(value(&a, "count", 1, 1).as_i64(), Some(4));
+  assert_eq!(a.tables(), b.tables());
+}
+
+#[test]
+fn writes_are_counted_once_however_late_they_arrive() {
+  let count = Hasher::hash_str("count");
+  let mut a = make_site(1);
+  let mut b = make_site(2);
+  let from_a = a.write((0..2000).map(|_| set(count, 1, 1, Value::from_u64(1))).collect());
+  // Newest first, so the oldest writes turn up long after the rest
+  for write in from_a.iter().rev() {
+    b.receive(&[write.clone()]);
+  }
+  b.receive(&from_a);
+  assert_eq!(value(&b, "count", 1, 1).as_u64(), Some(2000));
+  assert_eq!(a.tables(), b.tables());
+}
+
+#[test]
+fn counters_report_writes_they_cant_add() {
+  let count = Hasher::hash_str("count");
+  let mut a = make_site(1);
+  a.write(vec![set(count, 1, 1, Value::from_u64(2))]);
+  a.write(vec![set(count, 1, 1, Value::from_quantity(make_quantity(1, 0, 1)))]);
+  assert_eq!(value(&a, "count", 1, 1).as_u64(), Some(2));
+  assert_eq!(a.reconciler.errors, vec![ErrorType::DomainMismatch(0, 1)]);
+}
+
+#[test]
+fn sets_grow_in_a_fixed_order() {
+  let members = Hasher::hash_str("set");
+  let mut a = make_site(1);
+  let mut b = make_site(2);
+  let from_a = a.write(vec![set(members, 1, 1, Value::from_str("pear")), set(members, 1, 1, Value::from_str("apple"))]);
+  let from_b = b.write(vec![set(members, 1, 1, Value::from_str("fig")), set(members, 1, 1, Value::from_str("apple"))]);
+  a.receive(&from_b);
+  b.receive(&from_a);
+  assert_eq!(a.tables(), b.tables());
+  assert_eq!(a.core.store.get_table(members).unwrap().data[0].to_values(), vec![
+    Value::from_str("apple"), Value::from_str("fig"), Value::from_str("pear"),
+  ]);
+}
+
+#[test]
+fn hybrid_clocks_follow_physical_time_but_never_go_back() {
+  let mut readings = vec![5, 3, 3].into_iter();
+  let mut clock = HybridClock::new(1, Box::new(move || readings.next().unwrap()));
+  let first = clock.stamp();
+  assert_eq!(first, Stamp{time: 5 << 16, site: 1});
+  assert!(clock.stamp() > first);
+  clock.observe(&Stamp{time: 9 << 16, site: 2});
+  assert!(clock.stamp() > Stamp{time: 9 << 16, site: 2});
+}
+
+fn write_strategy() -> impl Strategy<Value = (usize, &'static str, u64, u64, u64)> {
+  (0..3usize, prop_oneof![Just("lww"), Just("set"), Just("count"), Just("max")], 1..3u64, 1..3u64, 0..4u64)
+}
+
+proptest! {
+
+  // However the writes from three sites are delivered, every site ends up
+  // with the same tables.
+  #[test]
+  fn sites_converge(writes in proptest::collection::vec(write_strategy(), 1..24),
+                    orders in proptest::collection::vec(Just(()).prop_perturb(|_, mut rng| rng.next_u64()), 3)) {
+    let mut sites: Vec<Site> = (1..4).map(make_site).collect();
+    let mut outboxes: Vec<Vec<Stamped>> = vec![Vec::new(), Vec::new(), Vec::new()];
+    for (site, table, row, column, number) in writes {
+      let table = Hasher::hash_str(table);
+      let change = match number {
+        0 => Change::Remove{table, row: Index::Index(row), column: Index::Index(column), value: Value::Empty},
+        number => set(table, row, column, Value::from_u64(number)),
+      };
+      let stamped = sites[site].write(vec![change]);
+      outboxes[site].extend(stamped);
+    }
+    for (ix, site) in sites.iter_mut().enumerate() {
+      let mut incoming: Vec<Stamped> = outboxes.iter().enumerate()
+        .filter(|(from, _)| *from != ix)
+        .flat_map(|(_, writes)| writes.iter().cloned())
+        .collect();
+      // Shuffle the delivery order
+      let mut seed = orders[ix];
+      for i in (1..incoming.len()).rev() {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        incoming.swap(i, (seed >> 33) as usize % (i + 1));
+      }
+      for write in incoming {
+        site.receive(&[write]);
+      }
+    }
+    prop_assert_eq!(sites[0].tables(), sites[1].tables());
+    prop_assert_eq!(sites[1].tables(), sites[2].tables());
+  }
+
+}
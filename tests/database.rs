@@ -1,10 +1,403 @@
 extern crate mech_core;
+extern crate bincode;
 
 use mech_core::Hasher;
-use mech_core::{Core, Transaction, Change};
+use mech_core::{Core, Transaction, Change, Value, Index, Table, TableId};
+use mech_core::{Block, Constraint, Function, make_quantity};
+use mech_core::{ErrorType, MemoryArchive};
 
 #[test]
 fn create_database() {
     let db = Core::new(1,1);
     assert_eq!("", "");
-}
\ No newline at end of file
+}
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+fn snapshot(core: &Core) -> Vec<u8> {
+  bincode::serialize(&core.store.tables).unwrap()
+}
+
+#[test]
+fn history_holds_as_many_changes_as_asked_for() {
+  let mut core = Core::new(3, 10);
+  let table = Hasher::hash_str("table");
+  // However much room the log happens to have, only three changes are kept
+  core.store.changes.reserve(100);
+  core.process_transaction(&Transaction::from_change(Change::NewTable{id: table, rows: 1, columns: 1}));
+  for n in 1..6 {
+    core.process_transaction(&Transaction::from_change(set(table, 1, 1, Value::from_u64(n))));
+  }
+  assert_eq!(core.store.changes.len(), 3);
+  assert_eq!(core.store.oldest_change(), core.store.changes_count - 3);
+  assert_eq!(core.store.get_change(core.store.changes_count - 1).unwrap().0, set(table, 1, 1, Value::from_u64(5)));
+}
+
+// Stepping through time should visit exactly the states the store was in.
+#[test]
+fn time_travel_reproduces_every_state() {
+  let mut core = Core::new(100, 10);
+  let students = Hasher::hash_str("students");
+  let scores = Hasher::hash_str("scores");
+  let name = Hasher::hash_str("name");
+  let transactions = vec![
+    vec![Change::NewTable{id: students, rows: 1, columns: 1}],
+    vec![Change::RenameColumn{table: students, column_ix: 1, column_alias: name},
+         set(students, 1, 1, Value::from_str("Mark"))],
+    vec![set(students, 1, 1, Value::from_str("Sabra")),
+         set(students, 3, 2, Value::from_u64(99))],
+    vec![Change::NewTable{id: scores, rows: 1, columns: 1},
+         set(scores, 1, 1, Value::Bool(true))],
+    vec![Change::Remove{table: students, row: Index::Index(3), column: Index::Index(2), value: Value::Empty}],
+    vec![Change::RemoveTable{id: students, rows: 0, columns: 0, contents: Vec::new()}],
+  ];
+  let mut states = vec![snapshot(&core)];
+  for changes in transactions {
+    core.process_transaction(&Transaction::from_changeset(changes));
+    states.push(snapshot(&core));
+  }
+  let now = states.len() - 1;
+  for time in 1..states.len() {
+    core.step_backward(1).unwrap();
+    assert_eq!(core.offset, time);
+    assert_eq!(snapshot(&core), states[now - time]);
+  }
+  // Stepping past the beginning of time stays at the beginning
+  core.step_backward(1).unwrap();
+  assert_eq!(core.offset, now);
+  assert_eq!(snapshot(&core), states[0]);
+  for time in (0..now).rev() {
+    core.step_forward(1).unwrap();
+    assert_eq!(core.offset, time);
+    assert_eq!(snapshot(&core), states[now - time]);
+  }
+}
+
+#[test]
+fn time_travel_recreates_removed_tables() {
+  let mut core = Core::new(100, 10);
+  let students = Hasher::hash_str("students");
+  let name = Hasher::hash_str("name");
+  let mut table = Table::new(students, 2, 1);
+  table.set_column_alias(name, 1);
+  table.set_cell(&Index::Index(1), &Index::Alias(name), Value::from_str("Mark"));
+  table.set_cell(&Index::Index(2), &Index::Alias(name), Value::from_str("Sabra"));
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: students, rows: 2, columns: 1},
+    Change::RenameColumn{table: students, column_ix: 1, column_alias: name},
+    set(students, 1, 1, Value::from_str("Mark")),
+    set(students, 2, 1, Value::from_str("Sabra")),
+  ]));
+  core.process_transaction(&Transaction::from_change(Change::RemoveTable{id: students, rows: 0, columns: 0, contents: Vec::new()}));
+  assert_eq!(core.store.get_table(students), None);
+  core.step_backward(1).unwrap();
+  assert_eq!(core.store.get_table(students), Some(&table));
+  core.resume().unwrap();
+  assert_eq!(core.store.get_table(students), None);
+}
+
+// The logged change holds the whole table, so another core can put it back
+// from the change alone
+#[test]
+fn removed_tables_travel_with_their_contents() {
+  let mut core = Core::new(100, 10);
+  let students = Hasher::hash_str("students");
+  let name = Hasher::hash_str("name");
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: students, rows: 2, columns: 2},
+    Change::RenameColumn{table: students, column_ix: 1, column_alias: name},
+    set(students, 1, 1, Value::from_str("Mark")),
+    set(students, 2, 2, Value::from_u64(99)),
+  ]));
+  let table = core.store.get_table(students).unwrap().clone();
+  core.process_transaction(&Transaction::from_change(Change::RemoveTable{id: students, rows: 0, columns: 0, contents: Vec::new()}));
+  let (removed, undo) = core.store.get_change(core.store.changes_count - 1).unwrap();
+  assert_eq!(undo, None);
+  let sent = Transaction::decode(&Transaction::from_change(removed).encode()).unwrap();
+  let mut other = Core::new(100, 10);
+  other.store.revert_change(&sent.tables[0], None);
+  assert_eq!(other.store.get_table(students), Some(&table));
+}
+
+// Each transaction after the first overwrites one cell, which saves a Remove of
+// the old value and a Set of the new one.
+fn count_to(core: &mut Core, table: u64, n: u64) -> Vec<Vec<u8>> {
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: table, rows: 1, columns: 1},
+    set(table, 1, 1, Value::from_u64(0)),
+  ]));
+  let mut states = vec![snapshot(core)];
+  for i in 1..n + 1 {
+    core.process_transaction(&Transaction::from_change(set(table, 1, 1, Value::from_u64(i))));
+    states.push(snapshot(core));
+  }
+  states
+}
+
+#[test]
+fn time_travel_across_rollover() {
+  let mut core = Core::new(7, 1);
+  let x = Hasher::hash_str("x");
+  let states = count_to(&mut core, x, 10);
+  assert!(core.store.rollover > 0);
+  // 7 slots hold the last three transactions, plus one change of a fourth
+  for time in 1..4 {
+    core.step_backward(1).unwrap();
+    assert_eq!(snapshot(&core), states[10 - time]);
+  }
+  assert_eq!(core.step_backward(1), Err(ErrorType::TimeNotRetained(4)));
+  assert_eq!(core.offset, 3);
+  assert_eq!(snapshot(&core), states[7]);
+  core.resume().unwrap();
+  assert_eq!(snapshot(&core), states[10]);
+  assert_eq!(core.set_time(9), Err(ErrorType::TimeNotRetained(4)));
+}
+
+#[test]
+fn time_travel_into_the_archive() {
+  let mut core = Core::new(7, 1);
+  core.store.archive = Some(Box::new(MemoryArchive::new()));
+  let x = Hasher::hash_str("x");
+  let states = count_to(&mut core, x, 10);
+  core.set_time(10).unwrap();
+  assert_eq!(snapshot(&core), states[0]);
+  core.step_backward(1).unwrap();
+  assert_eq!(core.store.get_table(x), None);
+  for time in (0..11).rev() {
+    core.step_forward(1).unwrap();
+    assert_eq!(snapshot(&core), states[10 - time]);
+  }
+}
+
+// #y = #x * 2
+fn make_doubling_block(x: u64, y: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+#[test]
+fn time_travel_restores_block_memory() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  core.register_blocks(vec![make_doubling_block(x, y)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    set(x, 1, 1, Value::from_u64(1)),
+    set(y, 1, 1, Value::from_u64(0)),
+  ]));
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(5))));
+  let block_id = *core.runtime.blocks.keys().next().unwrap();
+  let doubled = |core: &Core| core.runtime.blocks[&block_id].get_table(3).unwrap().data[0].value(0);
+  assert_eq!(core.store.get_table(y).unwrap().data[0].value(0), Value::from_u64(10));
+  assert_eq!(doubled(&core), Value::from_u64(10));
+  core.step_backward(1).unwrap();
+  assert_eq!(core.store.get_table(y).unwrap().data[0].value(0), Value::from_u64(2));
+  assert_eq!(doubled(&core), Value::from_u64(2));
+  core.step_forward(1).unwrap();
+  assert_eq!(doubled(&core), Value::from_u64(10));
+}
+
+fn x_value(core: &Core, x: u64) -> Value {
+  core.store.get_table(x).unwrap().data[0].value(0)
+}
+
+// A fork can go its own way without disturbing the core it came from.
+#[test]
+fn fork_explores_an_alternate_timeline() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let states = count_to(&mut core, x, 5);
+  let mut branch = core.fork(2).unwrap();
+  assert_eq!(snapshot(&branch), states[3]);
+  assert_eq!(branch.offset, 0);
+  assert_eq!(branch.fork_point, Some(core.store.changes_count - 4));
+  branch.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(42))));
+  assert_eq!(x_value(&branch, x), Value::from_u64(42));
+  assert_eq!(snapshot(&core), states[5]);
+  // The branch's history leads back through the fork, not through the future
+  // it left behind.
+  branch.step_backward(1).unwrap();
+  assert_eq!(snapshot(&branch), states[3]);
+  branch.step_backward(1).unwrap();
+  assert_eq!(snapshot(&branch), states[2]);
+  branch.resume().unwrap();
+  assert_eq!(x_value(&branch, x), Value::from_u64(42));
+  assert_eq!(branch.branch_changes().unwrap(), vec![
+    Change::Remove{table: x, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(3)},
+    set(x, 1, 1, Value::from_u64(42)),
+  ]);
+}
+
+#[test]
+fn fork_runs_blocks() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  core.register_blocks(vec![make_doubling_block(x, y)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    set(x, 1, 1, Value::from_u64(1)),
+    set(y, 1, 1, Value::from_u64(0)),
+  ]));
+  let mut branch = core.fork(0).unwrap();
+  branch.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(21))));
+  assert_eq!(x_value(&branch, y), Value::from_u64(42));
+  assert_eq!(x_value(&core, y), Value::from_u64(2));
+}
+
+#[test]
+fn fork_beyond_retained_history_fails() {
+  let mut core = Core::new(7, 1);
+  let x = Hasher::hash_str("x");
+  count_to(&mut core, x, 10);
+  assert_eq!(core.fork(4).err(), Some(ErrorType::TimeNotRetained(4)));
+  // The ring of a fork keeps working after it's been truncated
+  let mut branch = core.fork(3).unwrap();
+  let states = count_to(&mut branch, x, 10);
+  branch.step_backward(3).unwrap();
+  assert_eq!(snapshot(&branch), states[7]);
+}
+
+#[test]
+fn merge_applies_a_branch() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  count_to(&mut core, x, 2);
+  let mut branch = core.fork(0).unwrap();
+  branch.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(7))));
+  branch.process_transaction(&Transaction::from_change(
+    Change::Remove{table: x, row: Index::Index(1), column: Index::Index(1), value: Value::Empty}
+  ));
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(3))));
+  core.merge(&branch).unwrap();
+  assert_eq!(x_value(&core, x), Value::Empty);
+  // The merge is a single transaction
+  core.step_backward(1).unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(3));
+}
+
+#[test]
+fn input_waits_while_paused() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  count_to(&mut core, x, 2);
+  core.pause();
+  core.step_backward(1).unwrap();
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(9))));
+  assert_eq!(x_value(&core, x), Value::from_u64(1));
+  core.resume().unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(9));
+  core.step_backward(1).unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(2));
+}
+
+#[test]
+fn input_waits_while_rewound() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  count_to(&mut core, x, 2);
+  core.step_backward(1).unwrap();
+  let before = x_value(&core, x);
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(9))));
+  assert_eq!(x_value(&core, x), before);
+  // Back at now, the input goes in after everything that already happened
+  core.set_time(0).unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(9));
+  core.step_backward(1).unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(2));
+  core.step_forward(1).unwrap();
+  assert_eq!(x_value(&core, x), Value::from_u64(9));
+}
+
+#[test]
+fn merge_replays_only_input() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  core.register_blocks(vec![make_doubling_block(x, y)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    set(x, 1, 1, Value::from_u64(1)),
+    set(y, 1, 1, Value::from_u64(0)),
+  ]));
+  let mut branch = core.fork(0).unwrap();
+  branch.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(21))));
+  assert_eq!(branch.branch_inputs().unwrap(), vec![vec![set(x, 1, 1, Value::from_u64(21))]]);
+  core.merge(&branch).unwrap();
+  assert_eq!(x_value(&core, y), Value::from_u64(42));
+}
+
+// Views reconstruct the past without moving the core through time.
+#[test]
+fn view_at_shows_the_past_without_rewinding() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let states = count_to(&mut core, x, 5);
+  for time in 0..6 {
+    let view = core.view_at(time).unwrap();
+    assert_eq!(view.index(x, &Index::Index(1), &Index::Index(1)), Some(Value::from_u64(5 - time as u64)));
+    assert_eq!(bincode::serialize(&view.tables).unwrap(), states[5 - time]);
+  }
+  assert_eq!(core.offset, 0);
+  assert_eq!(snapshot(&core), states[5]);
+  // Before the first transaction there was nothing
+  let view = core.view_at(100).unwrap();
+  assert_eq!(view.time, 6);
+  assert_eq!(view.get_table(x), None);
+  // Views are relative to now even while the core is rewound
+  core.step_backward(3).unwrap();
+  let view = core.view_at(1).unwrap();
+  assert_eq!(bincode::serialize(&view.tables).unwrap(), states[4]);
+  assert_eq!(view.column(x, &Index::Index(1)).unwrap().collect::<Vec<Value>>(), vec![Value::from_u64(4)]);
+  assert_eq!(snapshot(&core), states[2]);
+}
+
+#[test]
+fn view_at_needs_retained_history() {
+  let mut core = Core::new(7, 1);
+  let x = Hasher::hash_str("x");
+  count_to(&mut core, x, 10);
+  assert!(core.view_at(3).is_ok());
+  assert_eq!(core.view_at(4).err(), Some(ErrorType::TimeNotRetained(4)));
+}
+
+#[test]
+fn diff_since_catches_up_an_old_state() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  count_to(&mut core, x, 3);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    set(y, 2, 1, Value::from_str("new")),
+    Change::Remove{table: x, row: Index::Index(1), column: Index::Index(1), value: Value::Empty},
+  ]));
+  assert_eq!(core.diff_since(0).unwrap(), Transaction::new());
+  let diff = core.diff_since(2).unwrap();
+  assert_eq!(diff, Transaction::from_changeset(vec![
+    Change::NewTable{id: y, rows: 2, columns: 1},
+    set(y, 2, 1, Value::from_str("new")),
+    Change::Remove{table: x, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(2)},
+  ]));
+  let mut branch = core.fork(2).unwrap();
+  branch.process_transaction(&diff);
+  assert_eq!(snapshot(&branch), snapshot(&core));
+}
@@ -0,0 +1,82 @@
+extern crate mech_core;
+
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, Hasher, ErrorType, Register};
+use mech_core::{Block, Constraint, Function, make_quantity};
+use mech_core::MockMachine;
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+// #y = #x * 2
+fn make_doubling_block(x: u64, y: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+#[test]
+fn machines_drive_blocks_and_receive_their_output() {
+  let sensor = Hasher::hash_str("sensor");
+  let motor = Hasher::hash_str("motor");
+  let mut core = Core::new(100, 10);
+  core.register_blocks(vec![make_doubling_block(sensor, motor)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: sensor, rows: 1, columns: 1},
+    Change::NewTable{id: motor, rows: 1, columns: 1},
+    set(motor, 1, 1, Value::from_u64(0)),
+  ]));
+  let machine = MockMachine::new(vec![sensor], vec![Register::new(motor, Index::Index(0))]);
+  core.register_machine(Box::new(machine.clone())).unwrap();
+  assert_eq!(core.poll_machines(), Ok(0));
+  machine.feed(set(sensor, 1, 1, Value::from_u64(3)));
+  assert_eq!(core.poll_machines(), Ok(1));
+  let received = machine.take_received();
+  assert_eq!(received.len(), 1);
+  assert_eq!(received[0].0, Register::new(motor, Index::Index(0)));
+  assert_eq!(received[0].1.data[0].value(0), Value::from_u64(6));
+  // Steps that don't touch the output leave the machine alone
+  core.process_transaction(&Transaction::from_change(Change::NewTable{id: 99, rows: 1, columns: 1}));
+  assert!(machine.take_received().is_empty());
+}
+
+#[test]
+fn column_outputs_only_fire_for_their_column() {
+  let display = Hasher::hash_str("display");
+  let text = Hasher::hash_str("text");
+  let mut core = Core::new(100, 10);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: display, rows: 1, columns: 2},
+    Change::RenameColumn{table: display, column_ix: 2, column_alias: text},
+  ]));
+  let machine = MockMachine::new(vec![], vec![Register::new(display, Index::Alias(text))]);
+  core.register_machine(Box::new(machine.clone())).unwrap();
+  core.process_transaction(&Transaction::from_change(set(display, 1, 1, Value::from_u64(1))));
+  assert!(machine.take_received().is_empty());
+  core.process_transaction(&Transaction::from_change(set(display, 1, 2, Value::from_str("hi"))));
+  assert_eq!(machine.take_received().len(), 1);
+}
+
+#[test]
+fn machines_keep_to_their_own_tables() {
+  let keyboard = Hasher::hash_str("keyboard");
+  let mut core = Core::new(100, 10);
+  let first = MockMachine::new(vec![keyboard], vec![]);
+  core.register_machine(Box::new(first.clone())).unwrap();
+  let second = MockMachine::new(vec![keyboard], vec![]);
+  assert_eq!(core.register_machine(Box::new(second)), Err(ErrorType::DuplicateProvider(keyboard)));
+  first.feed(Change::NewTable{id: 7, rows: 1, columns: 1});
+  assert_eq!(core.poll_machines(), Err(ErrorType::NotProvided(7)));
+  assert_eq!(core.store.get_table(7), None);
+}
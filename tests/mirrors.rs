@@ -0,0 +1,187 @@
+extern crate mech_core;
+
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, Hasher, ErrorType};
+use mech_core::{Block, Constraint, Function, make_quantity};
+use mech_core::{MemoryTransport, MirrorMessage, MirrorServer, Mirrors, Transport};
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+// #y = #x * 2
+fn make_doubling_block(x: u64, y: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+fn value(core: &Core, table: u64) -> Value {
+  core.store.get_table(table).unwrap().data[0].value(0)
+}
+
+#[test]
+fn blocks_run_on_mirrored_tables() {
+  let sensor = Hasher::hash_str("sensor");
+  let readings = Hasher::hash_str("readings");
+  let latest = Hasher::hash_str("latest");
+  let doubled = Hasher::hash_str("doubled");
+  let mut remote = Core::new(100, 10);
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: readings, rows: 1, columns: 1},
+    set(readings, 1, 1, Value::from_u64(4)),
+  ]));
+  let (server_end, client_end) = MemoryTransport::pair();
+  let mut server = MirrorServer::new(server_end);
+  let mut local = Core::new(100, 10);
+  local.register_blocks(vec![make_doubling_block(latest, doubled)]);
+  local.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: doubled, rows: 1, columns: 1},
+    set(doubled, 1, 1, Value::from_u64(0)),
+  ]));
+  let mut mirrors = Mirrors::new();
+  mirrors.add_remote(sensor, client_end);
+  mirrors.mirror(latest, sensor, readings).unwrap();
+  server.serve(&remote);
+  mirrors.sync(&mut local);
+  assert_eq!(value(&local, latest), Value::from_u64(4));
+  assert_eq!(value(&local, doubled), Value::from_u64(8));
+  // Changes to other tables on the remote stay there
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    set(readings, 1, 1, Value::from_u64(5)),
+    Change::NewTable{id: doubled, rows: 1, columns: 1},
+  ]));
+  server.serve(&remote);
+  assert_eq!(mirrors.sync(&mut local), 2);
+  assert_eq!(value(&local, doubled), Value::from_u64(10));
+  // After unmirroring, updates stop
+  mirrors.unmirror(latest);
+  server.serve(&remote);
+  remote.process_transaction(&Transaction::from_change(set(readings, 1, 1, Value::from_u64(6))));
+  server.serve(&remote);
+  assert_eq!(mirrors.sync(&mut local), 0);
+  assert_eq!(value(&local, latest), Value::from_u64(5));
+}
+
+#[test]
+fn mirrors_start_over_when_history_is_gone() {
+  let sensor = Hasher::hash_str("sensor");
+  let readings = Hasher::hash_str("readings");
+  let mut remote = Core::new(2, 10);
+  remote.process_transaction(&Transaction::from_change(Change::NewTable{id: readings, rows: 1, columns: 1}));
+  let (server_end, client_end) = MemoryTransport::pair();
+  let mut server = MirrorServer::new(server_end);
+  let mut local = Core::new(100, 10);
+  let mut mirrors = Mirrors::new();
+  mirrors.add_remote(sensor, client_end);
+  mirrors.mirror(readings, sensor, readings).unwrap();
+  server.serve(&remote);
+  for i in 1..4 {
+    remote.process_transaction(&Transaction::from_change(set(readings, 1, 1, Value::from_u64(i))));
+  }
+  server.serve(&remote);
+  mirrors.sync(&mut local);
+  assert_eq!(local.store.get_table(readings), remote.store.get_table(readings));
+}
+
+#[test]
+fn mirrors_take_the_shape_of_each_snapshot() {
+  let sensor = Hasher::hash_str("sensor");
+  let readings = Hasher::hash_str("readings");
+  let mut remote = Core::new(100, 10);
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: readings, rows: 3, columns: 2},
+    set(readings, 3, 2, Value::from_u64(5)),
+  ]));
+  let wide = remote.store.get_table(readings).unwrap().clone();
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    Change::RemoveTable{id: readings, rows: 3, columns: 2, contents: vec![]},
+    Change::NewTable{id: readings, rows: 1, columns: 1},
+    set(readings, 1, 1, Value::from_u64(7)),
+  ]));
+  let narrow = remote.store.get_table(readings).unwrap().clone();
+  let (mut server_end, client_end) = MemoryTransport::pair();
+  let mut local = Core::new(100, 10);
+  let mut mirrors = Mirrors::new();
+  mirrors.add_remote(sensor, client_end);
+  mirrors.mirror(readings, sensor, readings).unwrap();
+  server_end.send(MirrorMessage::Snapshot{table: readings, contents: Some(Box::new(wide))});
+  server_end.send(MirrorMessage::Changes{table: readings, changes: vec![set(readings, 1, 1, Value::from_u64(6))]});
+  server_end.send(MirrorMessage::Snapshot{table: readings, contents: Some(Box::new(narrow))});
+  mirrors.sync(&mut local);
+  assert_eq!(local.store.get_table(readings), remote.store.get_table(readings));
+  server_end.send(MirrorMessage::Snapshot{table: readings, contents: None});
+  mirrors.sync(&mut local);
+  assert_eq!(local.store.get_table(readings), None);
+}
+
+#[test]
+fn mirroring_needs_a_known_remote() {
+  let mut mirrors: Mirrors<MemoryTransport<MirrorMessage>> = Mirrors::new();
+  assert_eq!(mirrors.mirror(1, 2, 3), Err(ErrorType::UnknownRemote(2)));
+}
+
+#[test]
+fn servers_answer_subscriptions_with_a_snapshot() {
+  let readings = Hasher::hash_str("readings");
+  let remote = Core::new(100, 10);
+  let (server_end, mut client_end) = MemoryTransport::pair();
+  let mut server = MirrorServer::new(server_end);
+  client_end.send(MirrorMessage::Subscribe{table: readings});
+  server.serve(&remote);
+  assert_eq!(client_end.receive(), Some(MirrorMessage::Snapshot{table: readings, contents: None}));
+}
+
+#[test]
+fn servers_send_each_table_its_own_changes() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut remote = Core::new(100, 10);
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+  ]));
+  let (server_end, mut client_end) = MemoryTransport::pair();
+  let mut server = MirrorServer::new(server_end);
+  client_end.send(MirrorMessage::Subscribe{table: x});
+  server.serve(&remote);
+  client_end.receive();
+  remote.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(1))));
+  client_end.send(MirrorMessage::Subscribe{table: y});
+  server.serve(&remote);
+  client_end.receive();
+  assert_eq!(client_end.receive(), Some(MirrorMessage::Changes{table: x, changes: vec![set(x, 1, 1, Value::from_u64(1))]}));
+  remote.process_transaction(&Transaction::from_changeset(vec![
+    set(x, 1, 1, Value::from_u64(2)),
+    set(y, 1, 1, Value::from_u64(3)),
+  ]));
+  server.serve(&remote);
+  let mut received = vec![client_end.receive().unwrap(), client_end.receive().unwrap()];
+  received.sort_by_key(|message| match message {
+    MirrorMessage::Changes{table, ..} => *table,
+    _ => 0,
+  });
+  let mut expected = vec![
+    MirrorMessage::Changes{table: x, changes: vec![
+      Change::Remove{table: x, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(1)},
+      set(x, 1, 1, Value::from_u64(2)),
+    ]},
+    MirrorMessage::Changes{table: y, changes: vec![set(y, 1, 1, Value::from_u64(3))]},
+  ];
+  expected.sort_by_key(|message| match message {
+    MirrorMessage::Changes{table, ..} => *table,
+    _ => 0,
+  });
+  assert_eq!(received, expected);
+  assert_eq!(client_end.receive(), None);
+}
@@ -1,7 +1,113 @@
 extern crate mech_core;
+#[macro_use]
+extern crate proptest;
 
 use mech_core::Hasher;
 use mech_core::{Core, Transaction, Change};
 use mech_core::{Function};
-use mech_core::{Value};
-use mech_core::Block;
\ No newline at end of file
+use mech_core::{Value, Column};
+use mech_core::Block;
+use mech_core::{Table, Quantity, QuantityMath, make_quantity, ErrorType};
+use mech_core::{math_add, math_subtract, math_multiply, math_divide};
+use mech_core::{compare_less_than, compare_less_than_equal, compare_equal, logic_and, logic_or};
+use proptest::prelude::*;
+
+type Math = fn(&Table, &Column, &Column, &Table, &Column, &Column, &mut Table, &mut Vec<ErrorType>);
+type Compare = fn(&Table, &Column, &Column, &Table, &Column, &Column, &mut Table);
+
+fn column_table(values: Vec<Value>) -> Table {
+  let mut table = Table::new(0, values.len() as u64, 1);
+  table.data[0] = Column::from_values(values);
+  table
+}
+
+fn numbers(quantities: &Vec<Quantity>) -> Table {
+  column_table(quantities.iter().map(|x| Value::from_quantity(*x)).collect())
+}
+
+fn run_math(op: Math, lhs: &Table, rhs: &Table) -> Vec<Value> {
+  let empty = Column::new(0);
+  let mut out = Table::new(0, 0, 0);
+  let mut errors = Vec::new();
+  op(lhs, &empty, &empty, rhs, &empty, &empty, &mut out, &mut errors);
+  assert!(errors.is_empty());
+  out.data.remove(0).to_values()
+}
+
+fn run_compare(op: Compare, lhs: &Table, rhs: &Table) -> Vec<Value> {
+  let empty = Column::new(0);
+  let mut out = Table::new(0, 0, 0);
+  op(lhs, &empty, &empty, rhs, &empty, &empty, &mut out);
+  out.data.remove(0).to_values()
+}
+
+// Whole numbers go through the kernels, anything with a scale goes cell by 
+// cell, and the two have to agree with quantity math
+fn quantity() -> impl Strategy<Value = Quantity> {
+  prop_oneof![
+    (-100000i64..100000).prop_map(|x| make_quantity(x, 0, 0)),
+    (-1000i64..1000, -2i64..3).prop_map(|(x, range)| make_quantity(x, range, 0)),
+  ]
+}
+
+fn pairs() -> impl Strategy<Value = (Vec<Quantity>, Vec<Quantity>)> {
+  (1usize..40).prop_flat_map(|length| {
+    (proptest::collection::vec(quantity(), length), proptest::collection::vec(quantity(), length))
+  })
+}
+
+proptest! {
+  #[test]
+  fn kernels_match_quantity_math((lhs, rhs) in pairs(), uniform in any::<bool>()) {
+    // Either every cell is a whole number or the column falls back
+    let (lhs, rhs) = if uniform {
+      (lhs.iter().map(|x| make_quantity(x.mantissa(), 0, 0)).collect(), rhs.iter().map(|x| make_quantity(x.mantissa(), 0, 0)).collect())
+    } else {
+      (lhs, rhs)
+    };
+    let (lhs_table, rhs_table) = (numbers(&lhs), numbers(&rhs));
+    let expected = |f: &Fn(Quantity, Quantity) -> Quantity| -> Vec<Value> {
+      lhs.iter().zip(rhs.iter()).map(|(x, y)| Value::from_quantity(f(*x, *y))).collect()
+    };
+    prop_assert_eq!(run_math(math_add, &lhs_table, &rhs_table), expected(&|x, y| x.add(y).unwrap()));
+    prop_assert_eq!(run_math(math_subtract, &lhs_table, &rhs_table), expected(&|x, y| x.sub(y).unwrap()));
+    prop_assert_eq!(run_math(math_multiply, &lhs_table, &rhs_table), expected(&|x, y| x.multiply(y).unwrap()));
+    if rhs.iter().all(|y| y.mantissa() != 0) {
+      prop_assert_eq!(run_math(math_divide, &lhs_table, &rhs_table), expected(&|x, y| x.divide(y).unwrap()));
+    }
+    let compared = |f: &Fn(Quantity, Quantity) -> bool| -> Vec<Value> {
+      lhs.iter().zip(rhs.iter()).map(|(x, y)| Value::Bool(f(*x, *y))).collect()
+    };
+    prop_assert_eq!(run_compare(compare_less_than, &lhs_table, &rhs_table), compared(&|x, y| x.less_than(y)));
+    prop_assert_eq!(run_compare(compare_less_than_equal, &lhs_table, &rhs_table), compared(&|x, y| x.less_than_equal(y)));
+    prop_assert_eq!(run_compare(compare_equal, &lhs_table, &rhs_table), compared(&|x, y| x.equal(y)));
+  }
+
+  #[test]
+  fn kernels_broadcast_scalars(lhs in proptest::collection::vec(-1000i64..1000, 1..40), scalar in -1000i64..1000) {
+    let lhs: Vec<Quantity> = lhs.into_iter().map(|x| make_quantity(x, 0, 0)).collect();
+    let scalar = make_quantity(scalar, 0, 0);
+    let (lhs_table, scalar_table) = (numbers(&lhs), numbers(&vec![scalar]));
+    let right: Vec<Value> = lhs.iter().map(|x| Value::from_quantity(x.sub(scalar).unwrap())).collect();
+    let left: Vec<Value> = lhs.iter().map(|x| Value::from_quantity(scalar.sub(*x).unwrap())).collect();
+    prop_assert_eq!(run_math(math_subtract, &lhs_table, &scalar_table), right);
+    prop_assert_eq!(run_math(math_subtract, &scalar_table, &lhs_table), left);
+  }
+
+  #[test]
+  fn logic_kernels_match(values in proptest::collection::vec((any::<bool>(), any::<bool>()), 1..40)) {
+    let lhs = column_table(values.iter().map(|(x, _)| Value::Bool(*x)).collect());
+    let rhs = column_table(values.iter().map(|(_, y)| Value::Bool(*y)).collect());
+    let and: Vec<Value> = values.iter().map(|(x, y)| Value::Bool(*x && *y)).collect();
+    let or: Vec<Value> = values.iter().map(|(x, y)| Value::Bool(*x || *y)).collect();
+    prop_assert_eq!(run_compare(logic_and, &lhs, &rhs), and);
+    prop_assert_eq!(run_compare(logic_or, &lhs, &rhs), or);
+  }
+}
+
+#[test]
+fn empty_cells_fall_back() {
+  let lhs = column_table(vec![Value::from_u64(1), Value::Empty, Value::from_u64(3)]);
+  let rhs = column_table(vec![Value::from_u64(10), Value::from_u64(20), Value::from_u64(30)]);
+  assert_eq!(run_math(math_add, &lhs, &rhs), vec![Value::from_u64(11), Value::Empty, Value::from_u64(33)]);
+}
@@ -0,0 +1,166 @@
+extern crate mech_core;
+
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, Hasher, ErrorType};
+use mech_core::{Block, Constraint, Function, make_quantity};
+use mech_core::{Primary, Follower, MemoryTransport, Message, Transport};
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+// #y = #x * 2
+fn make_doubling_block(x: u64, y: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+fn start(primary: &mut Core, x: u64, y: u64) {
+  primary.register_blocks(vec![make_doubling_block(x, y)]);
+  primary.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    set(x, 1, 1, Value::from_u64(1)),
+    set(y, 1, 1, Value::from_u64(0)),
+  ]));
+}
+
+fn value(core: &Core, table: u64) -> Value {
+  core.store.get_table(table).unwrap().data[0].value(0)
+}
+
+#[test]
+fn followers_receive_block_output() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let (primary_end, follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(100, 10);
+  let mut link = Primary::new(primary_end);
+  let mut follower = Core::new(100, 10);
+  let mut replica = Follower::new(follower_end);
+  start(&mut primary, x, y);
+  primary.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(21))));
+  link.publish(&primary).unwrap();
+  assert_eq!(replica.sync(&mut follower).unwrap(), primary.store.changes_count);
+  // The follower has no blocks, but gets what the primary's blocks computed
+  assert_eq!(value(&follower, y), Value::from_u64(42));
+  assert_eq!(replica.hash, link.hash);
+  assert_eq!(follower.store.tables.diff(&primary.store.tables), vec![]);
+}
+
+#[test]
+fn followers_do_not_run_their_blocks() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let (primary_end, follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(100, 10);
+  let mut link = Primary::new(primary_end);
+  let mut follower = Core::new(100, 10);
+  let mut replica = Follower::new(follower_end);
+  // Had this run on the follower, #x would come out different than the primary's
+  follower.register_blocks(vec![make_doubling_block(y, x)]);
+  start(&mut primary, x, y);
+  link.publish(&primary).unwrap();
+  replica.sync(&mut follower).unwrap();
+  for n in 2..5 {
+    primary.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(n))));
+    link.publish(&primary).unwrap();
+    replica.sync(&mut follower).unwrap();
+    assert_eq!(value(&follower, x), Value::from_u64(n));
+    assert_eq!(value(&follower, y), Value::from_u64(n * 2));
+  }
+  assert_eq!(follower.store.changes_count, primary.store.changes_count);
+  assert_eq!(replica.hash, link.hash);
+}
+
+#[test]
+fn followers_catch_up_after_lost_messages() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let (primary_end, follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(100, 10);
+  let mut link = Primary::new(primary_end);
+  let mut follower = Core::new(100, 10);
+  let mut replica = Follower::new(follower_end);
+  start(&mut primary, x, y);
+  link.publish(&primary).unwrap();
+  replica.sync(&mut follower).unwrap();
+  primary.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(2))));
+  link.publish(&primary).unwrap();
+  link.transport.drop_in_flight();
+  primary.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(3))));
+  link.publish(&primary).unwrap();
+  // The gap is noticed and the follower asks to catch up
+  assert_eq!(replica.sync(&mut follower).unwrap(), 0);
+  link.publish(&primary).unwrap();
+  replica.sync(&mut follower).unwrap();
+  assert_eq!(value(&follower, y), Value::from_u64(6));
+  assert_eq!(replica.applied, primary.store.changes_count);
+  assert_eq!(replica.hash, link.hash);
+}
+
+#[test]
+fn late_followers_start_from_the_beginning() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let (primary_end, follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(100, 10);
+  let mut link = Primary::new(primary_end);
+  start(&mut primary, x, y);
+  link.publish(&primary).unwrap();
+  link.transport.drop_in_flight();
+  let mut follower = Core::new(100, 10);
+  let mut replica = Follower::new(follower_end);
+  replica.catch_up();
+  link.publish(&primary).unwrap();
+  replica.sync(&mut follower).unwrap();
+  assert_eq!(value(&follower, y), Value::from_u64(2));
+}
+
+#[test]
+fn divergent_followers_are_detected() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let (primary_end, follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(100, 10);
+  let mut link = Primary::new(primary_end);
+  let mut follower = Core::new(100, 10);
+  let mut replica = Follower::new(follower_end);
+  start(&mut primary, x, y);
+  link.publish(&primary).unwrap();
+  replica.sync(&mut follower).unwrap();
+  // A local write on the follower puts its log out of step with the primary
+  follower.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(9))));
+  primary.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(2))));
+  link.publish(&primary).unwrap();
+  assert_eq!(replica.sync(&mut follower), Err(ErrorType::ReplicaDiverged(replica.applied)));
+  // The primary refuses to catch up a log it has never seen
+  replica.transport.send(Message::CatchUp{from: replica.applied, hash: 1234});
+  link.publish(&primary).unwrap();
+  assert_eq!(replica.sync(&mut follower), Err(ErrorType::ReplicaDiverged(replica.applied)));
+}
+
+#[test]
+fn primaries_report_changes_they_no_longer_have() {
+  let x = Hasher::hash_str("x");
+  let (primary_end, _follower_end) = MemoryTransport::pair();
+  let mut primary = Core::new(2, 1);
+  let mut link = Primary::new(primary_end);
+  primary.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    set(x, 1, 1, Value::from_u64(1)),
+    set(x, 2, 1, Value::from_u64(2)),
+  ]));
+  assert_eq!(link.publish(&primary), Err(ErrorType::ChangeNotRetained(0)));
+}
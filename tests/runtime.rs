@@ -1,12 +1,857 @@
 extern crate mech_core;
+#[macro_use]
+extern crate proptest;
 
 use mech_core::Hasher;
-use mech_core::{Core, Transaction, Change};
-use mech_core::{Runtime};
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, Table};
+use mech_core::{Runtime, Block, Constraint, Function, Parameter, Register, Trigger};
+use mech_core::{make_quantity, ErrorType, ChangeArchive, MemoryArchive, Undo};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 
 #[test]
 fn create_runtime() {
     let runtime = Runtime::new();
     assert_eq!("", "");
-}
\ No newline at end of file
+}
+
+fn set(table: u64, value: u64) -> Change {
+  Change::Set{table, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(value)}
+}
+
+// #total = #total' + #step
+fn make_running_total_block(total: u64, step: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Previous{table: total, output: TableId::Local(1)},
+    Constraint::Scan{table: TableId::Global(step), indices: vec![None, None], output: TableId::Local(2)},
+    Constraint::Function{operation: Function::Add, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(total), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+fn value_of(core: &Core, table: u64) -> Value {
+  core.store.get_table(table).unwrap().data[0].value(0)
+}
+
+#[test]
+fn previous_values_accumulate() {
+  let mut core = Core::new(100, 10);
+  let total = Hasher::hash_str("total");
+  let step = Hasher::hash_str("step");
+  core.register_blocks(vec![make_running_total_block(total, step)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: total, rows: 1, columns: 1},
+    Change::NewTable{id: step, rows: 1, columns: 1},
+    set(total, 0),
+  ]));
+  for (amount, expected) in vec![(1, 1), (2, 3), (3, 6)] {
+    core.process_transaction(&Transaction::from_change(set(step, amount)));
+    assert_eq!(value_of(&core, total), Value::from_u64(expected));
+  }
+  // Going back in time takes the previous values along with it
+  core.step_backward(1).unwrap();
+  assert_eq!(value_of(&core, total), Value::from_u64(3));
+  assert_eq!(core.store.previous.get(total).unwrap().data[0].value(0), Value::from_u64(1));
+  core.step_forward(1).unwrap();
+  assert_eq!(value_of(&core, total), Value::from_u64(6));
+  core.process_transaction(&Transaction::from_change(set(step, 4)));
+  assert_eq!(value_of(&core, total), Value::from_u64(10));
+}
+
+#[test]
+fn previous_values_start_from_the_tables_at_the_boundary() {
+  let mut core = Core::new(100, 10);
+  let total = Hasher::hash_str("total");
+  let step = Hasher::hash_str("step");
+  let other = Hasher::hash_str("other");
+  core.register_blocks(vec![make_running_total_block(total, step)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: total, rows: 1, columns: 1},
+    Change::NewTable{id: step, rows: 1, columns: 1},
+    Change::NewTable{id: other, rows: 1, columns: 1},
+    set(total, 0),
+  ]));
+  core.process_transaction(&Transaction::from_change(set(step, 2)));
+  core.process_transaction(&Transaction::from_change(set(step, 3)));
+  core.process_transaction(&Transaction::from_change(set(other, 1)));
+  core.process_transaction(&Transaction::from_change(set(other, 2)));
+  assert_eq!(core.store.previous.get(total).unwrap().data[0].value(0), Value::from_u64(5));
+  // Looking back to just after #total last changed, its previous value is
+  // from before that
+  core.step_backward(2).unwrap();
+  assert_eq!(core.store.previous.get(total).unwrap().data[0].value(0), Value::from_u64(2));
+  // Input waits until we're back to now, and starts from what #total is then
+  core.process_transaction(&Transaction::from_change(set(step, 1)));
+  assert_eq!(value_of(&core, total), Value::from_u64(5));
+  core.set_time(0).unwrap();
+  assert_eq!(value_of(&core, total), Value::from_u64(6));
+}
+
+// #sum = #x + #y
+fn make_sum_block(x: u64, y: u64, sum: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Scan{table: TableId::Global(y), indices: vec![None, None], output: TableId::Local(2)},
+    Constraint::Function{operation: Function::Add, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(sum), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+// #count = #count' + 1
+fn make_counting_block(count: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Previous{table: count, output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(1, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Add, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(count), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+fn new_tables(tables: &[u64]) -> Transaction {
+  let mut changes = Vec::new();
+  for table in tables {
+    changes.push(Change::NewTable{id: *table, rows: 1, columns: 1});
+  }
+  for table in tables {
+    changes.push(set(*table, 0));
+  }
+  Transaction::from_changeset(changes)
+}
+
+#[test]
+fn change_trigger_ignores_other_inputs() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let sum = Hasher::hash_str("sum");
+  let mut block = make_sum_block(x, y, sum);
+  block.add_trigger(Trigger::Change(vec![Register::new(x, Index::Index(0))]));
+  core.register_blocks(vec![block]);
+  core.process_transaction(&new_tables(&[x, y, sum]));
+  core.process_transaction(&Transaction::from_change(set(x, 1)));
+  assert_eq!(value_of(&core, sum), Value::from_u64(1));
+  // #y is read, but changing it doesn't run the block
+  core.process_transaction(&Transaction::from_change(set(y, 10)));
+  assert_eq!(value_of(&core, sum), Value::from_u64(1));
+  core.process_transaction(&Transaction::from_change(set(x, 2)));
+  assert_eq!(value_of(&core, sum), Value::from_u64(12));
+}
+
+#[test]
+fn rising_edge_trigger() {
+  let mut core = Core::new(100, 10);
+  let button = Hasher::hash_str("button");
+  let count = Hasher::hash_str("count");
+  let mut block = make_counting_block(count);
+  block.add_trigger(Trigger::RisingEdge(Register::new(button, Index::Index(1))));
+  core.register_blocks(vec![block]);
+  core.process_transaction(&new_tables(&[count, button]));
+  let press = |core: &mut Core, down: bool| {
+    core.process_transaction(&Transaction::from_change(Change::Set{table: button, row: Index::Index(1), column: Index::Index(1), value: Value::Bool(down)}));
+  };
+  press(&mut core, true);
+  assert_eq!(value_of(&core, count), Value::from_u64(1));
+  // Staying down isn't an edge
+  press(&mut core, true);
+  press(&mut core, false);
+  assert_eq!(value_of(&core, count), Value::from_u64(1));
+  press(&mut core, true);
+  assert_eq!(value_of(&core, count), Value::from_u64(2));
+}
+
+#[test]
+fn every_n_transactions_trigger() {
+  let mut core = Core::new(100, 10);
+  let count = Hasher::hash_str("count");
+  let other = Hasher::hash_str("other");
+  let mut block = make_counting_block(count);
+  block.add_trigger(Trigger::Every(3));
+  core.register_blocks(vec![block]);
+  core.process_transaction(&new_tables(&[count, other]));
+  for n in 1..10 {
+    core.process_transaction(&Transaction::from_change(set(other, n)));
+  }
+  // Ten transactions, so the block ran on the third, sixth and ninth
+  assert_eq!(value_of(&core, count), Value::from_u64(3));
+}
+
+#[test]
+fn every_n_transactions_counts_transactions_without_blocks() {
+  let mut core = Core::new(100, 10);
+  let count = Hasher::hash_str("count");
+  let other = Hasher::hash_str("other");
+  let mut block = make_counting_block(count);
+  block.add_trigger(Trigger::Every(3));
+  core.register_blocks(vec![block]);
+  core.process_transaction(&new_tables(&[count, other]));
+  // Replicated changes make a transaction, but don't run the network
+  core.apply_logged_changes(&[set(other, 1)]);
+  assert_eq!(value_of(&core, count), Value::from_u64(0));
+  core.process_transaction(&Transaction::from_change(set(other, 2)));
+  assert_eq!(value_of(&core, count), Value::from_u64(1));
+}
+
+// #y = #x * 2
+fn make_doubling_block(x: u64, y: u64) -> Block {
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+// The number of values written to the table since the given change
+fn writes(core: &Core, table: u64, since: usize) -> usize {
+  (since..core.store.changes_count).filter(|number| {
+    match core.store.get_change(*number) {
+      Some((Change::Set{table: written, ..}, _)) => written == table,
+      _ => false,
+    }
+  }).count()
+}
+
+#[test]
+fn blocks_run_once_in_dependency_order() {
+  let mut core = Core::new(100, 10);
+  let a = Hasher::hash_str("a");
+  let b = Hasher::hash_str("b");
+  let c = Hasher::hash_str("c");
+  // The block with the lower id reads what the other one writes
+  let mut sum = make_sum_block(a, b, c);
+  sum.id = 1;
+  let mut double = make_doubling_block(a, b);
+  double.id = 2;
+  core.register_blocks(vec![sum, double]);
+  let order: Vec<Vec<usize>> = core.runtime.schedule.iter().map(|component| component.blocks.clone()).collect();
+  assert_eq!(order, vec![vec![2], vec![1]]);
+  assert!(core.runtime.schedule.iter().all(|component| !component.feedback));
+  core.process_transaction(&new_tables(&[a, b, c]));
+  let before = core.store.changes_count;
+  core.process_transaction(&Transaction::from_change(set(a, 5)));
+  assert_eq!(value_of(&core, b), Value::from_u64(10));
+  assert_eq!(value_of(&core, c), Value::from_u64(15));
+  assert_eq!(writes(&core, c, before), 1);
+}
+
+#[test]
+fn feedback_loops_are_grouped() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let z = Hasher::hash_str("z");
+  let mut forward = make_doubling_block(x, y);
+  forward.id = 1;
+  let mut back = make_doubling_block(y, x);
+  back.id = 2;
+  let mut after = make_doubling_block(y, z);
+  after.id = 3;
+  core.register_blocks(vec![after, forward, back]);
+  assert_eq!(core.runtime.schedule.len(), 2);
+  assert_eq!(core.runtime.schedule[0].blocks, vec![1, 2]);
+  assert!(core.runtime.schedule[0].feedback);
+  assert_eq!(core.runtime.schedule[1].blocks, vec![3]);
+  assert!(!core.runtime.schedule[1].feedback);
+}
+
+#[test]
+fn long_chains_are_scheduled() {
+  let mut core = Core::new(100, 10);
+  let tables: Vec<u64> = (1..20002).collect();
+  let blocks: Vec<Block> = (0..20000).map(|n| {
+    let mut block = make_doubling_block(tables[n], tables[n + 1]);
+    // Starting from the first block walks the whole chain
+    block.id = n + 1;
+    block
+  }).collect();
+  core.register_blocks(blocks);
+  assert_eq!(core.runtime.schedule.len(), 20000);
+  assert_eq!(core.runtime.schedule[0].blocks, vec![1]);
+  assert_eq!(core.runtime.schedule[19999].blocks, vec![20000]);
+}
+
+#[test]
+fn cycles_are_reported() {
+  let mut core = Core::new(100, 10);
+  core.max_iterations = 5;
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut forward = make_doubling_block(x, y);
+  forward.id = 1;
+  let mut back = make_doubling_block(y, x);
+  back.id = 2;
+  core.register_blocks(vec![forward, back]);
+  let registers = vec![Register::new(x, Index::Index(0)), Register::new(y, Index::Index(0))];
+  assert_eq!(core.runtime.errors.len(), 0);
+  assert_eq!(core.runtime.warnings.len(), 1);
+  assert_eq!(core.runtime.warnings[0].error_id, ErrorType::CycleDetected(vec![1, 2], registers.clone()));
+  // Scheduling again doesn't warn twice
+  core.runtime.schedule_blocks();
+  assert_eq!(core.runtime.warnings.len(), 1);
+  // Each time around doubles #x, so the loop never settles
+  core.process_transaction(&new_tables(&[x, y]));
+  core.process_transaction(&Transaction::from_change(set(x, 1)));
+  assert_eq!(core.runtime.errors.last().unwrap().error_id, ErrorType::Oscillation(vec![1, 2], registers));
+}
+
+#[test]
+fn blocks_that_write_their_own_input_are_not_warned_about() {
+  let mut core = Core::new(100, 10);
+  let x = Hasher::hash_str("x");
+  core.register_blocks(vec![make_doubling_block(x, x)]);
+  assert!(core.runtime.schedule[0].feedback);
+  assert_eq!(core.runtime.warnings.len(), 0);
+}
+
+fn run_independent_blocks(threads: usize, archive: Option<Box<dyn ChangeArchive>>) -> Core {
+  // With an archive, most of the changes get evicted into it
+  let capacity = if archive.is_some() { 100 } else { 1000 };
+  let mut core = Core::new(capacity, 1000);
+  core.runtime.threads = threads;
+  core.store.archive = archive;
+  let mut blocks = Vec::new();
+  let mut tables = Vec::new();
+  for ix in 0..200 {
+    let x = Hasher::hash_str(&format!("x{}", ix));
+    let y = Hasher::hash_str(&format!("y{}", ix));
+    let z = Hasher::hash_str(&format!("z{}", ix));
+    // Each pair of blocks is a chain of two, independent of the others
+    blocks.push(make_doubling_block(x, y));
+    blocks.push(make_doubling_block(y, z));
+    tables.extend(vec![x, y, z]);
+  }
+  core.register_blocks(blocks);
+  core.process_transaction(&new_tables(&tables));
+  let mut changes = Vec::new();
+  for (ix, x) in tables.iter().step_by(3).enumerate() {
+    changes.push(set(*x, ix as u64));
+  }
+  core.process_transaction(&Transaction::from_changeset(changes));
+  core
+}
+
+#[test]
+fn parallel_blocks_match_serial() {
+  let serial = run_independent_blocks(1, None);
+  let parallel = run_independent_blocks(4, None);
+  let z = Hasher::hash_str("z7");
+  assert_eq!(value_of(&parallel, z), Value::from_u64(28));
+  assert_eq!(parallel.store.changes_count, serial.store.changes_count);
+  for number in 0..serial.store.changes_count {
+    assert_eq!(parallel.store.get_change(number), serial.store.get_change(number));
+  }
+}
+
+// Keeps evicted changes somewhere that can't be shared between threads
+#[derive(Debug)]
+struct LocalArchive {
+  changes: Rc<RefCell<HashMap<usize, (Change, Option<Undo>)>>>,
+}
+
+impl ChangeArchive for LocalArchive {
+
+  fn archive(&mut self, number: usize, change: &Change, undo: Option<&Undo>) {
+    self.changes.borrow_mut().insert(number, (change.clone(), undo.cloned()));
+  }
+
+  fn retrieve(&self, number: usize) -> Option<(Change, Option<Undo>)> {
+    self.changes.borrow().get(&number).cloned()
+  }
+
+  fn clear(&mut self) {
+    self.changes.borrow_mut().clear();
+  }
+
+}
+
+#[test]
+fn parallel_blocks_keep_the_archive() {
+  let serial = run_independent_blocks(1, Some(Box::new(MemoryArchive::new())));
+  let archived = Rc::new(RefCell::new(HashMap::new()));
+  let parallel = run_independent_blocks(4, Some(Box::new(LocalArchive{changes: archived.clone()})));
+  assert!(parallel.store.archive.is_some());
+  assert!(archived.borrow().len() > 0);
+  for number in 0..serial.store.changes_count {
+    assert_eq!(parallel.store.get_change(number), serial.store.get_change(number));
+  }
+}
+
+// #y = #x * 2, #total = stat/sum(column: #y)
+fn make_scaled_sum_block(x: u64, y: u64, total: u64) -> Block {
+  let column = Hasher::hash_str("column");
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(4), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Function{operation: Function::StatSum, parameters: vec![(TableId::Local(column), None, None), (TableId::Local(3), None, None)], output: vec![TableId::Local(4)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+    Constraint::Insert{from: (TableId::Local(4), vec![None, None]), to: (TableId::Global(total), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+proptest! {
+  // After the first run the block works from deltas, and should always agree
+  // with going over the whole table
+  #[test]
+  fn incremental_evaluation_matches_full(updates in proptest::collection::vec(proptest::collection::vec((0u64..20, 0u64..100), 1..4), 1..10)) {
+    let mut core = Core::new(1000, 10);
+    let x = Hasher::hash_str("x");
+    let y = Hasher::hash_str("y");
+    let total = Hasher::hash_str("total");
+    core.register_blocks(vec![make_scaled_sum_block(x, y, total)]);
+    let mut model = vec![0; 20];
+    let mut changes = vec![
+      Change::NewTable{id: x, rows: 20, columns: 1},
+      Change::NewTable{id: y, rows: 20, columns: 1},
+      Change::NewTable{id: total, rows: 1, columns: 1},
+    ];
+    for row in 0..20 {
+      changes.push(Change::Set{table: x, row: Index::Index(row + 1), column: Index::Index(1), value: Value::from_u64(0)});
+      changes.push(Change::Set{table: y, row: Index::Index(row + 1), column: Index::Index(1), value: Value::from_u64(0)});
+    }
+    changes.push(set(total, 0));
+    core.process_transaction(&Transaction::from_changeset(changes));
+    for transaction in updates {
+      let mut changes = Vec::new();
+      for (row, value) in transaction {
+        model[row as usize] = value;
+        changes.push(Change::Set{table: x, row: Index::Index(row + 1), column: Index::Index(1), value: Value::from_u64(value)});
+      }
+      core.process_transaction(&Transaction::from_changeset(changes));
+      let doubled = &core.store.get_table(y).unwrap().data[0];
+      for (row, value) in model.iter().enumerate() {
+        prop_assert_eq!(doubled.value(row), Value::from_u64(value * 2));
+      }
+      prop_assert_eq!(value_of(&core, total), Value::from_u64(model.iter().sum::<u64>() * 2));
+    }
+  }
+}
+
+// #total = stat/sum(column: #x), with whatever steps come in between
+fn make_summing_block(x: u64, total: u64, between: Vec<Constraint>, summed: u64) -> Block {
+  let column = Hasher::hash_str("column");
+  let mut block = Block::new();
+  let mut steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(4), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(5), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+  ];
+  steps.extend(between);
+  steps.push(Constraint::Function{operation: Function::StatSum, parameters: vec![(TableId::Local(column), None, None), (TableId::Local(summed), None, None)], output: vec![TableId::Local(5)]});
+  steps.push(Constraint::Insert{from: (TableId::Local(5), vec![None, None]), to: (TableId::Global(total), vec![None, None])});
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+// Runs the block over the tables of a core as they stand, from scratch. That's
+// the whole plan over whole tables, since it's the block's first run.
+fn run_in_full(block: Block, core: &Core, inputs: &[u64], total: u64) -> Core {
+  let mut full = Core::new(1000, 10);
+  full.register_blocks(vec![block]);
+  let mut changes = vec![Change::NewTable{id: total, rows: 1, columns: 1}, set(total, 0)];
+  for input in inputs {
+    let table = core.store.get_table(*input).unwrap();
+    changes.push(Change::NewTable{id: *input, rows: table.rows, columns: table.columns});
+    changes.extend(Table::new(*input, table.rows, table.columns).diff(table));
+  }
+  full.process_transaction(&Transaction::from_changeset(changes));
+  full
+}
+
+// The sum as the block last worked it out
+fn sum_of(core: &Core) -> Value {
+  core.runtime.blocks.values().next().unwrap().get_table(5).unwrap().data[0].value(0)
+}
+
+fn cell(table: u64, row: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(1), value}
+}
+
+fn number(value: Option<(i64, i64)>) -> Value {
+  match value {
+    Some((mantissa, range)) => Value::from_quantity(make_quantity(mantissa, range, 0)),
+    None => Value::Empty,
+  }
+}
+
+proptest! {
+  // Whether a sum is adjusted or added up again, it comes out the same as 
+  // adding up the whole table, decimals and empty cells included
+  #[test]
+  fn incremental_sums_match_full_sums(
+    start in proptest::collection::vec(proptest::option::of((-500i64..500, -2i64..1)), 6),
+    updates in proptest::collection::vec(proptest::collection::vec((1u64..7, proptest::option::of((-500i64..500, -2i64..1))), 1..4), 1..8))
+  {
+    let x = Hasher::hash_str("x");
+    let total = Hasher::hash_str("total");
+    let mut core = Core::new(1000, 10);
+    core.register_blocks(vec![make_summing_block(x, total, vec![], 1)]);
+    let mut changes = vec![
+      Change::NewTable{id: x, rows: 6, columns: 1},
+      Change::NewTable{id: total, rows: 1, columns: 1},
+      set(total, 0),
+    ];
+    for (row, value) in start.into_iter().enumerate() {
+      changes.push(cell(x, row as u64 + 1, number(value)));
+    }
+    core.process_transaction(&Transaction::from_changeset(changes));
+    for transaction in updates {
+      let changes = transaction.into_iter().map(|(row, value)| cell(x, row, number(value))).collect();
+      core.process_transaction(&Transaction::from_changeset(changes));
+      let full = run_in_full(make_summing_block(x, total, vec![], 1), &core, &[x], total);
+      prop_assert_eq!(sum_of(&core), sum_of(&full));
+    }
+  }
+}
+
+// Sets up #x = [1; 2; 3] and runs the block, then makes each set of changes
+// in turn, checking the block against a full run after each
+fn check_against_full_runs(make_block: &dyn Fn(u64, u64) -> Block, transactions: Vec<Vec<Change>>) -> Core {
+  check_against_full_runs_in(Core::new(1000, 10), make_block, transactions)
+}
+
+fn check_against_full_runs_in(mut core: Core, make_block: &dyn Fn(u64, u64) -> Block, transactions: Vec<Vec<Change>>) -> Core {
+  let x = Hasher::hash_str("x");
+  let total = Hasher::hash_str("total");
+  core.register_blocks(vec![make_block(x, total)]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 3, columns: 1},
+    Change::NewTable{id: total, rows: 1, columns: 1},
+    set(total, 0),
+    cell(x, 1, Value::from_u64(1)),
+    cell(x, 2, Value::from_u64(2)),
+    cell(x, 3, Value::from_u64(3)),
+  ]));
+  for changes in transactions {
+    core.process_transaction(&Transaction::from_changeset(changes));
+    let full = run_in_full(make_block(x, total), &core, &[x], total);
+    assert_eq!(sum_of(&core), sum_of(&full));
+  }
+  core
+}
+
+#[test]
+fn sums_start_over_when_a_table_they_read_is_replaced() {
+  let x = Hasher::hash_str("x");
+  let core = check_against_full_runs(&|x, total| make_summing_block(x, total, vec![], 1), vec![
+    vec![cell(x, 2, Value::from_u64(5))],
+    vec![
+      Change::RemoveTable{id: x, rows: 3, columns: 1, contents: vec![]},
+      Change::NewTable{id: x, rows: 2, columns: 1},
+      cell(x, 1, Value::from_u64(10)),
+      cell(x, 2, Value::from_u64(20)),
+    ],
+    vec![cell(x, 1, Value::from_u64(30))],
+  ]);
+  assert_eq!(value_of(&core, Hasher::hash_str("total")), Value::from_u64(50));
+}
+
+#[test]
+fn sums_follow_changes_the_log_no_longer_holds() {
+  let x = Hasher::hash_str("x");
+  // Each transaction pushes everything before it out of the change log
+  let core = check_against_full_runs_in(Core::new(4, 10), &|x, total| make_summing_block(x, total, vec![], 1), vec![
+    vec![cell(x, 2, Value::from_u64(5)), cell(x, 3, Value::from_u64(6)), cell(x, 1, Value::from_u64(7))],
+    vec![cell(x, 2, Value::from_u64(8)), cell(x, 3, Value::from_u64(9)), cell(x, 1, Value::from_u64(1))],
+  ]);
+  assert_eq!(value_of(&core, Hasher::hash_str("total")), Value::from_u64(18));
+}
+
+#[test]
+fn sums_count_a_row_written_twice_in_a_run_once() {
+  let x = Hasher::hash_str("x");
+  // #x is scanned into a table that's then doubled in place
+  let doubled = |x, total| make_summing_block(x, total, vec![
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(1)]},
+  ], 1);
+  let core = check_against_full_runs(&doubled, vec![
+    vec![cell(x, 2, Value::from_u64(5))],
+    vec![cell(x, 1, Value::from_u64(4)), cell(x, 3, Value::from_u64(6))],
+  ]);
+  assert_eq!(value_of(&core, Hasher::hash_str("total")), Value::from_u64(30));
+}
+
+#[test]
+fn sums_of_fused_steps_match_full_runs() {
+  let x = Hasher::hash_str("x");
+  // #x * 2 + #x, fused into one pass
+  let fused = |x, total| make_summing_block(x, total, vec![
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Function{operation: Function::Add, parameters: vec![(TableId::Local(3), None, None), (TableId::Local(1), None, None)], output: vec![TableId::Local(4)]},
+  ], 4);
+  assert!(fused(x, 0).explain().contains("Fused"));
+  let core = check_against_full_runs(&fused, vec![
+    vec![cell(x, 2, Value::from_u64(5))],
+    vec![cell(x, 3, Value::from_str("three"))],
+    vec![cell(x, 3, Value::from_u64(1))],
+  ]);
+  assert_eq!(value_of(&core, Hasher::hash_str("total")), Value::from_u64(21));
+}
+
+#[test]
+fn sums_of_nothing_are_empty() {
+  let x = Hasher::hash_str("x");
+  let core = check_against_full_runs(&|x, total| make_summing_block(x, total, vec![], 1), vec![
+    vec![cell(x, 1, Value::Empty), cell(x, 2, Value::Empty)],
+    vec![cell(x, 3, Value::Empty)],
+    vec![cell(x, 2, Value::from_u64(4))],
+  ]);
+  assert_eq!(value_of(&core, Hasher::hash_str("total")), Value::from_u64(4));
+}
+
+#[test]
+fn sums_without_a_table_to_sum_dont_run() {
+  let x = Hasher::hash_str("x");
+  let total = Hasher::hash_str("total");
+  let column = Hasher::hash_str("column");
+  let mut block = make_summing_block(x, total, vec![], 1);
+  block.add_constraints((String::from(""), vec![
+    Constraint::Function{operation: Function::StatSum, parameters: vec![(TableId::Local(column), None, None)], output: vec![TableId::Local(3)]},
+  ]));
+  let mut core = Core::new(1000, 10);
+  core.register_blocks(vec![block]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 1},
+    Change::NewTable{id: total, rows: 1, columns: 1},
+    cell(x, 1, Value::from_u64(1)),
+  ]));
+  core.process_transaction(&Transaction::from_change(cell(x, 1, Value::from_u64(2))));
+}
+
+#[test]
+fn blocks_start_over_when_their_plan_changes() {
+  let x = Hasher::hash_str("x");
+  let total = Hasher::hash_str("total");
+  let mut core = check_against_full_runs(&|x, total| make_summing_block(x, total, vec![], 1), vec![
+    vec![cell(x, 2, Value::from_u64(5))],
+  ]);
+  // From now on #x is doubled in place before it's summed
+  let block_id = *core.runtime.blocks.keys().next().unwrap();
+  core.runtime.blocks.get_mut(&block_id).unwrap().add_constraints((String::from(""), vec![
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(1)]},
+  ]));
+  core.process_transaction(&Transaction::from_change(cell(x, 1, Value::from_u64(2))));
+  assert_eq!(value_of(&core, total), Value::from_u64(20));
+}
+
+#[test]
+fn planner_orders_folds_shares_and_fuses() {
+  let mut core = Core::new(1000, 10);
+  let x = Hasher::hash_str("x");
+  let out = Hasher::hash_str("out");
+  let a = Hasher::hash_str("a");
+  let b = Hasher::hash_str("b");
+  let local = |id| TableId::Local(id);
+  let constant = |table, value| Constraint::Constant{table: TableId::Local(table), row: Index::Index(1), column: Index::Index(1), value: make_quantity(value, 0, 0), unit: None};
+  let math = |operation, lhs, rhs, output| Constraint::Function{operation, parameters: vec![(TableId::Local(lhs), None, None), (TableId::Local(rhs), None, None)], output: vec![TableId::Local(output)]};
+  let insert = |from, to| Constraint::Insert{from: (TableId::Local(from), vec![None, None]), to: (TableId::Global(to), vec![None, None])};
+  let mut block = Block::new();
+  let mut tables: Vec<Constraint> = (1..11).map(|id| Constraint::NewTable{id: local(id), rows: 1, columns: 1}).collect();
+  tables.push(constant(1, 3));
+  tables.push(constant(2, 4));
+  block.add_constraints((String::from("tables"), tables));
+  // Added before anything it reads is computed
+  block.add_constraints((String::from("#out = x * (3 + 4) + x"), vec![insert(5, out), math(Function::Add, 6, 4, 5), math(Function::Multiply, 4, 3, 6)]));
+  block.add_constraints((String::from("3 + 4"), vec![math(Function::Add, 1, 2, 3)]));
+  block.add_constraints((String::from("x"), vec![Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: local(4)}]));
+  block.add_constraints((String::from("unused"), vec![Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: local(7)}]));
+  block.add_constraints((String::from("#a = x - (3 + 4)"), vec![insert(9, a), math(Function::Subtract, 4, 3, 9)]));
+  block.add_constraints((String::from("#b = x - (3 + 4)"), vec![insert(10, b), math(Function::Subtract, 4, 3, 10)]));
+  // The unused scan, the constant sum and the second subtraction are gone, 
+  // and the multiply is fused into the add that reads it
+  assert_eq!(block.plan.len(), 6);
+  let plan = block.explain();
+  assert_eq!(plan.matches("Scan").count(), 1);
+  assert_eq!(plan.matches("Fxn::Subtract").count(), 1);
+  assert!(plan.contains("Fused"));
+  assert!(!plan.contains("Local(0x7)"));
+  core.register_blocks(vec![block]);
+  let mut changes = vec![Change::NewTable{id: x, rows: 3, columns: 1}];
+  for table in &[out, a, b] {
+    changes.push(Change::NewTable{id: *table, rows: 3, columns: 1});
+  }
+  for row in 1..4 {
+    changes.push(Change::Set{table: x, row: Index::Index(row), column: Index::Index(1), value: Value::from_u64(row * 10)});
+    for table in &[out, a, b] {
+      changes.push(Change::Set{table: *table, row: Index::Index(row), column: Index::Index(1), value: Value::from_u64(0)});
+    }
+  }
+  core.process_transaction(&Transaction::from_changeset(changes));
+  for row in 0..3 {
+    let x_value = (row as u64 + 1) * 10;
+    assert_eq!(core.store.get_table(out).unwrap().data[0].value(row), Value::from_u64(x_value * 7 + x_value));
+    assert_eq!(core.store.get_table(a).unwrap().data[0].value(row), Value::from_u64(x_value - 7));
+    assert_eq!(core.store.get_table(b).unwrap().data[0].value(row), Value::from_u64(x_value - 7));
+  }
+}
+
+// #out = lhs + rhs, where #a = [1 2; 3 4; 5 6], #b = [1 2 3; 4 5 6], #c = 1 
+// and #ixes = [4]
+fn make_shaped_block(out: u64, lhs: (TableId, Option<Parameter>, Option<Parameter>), rhs: (TableId, Option<Parameter>, Option<Parameter>)) -> Block {
+  let mut block = Block::new();
+  let mut steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 3, columns: 2},
+    Constraint::NewTable{id: TableId::Local(2), rows: 2, columns: 3},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(4), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(5), rows: 1, columns: 1},
+  ];
+  let constant = |table, row, column, value| Constraint::Constant{table: TableId::Local(table), row: Index::Index(row), column: Index::Index(column), value: make_quantity(value, 0, 0), unit: None};
+  for n in 0..6 {
+    steps.push(constant(1, n / 2 + 1, n % 2 + 1, n as i64 + 1));
+    steps.push(constant(2, n / 3 + 1, n % 3 + 1, n as i64 + 1));
+  }
+  steps.push(constant(3, 1, 1, 1));
+  steps.push(constant(4, 1, 1, 4));
+  steps.push(Constraint::Function{operation: Function::Add, parameters: vec![lhs, rhs], output: vec![TableId::Local(5)]});
+  steps.push(Constraint::Insert{from: (TableId::Local(5), vec![None, None]), to: (TableId::Global(out), vec![None, None])});
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+#[test]
+fn shapes_and_indices_are_checked_before_running() {
+  let out = Hasher::hash_str("out");
+  let whole = |id| (TableId::Local(id), None, None);
+  // A table plus a single value fits
+  let block = make_shaped_block(out, whole(1), whole(3));
+  assert!(block.errors.is_empty());
+
+  // A 3x2 plus a 2x3 doesn't
+  let block = make_shaped_block(out, whole(1), whole(2));
+  assert_eq!(block.errors.len(), 1);
+  assert_eq!(block.errors[0].error_id, ErrorType::DimensionMismatch(((3, 2), (2, 3))));
+  match &block.errors[0].constraint {
+    Constraint::Function{operation: Function::Add, ..} => (),
+    _ => panic!("expected the error on the add"),
+  }
+
+  // #a{#ixes, 3} is past the end of #a both ways
+  let picked = (TableId::Local(1), Some(Parameter::TableId(TableId::Local(4))), Some(Parameter::Index(Index::Index(3))));
+  let mut block = make_shaped_block(out, picked, whole(3));
+  let errors: Vec<ErrorType> = block.errors.iter().map(|error| error.error_id.clone()).collect();
+  assert_eq!(errors, vec![
+    ErrorType::IndexOutOfBounds(((4, 1), (3, 2))),
+    ErrorType::IndexOutOfBounds(((1, 3), (3, 2))),
+  ]);
+
+  // The errors are reported when the block is registered, and it never runs
+  block.id = 2;
+  let mut core = Core::new(100, 10);
+  core.process_transaction(&Transaction::from_change(Change::NewTable{id: out, rows: 1, columns: 1}));
+  core.register_blocks(vec![block]);
+  assert_eq!(core.runtime.errors.len(), 2);
+  assert!(core.runtime.errors.iter().all(|error| error.block == 2));
+  assert_eq!(value_of(&core, out), Value::Empty);
+}
+
+// #out = #x * 2 + #x. Unless it's fused, the product is pinned by an alias
+// so the planner leaves the two steps apart.
+fn make_fusable_block(x: u64, out: u64, fused: bool) -> Block {
+  let mut block = Block::new();
+  let mut steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(4), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(x), indices: vec![None, None], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Function{operation: Function::Add, parameters: vec![(TableId::Local(3), None, None), (TableId::Local(1), None, None)], output: vec![TableId::Local(4)]},
+    Constraint::Insert{from: (TableId::Local(4), vec![None, None]), to: (TableId::Global(out), vec![None, None])},
+  ];
+  if !fused {
+    steps.push(Constraint::AliasTable{table: TableId::Local(3), alias: Hasher::hash_str("product")});
+  }
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+#[test]
+fn fused_and_unfused_plans_agree() {
+  let x = Hasher::hash_str("x");
+  let out = Hasher::hash_str("out");
+  let cores: Vec<Core> = [true, false].iter().map(|fused| {
+    let block = make_fusable_block(x, out, *fused);
+    assert_eq!(block.explain().contains("Fused"), *fused);
+    let mut core = Core::new(1000, 10);
+    core.register_blocks(vec![block]);
+    let cell = |row, value| Change::Set{table: x, row: Index::Index(row), column: Index::Index(1), value};
+    let mut changes = vec![
+      Change::NewTable{id: x, rows: 4, columns: 1},
+      Change::NewTable{id: out, rows: 4, columns: 1},
+      cell(1, Value::from_u64(1)),
+      cell(3, Value::from_str("three")),
+      cell(4, Value::from_u64(4)),
+    ];
+    for row in 1..5 {
+      changes.push(Change::Set{table: out, row: Index::Index(row), column: Index::Index(1), value: Value::from_u64(0)});
+    }
+    core.process_transaction(&Transaction::from_changeset(changes));
+    // Then a row at a time
+    core.process_transaction(&Transaction::from_change(cell(1, Value::from_u64(10))));
+    core.process_transaction(&Transaction::from_change(cell(2, Value::from_u64(2))));
+    core.process_transaction(&Transaction::from_change(cell(4, Value::from_str("four"))));
+    core
+  }).collect();
+  let block = |core: &Core| core.runtime.blocks.values().next().unwrap().clone();
+  assert_eq!(cores[0].store.get_table(out), cores[1].store.get_table(out));
+  for table in 1..5 {
+    assert_eq!(block(&cores[0]).get_table(table), block(&cores[1]).get_table(table));
+  }
+  assert_eq!(cores[0].store.get_table(out).unwrap().data[0].value(0), Value::from_u64(30));
+}
+
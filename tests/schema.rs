@@ -0,0 +1,132 @@
+extern crate mech_core;
+
+use mech_core::Hasher;
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, ErrorType};
+use mech_core::{Block, Constraint, Comparator, Parameter};
+use mech_core::{Schema, ColumnSchema, Kind, make_quantity};
+
+fn set(table: u64, row: u64, column: Index, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column, value}
+}
+
+fn cell(core: &Core, table: u64, row: u64, column: u64) -> Value {
+  core.store.get_table(table).unwrap().get_value(&Index::Index(row), &Index::Index(column)).unwrap()
+}
+
+// #people = [name age weight]
+fn declare_people(core: &mut Core) -> u64 {
+  let people = Hasher::hash_str("people");
+  core.declare_schema(Schema::new(people, vec![
+    ColumnSchema{nullable: false, ..ColumnSchema::new(Hasher::hash_str("name"), Kind::String)},
+    ColumnSchema{default: Some(Value::from_u64(0)), ..ColumnSchema::new(Hasher::hash_str("age"), Kind::Number)},
+    ColumnSchema{domain: Some(1), ..ColumnSchema::new(Hasher::hash_str("weight"), Kind::Number)},
+  ]));
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: people, rows: 1, columns: 3},
+    set(people, 1, Index::Index(1), Value::from_str("Ann")),
+  ]));
+  people
+}
+
+#[test]
+fn set_changes_are_checked_against_the_schema() {
+  let mut core = Core::new(100, 10);
+  let people = declare_people(&mut core);
+  let age = Hasher::hash_str("age");
+  // Columns are named after the schema, and new rows start with the defaults
+  assert_eq!(core.store.get_table(people).unwrap().get_column_index(&Index::Alias(age)), Some(2));
+  assert_eq!(cell(&core, people, 1, 1), Value::from_str("Ann"));
+  assert_eq!(cell(&core, people, 1, 2), Value::from_u64(0));
+  assert!(core.store.errors.is_empty());
+
+  core.process_transaction(&Transaction::from_changeset(vec![
+    set(people, 1, Index::Alias(age), Value::from_str("old")),
+    set(people, 1, Index::Index(1), Value::Empty),
+    set(people, 1, Index::Index(3), Value::from_quantity(make_quantity(70, 0, 2))),
+    set(people, 1, Index::Alias(age), Value::from_u64(30)),
+  ]));
+  assert_eq!(core.store.errors, vec![
+    ErrorType::TypeMismatch(people, Index::Alias(age), Kind::Number, Some(Kind::String)),
+    ErrorType::TypeMismatch(people, Index::Index(1), Kind::String, None),
+    ErrorType::DomainMismatch(1, 2),
+  ]);
+  // Only the change that fit went in
+  assert_eq!(cell(&core, people, 1, 1), Value::from_str("Ann"));
+  assert_eq!(cell(&core, people, 1, 2), Value::from_u64(30));
+  assert_eq!(cell(&core, people, 1, 3), Value::Empty);
+
+  // Removing from a column that can't be empty is turned away as well, and
+  // errors are only kept for the transaction they happened in
+  core.process_transaction(&Transaction::from_change(
+    Change::Remove{table: people, row: Index::Index(1), column: Index::Index(1), value: Value::Empty}
+  ));
+  assert_eq!(core.store.errors, vec![ErrorType::TypeMismatch(people, Index::Index(1), Kind::String, None)]);
+  assert_eq!(cell(&core, people, 1, 1), Value::from_str("Ann"));
+
+  // A row added by a Set gets the defaults too, and they rewind with it
+  core.process_transaction(&Transaction::from_change(set(people, 2, Index::Index(1), Value::from_str("Bo"))));
+  assert_eq!(cell(&core, people, 2, 2), Value::from_u64(0));
+  assert!(core.store.errors.is_empty());
+  core.step_backward(1).unwrap();
+  assert_eq!(core.store.get_table(people).unwrap().rows, 1);
+}
+
+// #flags = #x > #y, or #flags = 5
+fn make_flag_block(flags: u64, compare: bool) -> Block {
+  let mut block = Block::new();
+  let mut steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Constant{table: TableId::Local(1), row: Index::Index(1), column: Index::Index(1), value: make_quantity(5, 0, 0), unit: None},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(3, 0, 0), unit: None},
+  ];
+  if compare {
+    steps.push(Constraint::Filter{comparator: Comparator::GreaterThan, lhs: (TableId::Local(1), None, None), rhs: (TableId::Local(2), None, None), output: TableId::Local(3)});
+    steps.push(Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(flags), vec![None, None])});
+  } else {
+    steps.push(Constraint::Insert{from: (TableId::Local(1), vec![None, None]), to: (TableId::Global(flags), vec![None, Some(Parameter::Index(Index::Index(1)))])});
+  }
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  block
+}
+
+#[test]
+fn blocks_are_checked_against_schemas_when_registered() {
+  let mut core = Core::new(100, 10);
+  let flags = Hasher::hash_str("flags");
+  core.declare_schema(Schema::new(flags, vec![ColumnSchema::new(Hasher::hash_str("on"), Kind::Bool)]));
+  core.register_blocks(vec![make_flag_block(flags, true)]);
+  assert!(core.runtime.errors.is_empty());
+  let mut numbers = make_flag_block(flags, false);
+  numbers.id = 2;
+  core.register_blocks(vec![numbers]);
+  assert_eq!(core.runtime.errors.len(), 1);
+  assert_eq!(core.runtime.errors[0].block, 2);
+  assert_eq!(core.runtime.errors[0].error_id, ErrorType::TypeMismatch(flags, Index::Index(1), Kind::Bool, Some(Kind::Number)));
+}
+
+#[test]
+fn moving_through_time_puts_back_values_the_schema_would_turn_away() {
+  let mut core = Core::new(100, 10);
+  let people = Hasher::hash_str("people");
+  let age = Hasher::hash_str("age");
+  // An age from before there was a schema for it
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: people, rows: 1, columns: 1},
+    set(people, 1, Index::Index(1), Value::from_str("old")),
+  ]));
+  core.declare_schema(Schema::new(people, vec![ColumnSchema::new(age, Kind::Number)]));
+  core.process_transaction(&Transaction::from_change(set(people, 1, Index::Alias(age), Value::from_u64(30))));
+  assert!(core.store.errors.is_empty());
+
+  // Replaying the age on the way forward puts it back as it was
+  core.step_backward(2).unwrap();
+  core.step_forward(1).unwrap();
+  assert_eq!(cell(&core, people, 1, 1), Value::from_str("old"));
+  assert!(core.store.errors.is_empty());
+  core.step_forward(1).unwrap();
+  assert_eq!(cell(&core, people, 1, 1), Value::from_u64(30));
+}
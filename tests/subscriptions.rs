@@ -0,0 +1,91 @@
+extern crate mech_core;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use mech_core::{Core, Transaction, Change, Value, Index, Register, CellChange, Hasher};
+
+fn set(table: u64, row: u64, column: u64, value: Value) -> Change {
+  Change::Set{table, row: Index::Index(row), column: Index::Index(column), value}
+}
+
+fn make_core(x: u64, y: u64) -> Core {
+  let mut core = Core::new(100, 10);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: x, rows: 1, columns: 2},
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    Change::RenameColumn{table: x, column_ix: 2, column_alias: Hasher::hash_str("b")},
+    set(x, 1, 1, Value::from_u64(1)),
+  ]));
+  core
+}
+
+#[test]
+fn subscribers_hear_old_and_new_values() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut core = make_core(x, y);
+  let heard = Rc::new(RefCell::new(Vec::new()));
+  let log = heard.clone();
+  core.subscribe(Register::new(x, Index::Index(0)), move |changes: &[CellChange]| {
+    log.borrow_mut().push(changes.to_vec());
+  });
+  core.process_transaction(&Transaction::from_changeset(vec![
+    set(x, 1, 1, Value::from_u64(2)),
+    set(x, 2, 2, Value::Bool(true)),
+  ]));
+  // Changes to other tables don't concern this subscriber
+  core.process_transaction(&Transaction::from_change(set(y, 1, 1, Value::from_u64(7))));
+  assert_eq!(*heard.borrow(), vec![vec![
+    CellChange{table: x, row: 1, column: 1, old: Value::from_u64(1), new: Value::from_u64(2)},
+    CellChange{table: x, row: 2, column: 2, old: Value::Empty, new: Value::Bool(true)},
+  ]]);
+}
+
+#[test]
+fn column_subscribers_only_hear_their_column() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut core = make_core(x, y);
+  let (id, receiver) = core.subscribe_channel(Register::new(x, Index::Alias(Hasher::hash_str("b"))));
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(5))));
+  assert!(receiver.try_recv().is_err());
+  core.process_transaction(&Transaction::from_change(set(x, 1, 2, Value::from_u64(6))));
+  assert_eq!(receiver.try_recv().unwrap(), vec![
+    CellChange{table: x, row: 1, column: 2, old: Value::Empty, new: Value::from_u64(6)},
+  ]);
+  assert!(core.unsubscribe(id));
+  core.process_transaction(&Transaction::from_change(set(x, 1, 2, Value::from_u64(8))));
+  assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn dropped_channels_end_their_subscription() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut core = make_core(x, y);
+  let (id, receiver) = core.subscribe_channel(Register::new(x, Index::Index(0)));
+  drop(receiver);
+  core.process_transaction(&Transaction::from_change(set(x, 1, 1, Value::from_u64(5))));
+  assert!(!core.unsubscribe(id));
+}
+
+#[test]
+fn subscribers_hear_each_cell_once_per_transaction() {
+  let x = Hasher::hash_str("x");
+  let y = Hasher::hash_str("y");
+  let mut core = make_core(x, y);
+  let (_, receiver) = core.subscribe_channel(Register::new(x, Index::Index(0)));
+  core.process_transaction(&Transaction::from_changeset(vec![
+    set(x, 1, 1, Value::from_u64(2)),
+    set(x, 1, 1, Value::from_u64(3)),
+    set(x, 1, 2, Value::from_u64(4)),
+    set(x, 1, 2, Value::Empty),
+  ]));
+  assert_eq!(receiver.try_recv().unwrap(), vec![
+    CellChange{table: x, row: 1, column: 1, old: Value::from_u64(1), new: Value::from_u64(3)},
+  ]);
+  core.process_transaction(&Transaction::from_change(Change::RemoveTable{id: x, rows: 1, columns: 2, contents: vec![]}));
+  assert_eq!(receiver.try_recv().unwrap(), vec![
+    CellChange{table: x, row: 1, column: 1, old: Value::from_u64(3), new: Value::Empty},
+  ]);
+}
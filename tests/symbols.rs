@@ -0,0 +1,75 @@
+extern crate mech_core;
+extern crate serde_json;
+
+use mech_core::{Symbol, Value, Table, Index, sweep_symbols, interned_symbols};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+fn hash(symbol: &Symbol) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  symbol.hash(&mut hasher);
+  hasher.finish()
+}
+
+// The pool is shared by the whole process, so everything that counts what's
+// in it happens in this one test
+#[test]
+fn strings_are_interned_and_reclaimed() {
+  let apple = Symbol::new("apple");
+  let also_apple = Symbol::from(String::from("apple"));
+  assert_eq!(apple, also_apple);
+  assert_eq!(apple.id(), also_apple.id());
+  assert_eq!(hash(&apple), hash(&also_apple));
+  assert_ne!(apple, Symbol::new("pear"));
+  // Symbols are ordered by their strings
+  assert!(apple < Symbol::new("banana"));
+  assert!(Symbol::new("fig") > apple);
+  assert_eq!(apple.as_str(), "apple");
+  assert_eq!(format!("{:?} {}", apple, apple), "\"apple\" apple");
+
+  // Values serialize as their strings, and come back interned
+  let value = Value::from_str("apple");
+  let json = serde_json::to_string(&value).unwrap();
+  assert_eq!(json, "{\"String\":\"apple\"}");
+  match serde_json::from_str::<Value>(&json).unwrap() {
+    Value::String(symbol) => assert_eq!(symbol, apple),
+    _ => panic!("expected a string"),
+  }
+
+  // Every cell holding the same string shares it
+  sweep_symbols();
+  let before = interned_symbols();
+  {
+    let mut table = Table::new(0, 100, 2);
+    for row in 1..101 {
+      table.set_cell(&Index::Index(row), &Index::Index(1), Value::from_str("only in this table"));
+      table.set_cell(&Index::Index(row), &Index::Index(2), Value::from_str("apple"));
+    }
+    let copy = table.clone();
+    assert_eq!(copy.get_value(&Index::Index(50), &Index::Index(1)), Some(Value::from_str("only in this table")));
+    assert_eq!(interned_symbols(), before + 1);
+  }
+
+  // Strings nothing refers to are dropped by a sweep, and the rest stay
+  assert_eq!(sweep_symbols(), 1);
+  assert_eq!(interned_symbols(), before);
+  assert_eq!(Symbol::new("apple"), apple);
+
+  // Overwriting a cell lets go of the string it held
+  let mut table = Table::new(0, 1, 1);
+  let mut overwrite = |table: &mut Table, times: u64| {
+    for n in 0..times {
+      table.set_cell(&Index::Index(1), &Index::Index(1), Value::from_string(format!("overwritten {}", n)));
+    }
+  };
+  overwrite(&mut table, 10);
+  let heap_size = table.heap_size();
+  overwrite(&mut table, 5000);
+  assert_eq!(table.heap_size(), heap_size);
+  sweep_symbols();
+  assert_eq!(interned_symbols(), before + 1);
+  // Emptying it lets go too
+  table.set_cell(&Index::Index(1), &Index::Index(1), Value::Empty);
+  sweep_symbols();
+  assert_eq!(interned_symbols(), before);
+}
@@ -1,7 +1,13 @@
 extern crate mech_core;
+extern crate serde_json;
+extern crate bincode;
+#[macro_use]
+extern crate proptest;
 
-use mech_core::{Table, Value, Index};
+use mech_core::{Table, Value, Index, Aliases, TableIndex, Interner, Transaction, Change, Column};
+use std::mem::size_of;
 use mech_core::Hasher;
+use proptest::prelude::*;
 
 fn make_table() -> Table {
 
@@ -38,7 +44,7 @@ fn get_a_row() {
 fn index_into_cell() {
     let mut table = make_table();
     let score = table.index(&Index::Index(1), &Index::Index(3));
-    assert_eq!(score, Some(&Value::from_u64(83)));
+    assert_eq!(score, Some(Value::from_u64(83)));
 } 
 
 #[test]
@@ -46,5 +52,216 @@ fn clear_cell() {
     let mut table = make_table();
     table.clear_cell(&Index::Index(1), &Index::Index(3));
     let score = table.index(&Index::Index(1), &Index::Index(3));
-    assert_eq!(score, Some(&Value::Empty));
-}
\ No newline at end of file
+    assert_eq!(score, Some(Value::Empty));
+}
+
+#[test]
+fn get_a_column() {
+    let mut table = make_table();
+    assert_eq!(table.get_column(&Index::Index(3)), Some(vec![Value::from_u64(83), Value::from_u64(99)]));
+    assert_eq!(table.get_column_data(&Index::Index(3)).unwrap().to_values(), vec![Value::from_u64(83), Value::from_u64(99)]);
+    // Columns changed directly show up too
+    table.data[2].set(0, Value::from_u64(84));
+    assert_eq!(table.get_column(&Index::Index(3)), Some(vec![Value::from_u64(84), Value::from_u64(99)]));
+    assert_eq!(table.index(&Index::Index(1), &Index::Index(3)), Some(Value::from_u64(84)));
+    assert_eq!(table.get_value(&Index::Index(1), &Index::Index(3)), Some(Value::from_u64(84)));
+}
+
+// ## Serialization
+
+fn value_strategy() -> impl Strategy<Value = Value> {
+  prop_oneof![
+    any::<u64>().prop_map(Value::Number),
+    ".*".prop_map(Value::from_string),
+    any::<bool>().prop_map(Value::Bool),
+    any::<u64>().prop_map(Value::Reference),
+    Just(Value::Empty),
+  ]
+}
+
+fn table_strategy() -> impl Strategy<Value = Table> {
+  (any::<u64>(), 1..6u64, 1..6u64).prop_flat_map(|(id, rows, columns)| {
+    (
+      Just(id),
+      proptest::collection::vec(value_strategy(), (rows * columns) as usize),
+      proptest::collection::vec(any::<u64>(), 0..columns as usize),
+      proptest::collection::vec((any::<u64>(), 1..rows + 1), 0..rows as usize),
+      Just((rows, columns)),
+    )
+  }).prop_map(|(id, values, column_aliases, row_aliases, (rows, columns))| {
+    let mut table = Table::new(id, rows, columns);
+    for (ix, value) in values.into_iter().enumerate() {
+      let row = ix as u64 % rows + 1;
+      let column = ix as u64 / rows + 1;
+      table.set_cell(&Index::Index(row), &Index::Index(column), value);
+    }
+    for (ix, alias) in column_aliases.into_iter().enumerate() {
+      table.set_column_alias(alias, ix as u64 + 1);
+    }
+    for (alias, ix) in row_aliases {
+      table.row_aliases.insert(alias, ix);
+    }
+    table
+  })
+}
+
+proptest! {
+
+  #[test]
+  fn value_json_round_trip(value in value_strategy()) {
+    let json = serde_json::to_string(&value).unwrap();
+    prop_assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+  }
+
+  #[test]
+  fn table_json_round_trip(table in table_strategy()) {
+    let json = serde_json::to_string(&table).unwrap();
+    prop_assert_eq!(serde_json::from_str::<Table>(&json).unwrap(), table);
+  }
+
+  #[test]
+  fn table_bincode_round_trip(table in table_strategy()) {
+    let bytes = bincode::serialize(&table).unwrap();
+    prop_assert_eq!(bincode::deserialize::<Table>(&bytes).unwrap(), table);
+  }
+
+  #[test]
+  fn table_index_round_trip(tables in proptest::collection::vec(table_strategy(), 0..4),
+                            alias in any::<u64>()) {
+    let mut index = TableIndex::new(tables.len());
+    for table in tables {
+      let id = table.id;
+      index.insert(table);
+      index.add_alias(id, alias).ok();
+      index.changed_this_round.insert((id, Index::Index(0)));
+    }
+    let json = serde_json::to_string(&index).unwrap();
+    prop_assert_eq!(serde_json::from_str::<TableIndex>(&json).unwrap(), index.clone());
+    let bytes = bincode::serialize(&index).unwrap();
+    prop_assert_eq!(bincode::deserialize::<TableIndex>(&bytes).unwrap(), index);
+  }
+
+  #[test]
+  fn table_diff_turns_one_table_into_another(before in table_strategy(), mut after in table_strategy()) {
+    after.id = before.id;
+    let mut store = Interner::new(100, 1);
+    store.tables.insert(before.clone());
+    store.process_transaction(&Transaction::from_changeset(before.diff(&after)));
+    let table = store.get_table(before.id).unwrap();
+    for column in 1..table.columns + 1 {
+      for row in 1..table.rows + 1 {
+        let expected = if row <= after.rows && column <= after.columns {
+          after.data[column as usize - 1].value(row as usize - 1)
+        } else {
+          Value::Empty
+        };
+        prop_assert_eq!(table.index(&Index::Index(row), &Index::Index(column)), Some(expected));
+      }
+    }
+    let cells_left = table.diff(&after).into_iter().filter(|change| match change {
+      Change::RenameColumn{..} => false,
+      _ => true,
+    }).count();
+    prop_assert_eq!(cells_left, 0);
+  }
+
+}
+
+#[test]
+fn aliases_round_trip() {
+  let mut aliases = Aliases::new();
+  aliases.insert(0xdeadbeef, 1);
+  aliases.insert(u64::max_value(), 2);
+  let json = serde_json::to_string(&aliases).unwrap();
+  assert_eq!(json, "{\"3735928559\":1,\"18446744073709551615\":2}");
+  assert_eq!(serde_json::from_str::<Aliases>(&json).unwrap(), aliases);
+}
+
+#[test]
+fn table_diff_is_minimal() {
+  let table = make_table();
+  let mut other = make_table();
+  assert_eq!(table.diff(&other), vec![]);
+  other.set_cell(&Index::Index(2), &Index::Index(3), Value::from_u64(100));
+  other.clear_cell(&Index::Index(1), &Index::Index(1));
+  other.set_column_alias(Hasher::hash_str("name"), 1);
+  assert_eq!(table.diff(&other), vec![
+    Change::RenameColumn{table: table.id, column_ix: 1, column_alias: Hasher::hash_str("name")},
+    Change::Remove{table: table.id, row: Index::Index(1), column: Index::Index(1), value: Value::from_str("Mark")},
+    Change::Set{table: table.id, row: Index::Index(2), column: Index::Index(3), value: Value::from_u64(100)},
+  ]);
+}
+
+#[test]
+fn table_index_diff() {
+  let students = make_table();
+  let mut before = TableIndex::new(2);
+  before.insert(students.clone());
+  before.insert(Table::new(1, 1, 1));
+  let mut after = TableIndex::new(2);
+  after.insert(students);
+  let mut fresh = Table::new(2, 1, 2);
+  fresh.set_cell(&Index::Index(1), &Index::Index(2), Value::Bool(true));
+  after.insert(fresh);
+  assert_eq!(before.diff(&after), vec![
+    Change::RemoveTable{id: 1, rows: 1, columns: 1, contents: Vec::new()},
+    Change::NewTable{id: 2, rows: 1, columns: 2},
+    Change::Set{table: 2, row: Index::Index(1), column: Index::Index(2), value: Value::Bool(true)},
+  ]);
+}
+
+// ## Columns
+
+proptest! {
+
+  #[test]
+  fn column_matches_a_vector_of_values(len in 0..20usize, cells in proptest::collection::vec((0..25usize, value_strategy()), 0..60)) {
+    let mut column = Column::new(len);
+    let mut values = vec![Value::Empty; len];
+    for (ix, value) in cells {
+      if ix < values.len() {
+        prop_assert_eq!(column.set(ix, value.clone()), values[ix].clone());
+        values[ix] = value;
+      } else {
+        column.push(value.clone());
+        values.push(value);
+      }
+      prop_assert_eq!(column.len(), values.len());
+    }
+    prop_assert_eq!(column.to_values(), values.clone());
+    let half = values.len() / 2;
+    column.truncate(half);
+    values.truncate(half);
+    column.resize(half + 3);
+    values.resize(half + 3, Value::Empty);
+    prop_assert_eq!(column.to_values(), values);
+  }
+
+}
+
+#[test]
+fn columns_are_stored_by_kind() {
+  let mut table = Table::new(0, 1000, 3);
+  for row in 1..1001 {
+    let even = row % 2 == 0;
+    table.set_cell(&Index::Index(row), &Index::Index(1), Value::from_u64(row));
+    table.set_cell(&Index::Index(row), &Index::Index(2), Value::Bool(even));
+    table.set_cell(&Index::Index(row), &Index::Index(3), Value::from_str(if even { "even" } else { "odd" }));
+  }
+  assert!(match table.data[0] { Column::Number(..) => true, _ => false });
+  assert!(match table.data[1] { Column::Bool(..) => true, _ => false });
+  assert!(match table.data[2] { Column::String(..) => true, _ => false });
+  // Numbers take a little over a quantity per cell, instead of a whole Value
+  assert!(table.data[0].heap_size() < 1000 * 9);
+  assert!(table.heap_size() < 1000 * size_of::<Value>());
+  // Emptying a cell keeps the column's kind, but a different kind of value
+  // turns it back into Values
+  table.clear_cell(&Index::Index(1), &Index::Index(1));
+  assert!(match table.data[0] { Column::Number(..) => true, _ => false });
+  assert_eq!(table.data[0].numbers(), None);
+  table.set_cell(&Index::Index(2), &Index::Index(1), Value::from_str("two"));
+  assert!(match table.data[0] { Column::Mixed(..) => true, _ => false });
+  assert_eq!(table.get_value(&Index::Index(1), &Index::Index(1)), Some(Value::Empty));
+  assert_eq!(table.get_value(&Index::Index(2), &Index::Index(1)), Some(Value::from_str("two")));
+  assert_eq!(table.get_value(&Index::Index(3), &Index::Index(1)), Some(Value::from_u64(3)));
+}
@@ -0,0 +1,91 @@
+extern crate mech_core;
+
+use mech_core::{Core, Transaction, Change, Value, Index, TableId, Hasher, Parameter};
+use mech_core::{Block, Constraint, Function, make_quantity};
+use mech_core::{ManualTime, TimerMachine, timer_table, period_column, ticks_column};
+
+fn set_period(core: &mut Core, row: u64, period: Value) {
+  core.process_transaction(&Transaction::from_change(Change::Set{
+    table: timer_table(), row: Index::Index(row), column: Index::Alias(period_column()), value: period,
+  }));
+}
+
+fn ticks(core: &Core, row: u64) -> Value {
+  core.store.get_table(timer_table()).unwrap()
+    .index(&Index::Index(row), &Index::Alias(ticks_column())).unwrap_or(Value::Empty)
+}
+
+fn make_core(time: &ManualTime) -> Core {
+  let mut core = Core::new(100, 10);
+  core.register_machine(Box::new(TimerMachine::new(Box::new(time.clone())))).unwrap();
+  core
+}
+
+#[test]
+fn timers_tick_with_the_clock() {
+  let time = ManualTime::new(1000);
+  let mut core = make_core(&time);
+  set_period(&mut core, 1, Value::from_u64(100));
+  core.poll_machines().unwrap();
+  assert_eq!(ticks(&core, 1), Value::Empty);
+  time.advance(100);
+  assert_eq!(core.poll_machines(), Ok(1));
+  assert_eq!(ticks(&core, 1), Value::from_u64(1));
+  // Missed ticks are caught up all at once
+  time.advance(250);
+  core.poll_machines().unwrap();
+  assert_eq!(ticks(&core, 1), Value::from_u64(3));
+  time.advance(50);
+  core.poll_machines().unwrap();
+  assert_eq!(ticks(&core, 1), Value::from_u64(4));
+}
+
+#[test]
+fn timers_keep_their_own_periods() {
+  let time = ManualTime::new(0);
+  let mut core = make_core(&time);
+  set_period(&mut core, 1, Value::from_u64(10));
+  set_period(&mut core, 2, Value::from_u64(25));
+  time.advance(50);
+  core.poll_machines().unwrap();
+  assert_eq!(ticks(&core, 1), Value::from_u64(5));
+  assert_eq!(ticks(&core, 2), Value::from_u64(2));
+  // Stopping a timer leaves its ticks where they were
+  set_period(&mut core, 1, Value::Empty);
+  time.advance(50);
+  core.poll_machines().unwrap();
+  assert_eq!(ticks(&core, 1), Value::from_u64(5));
+  assert_eq!(ticks(&core, 2), Value::from_u64(4));
+}
+
+// #y = #time/timer.ticks * 2
+#[test]
+fn blocks_run_on_every_tick() {
+  let y = Hasher::hash_str("y");
+  let time = ManualTime::new(0);
+  let mut core = make_core(&time);
+  let mut block = Block::new();
+  let steps = vec![
+    Constraint::NewTable{id: TableId::Local(1), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(2), rows: 1, columns: 1},
+    Constraint::NewTable{id: TableId::Local(3), rows: 1, columns: 1},
+    Constraint::Scan{table: TableId::Global(timer_table()), indices: vec![None, Some(Parameter::Index(Index::Alias(ticks_column())))], output: TableId::Local(1)},
+    Constraint::Constant{table: TableId::Local(2), row: Index::Index(1), column: Index::Index(1), value: make_quantity(2, 0, 0), unit: None},
+    Constraint::Function{operation: Function::Multiply, parameters: vec![(TableId::Local(1), None, None), (TableId::Local(2), None, None)], output: vec![TableId::Local(3)]},
+    Constraint::Insert{from: (TableId::Local(3), vec![None, None]), to: (TableId::Global(y), vec![None, None])},
+  ];
+  for step in steps {
+    block.add_constraints((String::from(""), vec![step]));
+  }
+  core.register_blocks(vec![block]);
+  core.process_transaction(&Transaction::from_changeset(vec![
+    Change::NewTable{id: y, rows: 1, columns: 1},
+    Change::Set{table: y, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(0)},
+  ]));
+  set_period(&mut core, 1, Value::from_u64(1000));
+  for tick in 1..4 {
+    time.advance(1000);
+    core.poll_machines().unwrap();
+    assert_eq!(core.store.get_table(y).unwrap().data[0].value(0), Value::from_u64(tick * 2));
+  }
+}
@@ -0,0 +1,156 @@
+extern crate mech_core;
+#[macro_use]
+extern crate proptest;
+
+use mech_core::{Transaction, Change, Value, Index, ErrorType, WIRE_VERSION};
+use mech_core::{make_quantity, Hasher};
+use proptest::prelude::*;
+
+fn make_transaction() -> Transaction {
+  let students = Hasher::hash_str("students");
+  let name = Hasher::hash_str("name");
+  Transaction::from_changeset(vec![
+    Change::NewTable{id: students, rows: 2, columns: 2},
+    Change::RenameColumn{table: students, column_ix: 1, column_alias: name},
+    Change::Set{table: students, row: Index::Index(1), column: Index::Alias(name), value: Value::from_str("Mark")},
+    Change::Set{table: students, row: Index::Index(2), column: Index::Alias(name), value: Value::from_str("Mark")},
+    Change::Set{table: students, row: Index::Index(1), column: Index::Index(2), value: Value::from_quantity(make_quantity(-314, -2, 3))},
+    Change::Set{table: students, row: Index::Index(2), column: Index::Index(2), value: Value::Bool(true)},
+    Change::Remove{table: students, row: Index::Index(2), column: Index::Index(1), value: Value::Reference(students)},
+    Change::RemoveTable{id: name, rows: 0, columns: 0, contents: Vec::new()},
+  ])
+}
+
+#[test]
+fn wire_round_trip() {
+  let txn = make_transaction();
+  let bytes = txn.encode();
+  assert_eq!(bytes[0], WIRE_VERSION);
+  assert_eq!(Transaction::decode(&bytes), Ok(txn));
+}
+
+#[test]
+fn wire_interns_strings() {
+  let txn = make_transaction();
+  let bytes = txn.encode();
+  let occurrences = bytes.windows(4).filter(|window| window == b"Mark").count();
+  assert_eq!(occurrences, 1);
+}
+
+#[test]
+fn wire_packs_small_cells() {
+  let txn = Transaction::from_change(
+    Change::Set{table: 1, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(5)}
+  );
+  // version, strings, 4 section counts, then tag, table, row, column, value
+  assert_eq!(txn.encode().len(), 11);
+}
+
+#[test]
+fn wire_rejects_unknown_version() {
+  let mut bytes = make_transaction().encode();
+  bytes[0] = WIRE_VERSION + 1;
+  assert_eq!(Transaction::decode(&bytes), Err(ErrorType::UnsupportedWireVersion(WIRE_VERSION + 1)));
+}
+
+#[test]
+fn wire_rejects_truncated_messages() {
+  let bytes = make_transaction().encode();
+  for length in 0..bytes.len() {
+    assert!(Transaction::decode(&bytes[..length]).is_err());
+  }
+}
+
+#[test]
+fn wire_rejects_trailing_bytes() {
+  let mut bytes = make_transaction().encode();
+  bytes.push(0);
+  assert!(Transaction::decode(&bytes).is_err());
+}
+
+#[test]
+fn wire_leaves_out_contents_that_cant_fill_a_table() {
+  let id = Hasher::hash_str("gone");
+  let set = Change::Set{table: id, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(1)};
+  let rename = Change::RenameColumn{table: id, column_ix: 1, column_alias: 7};
+  let txn = Transaction::from_change(Change::RemoveTable{id, rows: 1, columns: 1, contents: vec![
+    set.clone(),
+    Change::Remove{table: id, row: Index::Index(1), column: Index::Index(1), value: Value::from_u64(1)},
+    Change::Set{table: id, row: Index::Index(0), column: Index::Alias(7), value: Value::from_u64(2)},
+    Change::RemoveTable{id, rows: 0, columns: 0, contents: Vec::new()},
+    rename.clone(),
+  ]});
+  assert_eq!(Transaction::decode(&txn.encode()), Ok(Transaction::from_change(Change::RemoveTable{id, rows: 1, columns: 1, contents: vec![set, rename]})));
+}
+
+fn index_strategy() -> impl Strategy<Value = Index> {
+  prop_oneof![
+    any::<u64>().prop_map(Index::Index),
+    (0..8u64).prop_map(Index::Index),
+    any::<u64>().prop_map(Index::Alias),
+  ]
+}
+
+fn value_strategy() -> impl Strategy<Value = Value> {
+  prop_oneof![
+    any::<u64>().prop_map(Value::Number),
+    (-1000..1000i64).prop_map(Value::from_i64),
+    "[a-z]{0,3}".prop_map(Value::from_string),
+    any::<bool>().prop_map(Value::Bool),
+    any::<u64>().prop_map(Value::Reference),
+    Just(Value::Empty),
+  ]
+}
+
+fn change_strategy() -> impl Strategy<Value = Change> {
+  prop_oneof![
+    (any::<u64>(), index_strategy(), index_strategy(), value_strategy())
+      .prop_map(|(table, row, column, value)| Change::Set{table, row, column, value}),
+    (any::<u64>(), index_strategy(), index_strategy(), value_strategy())
+      .prop_map(|(table, row, column, value)| Change::Remove{table, row, column, value}),
+    (any::<u64>(), any::<u64>(), any::<u64>())
+      .prop_map(|(id, rows, columns)| Change::NewTable{id, rows, columns}),
+    (any::<u64>(), any::<u64>(), any::<u64>())
+      .prop_map(|(table, column_ix, column_alias)| Change::RenameColumn{table, column_ix, column_alias}),
+    (any::<u64>(), any::<u64>(), any::<u64>(), proptest::collection::vec((1..8u64, 1..8u64, value_strategy()), 0..4))
+      .prop_map(|(id, rows, columns, cells)| {
+        let contents = cells.into_iter().map(|(row, column, value)| Change::Set{table: id, row: Index::Index(row), column: Index::Index(column), value}).collect();
+        Change::RemoveTable{id, rows, columns, contents}
+      }),
+  ]
+}
+
+proptest! {
+
+  #[test]
+  fn wire_round_trips_any_transaction(changes in proptest::collection::vec(change_strategy(), 0..32)) {
+    let txn = Transaction::from_changeset(changes);
+    prop_assert_eq!(Transaction::decode(&txn.encode()), Ok(txn));
+  }
+
+  #[test]
+  fn wire_decodes_arbitrary_bytes_without_panicking(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+    let _ = Transaction::decode(&bytes);
+  }
+
+  #[test]
+  fn wire_decodes_versioned_garbage_without_panicking(bytes in proptest::collection::vec(any::<u8>(), 0..256)) {
+    let mut message = vec![WIRE_VERSION];
+    message.extend(bytes);
+    if let Ok(txn) = Transaction::decode(&message) {
+      prop_assert_eq!(Transaction::decode(&txn.encode()), Ok(txn));
+    }
+  }
+
+  #[test]
+  fn wire_decodes_corrupted_messages_without_panicking(changes in proptest::collection::vec(change_strategy(), 1..8),
+                                                        flips in proptest::collection::vec((any::<usize>(), any::<u8>()), 1..4)) {
+    let mut bytes = Transaction::from_changeset(changes).encode();
+    for (position, mask) in flips {
+      let ix = position % bytes.len();
+      bytes[ix] ^= mask;
+    }
+    let _ = Transaction::decode(&bytes);
+  }
+
+}